@@ -1,6 +1,4 @@
 #![windows_subsystem = "windows"]
-// Distinguish between editor commands and keybindings.
-// Keymap: key/object map, where the object is either an editor command, or a nested keymap.
 // Extension language: lua?
 // Config: TOML?
 // Q&A:
@@ -24,15 +22,20 @@
 //}
 
 use std::{
+    process,
     sync::{Arc, Mutex},
     time::Instant,
 };
 
+use tracing::warn;
 use wrenched::{
-    buffer::{Buffer, BufferView},
+    buffer::BufferView,
     code_widget::code_view,
+    command::EditorCommand,
+    config::{get_config, set_config, Config, EditorConfig},
+    editor::Editor,
     markdown::markdown_view,
-    utils::load_font_blobs_dir,
+    utils::{load_font_blobs_dirs, LoadFontOptions},
 };
 use xilem::{
     view::{button, checkbox, flex, textbox, Axis},
@@ -47,8 +50,7 @@ struct Task {
 struct TaskList {
     next_task: String,
     tasks: Vec<Task>,
-    #[allow(dead_code)]
-    buffer: Arc<Mutex<Buffer>>,
+    editor: Editor,
     buffer_view: Arc<Mutex<BufferView>>,
 }
 
@@ -62,6 +64,25 @@ impl TaskList {
             done: false,
         });
     }
+
+    /// Executes the file `command` bound to the keys of the code view. The
+    /// view is switched to the buffer the command made current (the new or
+    /// the opened one).
+    fn execute_editor_command(&mut self, command: EditorCommand) {
+        let current = self.editor.current_buffer_id();
+        if let Err(err) = self.editor.execute(command) {
+            warn!("The editor command failed: {err}");
+            return;
+        }
+        if self.editor.quit_requested() {
+            process::exit(0);
+        }
+        if self.editor.current_buffer_id() != current {
+            if let Some(buffer_view) = self.editor.new_view() {
+                self.buffer_view = Arc::new(Mutex::new(buffer_view));
+            }
+        }
+    }
 }
 
 fn app_logic(task_list: &mut TaskList) -> impl WidgetView<TaskList> {
@@ -100,20 +121,22 @@ fn app_logic(task_list: &mut TaskList) -> impl WidgetView<TaskList> {
             flex((checkbox, delete_button)).direction(Axis::Horizontal)
         })
         .collect::<Vec<_>>();
-    //let code_view = code_view(&task_list.buffer_view, |_s: &mut TaskList| {});
+    let code_view = code_view(&task_list.buffer_view, |_s: &mut TaskList| {})
+        .on_editor_command(TaskList::execute_editor_command);
     let markdown = markdown_view("test.md".into());
 
-    //flex((first_line, tasks, code_view))
-    flex((first_line, tasks, markdown))
+    flex((first_line, tasks, code_view, markdown))
 }
 
 fn run(event_loop: EventLoopBuilder) -> eyre::Result<()> {
     let now = Instant::now();
-    let buffer = Arc::new(Mutex::new(Buffer::load("./text.txt")?));
+    let mut editor = Editor::new();
+    editor.set_ensure_trailing_newline(get_config().ensure_trailing_newline);
+    editor.execute(EditorCommand::OpenFile("./text.txt".into()))?;
     let duration = now.elapsed();
     println!("parsing text time: {}s", duration.as_secs_f32());
     //let buffer = Arc::new(Mutex::new(Buffer::from_string("super cool text")));
-    let buffer_view = Arc::new(Mutex::new(BufferView::new(&buffer)));
+    let buffer_view = Arc::new(Mutex::new(editor.new_view().unwrap()));
     let data = TaskList {
         // Add a placeholder task for Android, whilst the
         next_task: "My Next Task".into(),
@@ -131,11 +154,12 @@ fn run(event_loop: EventLoopBuilder) -> eyre::Result<()> {
                 done: false,
             },
         ],
-        buffer,
+        editor,
         buffer_view,
     };
 
-    let font_blobs = load_font_blobs_dir("fonts/nerd-fonts")?;
+    let font_blobs =
+        load_font_blobs_dirs(&get_config().font_dirs(), LoadFontOptions::default());
     let mut app = Xilem::new(data, app_logic);
     for font_blob in font_blobs.into_iter() {
         app = app.with_font(font_blob);
@@ -145,7 +169,37 @@ fn run(event_loop: EventLoopBuilder) -> eyre::Result<()> {
     Ok(())
 }
 
+// The configuration is loaded before `run_windowed` installs the tracing
+// subscriber, so the errors are printed to stderr instead of logged.
+
+/// Loads the user's editor settings when there are some. Broken settings are
+/// reported and the defaults are kept.
+fn load_user_config() {
+    let Some(path) = EditorConfig::user_config_path().filter(|path| path.exists())
+    else {
+        return;
+    };
+    match EditorConfig::from_toml(&path) {
+        Ok(config) => set_config(config),
+        Err(err) => eprintln!("Editor settings {path:?} can't be loaded: {err}"),
+    }
+}
+
+/// Applies the user's theme configuration when there is one. A broken
+/// configuration is reported and the default theme is kept.
+fn load_user_theme() {
+    let Some(path) = get_config().theme_path().filter(|path| path.exists()) else {
+        return;
+    };
+    match Config::load(&path) {
+        Ok(config) => config.apply(),
+        Err(err) => eprintln!("Theme configuration {path:?} can't be loaded: {err}"),
+    }
+}
+
 fn main() -> eyre::Result<()> {
     color_backtrace::install();
+    load_user_config();
+    load_user_theme();
     run(EventLoop::with_user_event())
 }
@@ -1,16 +1,20 @@
 use core::ops::Range;
 use std::{
     cmp::min,
-    fs::File,
-    io::{BufReader, BufWriter},
+    fs,
+    io::Write,
     path::{Path, PathBuf},
     process::{Command, Stdio},
     sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
 };
 
-use eyre::Result;
+use eyre::{eyre, Result};
 use ropey::Rope;
-use tracing::debug;
+use similar::{DiffOp, TextDiff};
+use tracing::{debug, warn};
+
+use crate::generation::Generation;
 
 // TODO: Do something about `unwrap`s
 
@@ -24,12 +28,91 @@ pub enum NewLineStyle {
     CRLF,
 }
 
+/// Encoding of the file of the buffer. The buffer is saved in the encoding it
+/// was loaded with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    // UTF-8 starting with the byte order mark.
+    Utf8Bom,
+    // Used for the files that aren't valid UTF-8, every byte is a character.
+    Latin1,
+}
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+impl Encoding {
+    /// Decodes the `bytes` detecting their encoding.
+    pub fn decode(bytes: Vec<u8>) -> (String, Encoding) {
+        if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+            if let Ok(text) = std::str::from_utf8(rest) {
+                return (text.to_string(), Encoding::Utf8Bom);
+            }
+        }
+        match String::from_utf8(bytes) {
+            Ok(text) => (text, Encoding::Utf8),
+            Err(err) => {
+                let text = err.as_bytes().iter().map(|&b| b as char).collect();
+                (text, Encoding::Latin1)
+            }
+        }
+    }
+
+    /// Writes the `rope` in the encoding. Fails for the characters the
+    /// encoding can't represent.
+    fn write(self, rope: &Rope, mut writer: impl Write) -> Result<()> {
+        match self {
+            Encoding::Utf8 => rope.write_to(writer)?,
+            Encoding::Utf8Bom => {
+                writer.write_all(UTF8_BOM)?;
+                rope.write_to(writer)?;
+            }
+            Encoding::Latin1 => {
+                let bytes = rope
+                    .chars()
+                    .map(|c| {
+                        u8::try_from(c).map_err(|_| {
+                            eyre!("The character {c:?} can't be saved as Latin-1")
+                        })
+                    })
+                    .collect::<Result<Vec<u8>>>()?;
+                writer.write_all(&bytes)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Modification time and size of the file when it was loaded or saved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    fn read(path: &Path) -> std::io::Result<FileStamp> {
+        let metadata = fs::metadata(path)?;
+        Ok(FileStamp {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Buffer {
     path: Option<PathBuf>,
     pub rope: Rope,
     is_modified: bool,
     new_line_style: NewLineStyle,
+    encoding: Encoding,
+    // Whether the saved file ends with exactly one line break.
+    ensure_trailing_newline: bool,
+    generation: Generation,
+    // See `Buffer::check_external_change`.
+    file_stamp: Option<FileStamp>,
     // TODO: Add tab to spaces mapping here!!!
 }
 
@@ -40,18 +123,31 @@ impl Buffer {
             is_modified: false,
             rope: Rope::new(),
             new_line_style: NewLineStyle::default(),
+            encoding: Encoding::default(),
+            ensure_trailing_newline: false,
+            generation: Generation::default(),
+            file_stamp: None,
         }
     }
+
+    /// Loads the file at `path`. Files that aren't valid UTF-8 are decoded as
+    /// Latin-1, see [`Buffer::encoding`].
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Buffer> {
-        let file = File::open(&path)?;
-        let buf: BufReader<File> = BufReader::new(file);
-        let rope = Rope::from_reader(buf)?;
+        let file_stamp = FileStamp::read(path.as_ref()).ok();
+        let (text, encoding) = Encoding::decode(fs::read(&path)?);
+        if encoding == Encoding::Latin1 {
+            debug!("{:?} isn't valid UTF-8, loaded as Latin-1", path.as_ref());
+        }
 
         Ok(Buffer {
             path: Some(path.as_ref().to_path_buf()),
             is_modified: false,
-            rope,
+            rope: Rope::from_str(&text),
             new_line_style: NewLineStyle::default(),
+            encoding,
+            ensure_trailing_newline: false,
+            generation: Generation::default(),
+            file_stamp,
         })
     }
 
@@ -62,26 +158,374 @@ impl Buffer {
             is_modified: false,
             rope,
             new_line_style: NewLineStyle::default(),
+            encoding: Encoding::default(),
+            ensure_trailing_newline: false,
+            generation: Generation::default(),
+            file_stamp: None,
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns the encoding the buffer was loaded with and is saved in.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.is_modified
+    }
+
+    /// Returns the generation of the text, it changes whenever the text is
+    /// edited or reverted.
+    pub fn generation(&self) -> Generation {
+        self.generation
+    }
+
+    /// Returns the whole text of the buffer.
+    pub fn content(&self) -> String {
+        self.rope.to_string()
+    }
+
+    fn mark_modified(&mut self) {
+        self.is_modified = true;
+        self.generation.nudge();
+    }
+
+    /// Returns the number of lines. The empty text after the last line break
+    /// counts as a line.
+    pub fn len_lines(&self) -> usize {
+        self.rope.len_lines()
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.rope.len_chars()
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.rope.len_bytes()
+    }
+
+    /// Returns the number of words, the runs of whitespace separate them.
+    pub fn word_count(&self) -> usize {
+        let mut count = 0;
+        let mut in_word = false;
+        for chunk in self.rope.chunks() {
+            for c in chunk.chars() {
+                if c.is_whitespace() {
+                    in_word = false;
+                } else if !in_word {
+                    in_word = true;
+                    count += 1;
+                }
+            }
         }
+        count
+    }
+
+    /// Returns the estimated time to read the text at the `words_per_minute`
+    /// speed (200 to 250 is usual for the prose).
+    pub fn reading_time(&self, words_per_minute: u32) -> Duration {
+        let words = self.word_count() as f64;
+        Duration::from_secs_f64(words * 60.0 / words_per_minute.max(1) as f64)
+    }
+
+    /// When set, the saved files end with exactly one line break: a missing
+    /// one is added and the extra ones are dropped. Empty buffers are saved as
+    /// they are.
+    pub fn set_ensure_trailing_newline(&mut self, ensure: bool) {
+        self.ensure_trailing_newline = ensure;
+    }
+
+    /// Returns the text as it's saved. The text of the buffer itself is not
+    /// changed, so the points of its views stay valid.
+    fn text_to_save(&self) -> Rope {
+        let mut rope = self.rope.clone();
+        if !self.ensure_trailing_newline || rope.len_chars() == 0 {
+            return rope;
+        }
+        let len = rope.len_chars();
+        let line_breaks = rope
+            .chars_at(len)
+            .reversed()
+            .take_while(|c| matches!(c, '\n' | '\r'))
+            .count();
+        let new_line_text = match self.new_line_style {
+            NewLineStyle::LF => "\n",
+            NewLineStyle::CRLF => "\r\n",
+        };
+        rope.remove(len - line_breaks..len);
+        rope.insert(len - line_breaks, new_line_text);
+        rope
     }
 
-    pub fn save_as(&self, path: &PathBuf) -> Result<()> {
-        let writer = BufWriter::new(File::create(path)?);
-        self.rope.write_to(writer)?;
+    /// Writes the buffer into the file at `path`, which becomes the file of
+    /// the buffer.
+    pub fn save_as<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        // Encode first, so a failure leaves the file untouched.
+        let mut bytes = Vec::new();
+        self.encoding.write(&self.text_to_save(), &mut bytes)?;
+        fs::write(&path, bytes)?;
+        self.file_stamp = FileStamp::read(path.as_ref()).ok();
+        self.path = Some(path.as_ref().to_path_buf());
+        self.is_modified = false;
         Ok(())
     }
 
-    pub fn save(&self) -> Result<()> {
-        if !self.is_modified || self.path.is_none() {
+    pub fn save(&mut self) -> Result<()> {
+        if !self.is_modified {
             return Ok(());
         }
-        self.save_as(self.path.as_ref().unwrap())?;
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+        self.save_as(path)
+    }
+
+    /// Returns the column of the character at `char_idx` as displayed, the
+    /// tabs reach the next multiple of the `tab_width`.
+    pub fn visual_column(&self, char_idx: usize, tab_width: usize) -> usize {
+        let tab_width = tab_width.max(1);
+        let line_start = self.rope.line_to_char(self.rope.char_to_line(char_idx));
+        self.rope
+            .slice(line_start..char_idx)
+            .chars()
+            .fold(0, |column, c| match c {
+                '\t' => (column / tab_width + 1) * tab_width,
+                _ => column + 1,
+            })
+    }
+
+    /// Compares the lines of the buffer (`a`) with the lines of the `other`
+    /// buffer (`b`). The hunks contain the line ranges in order.
+    pub fn diff_from(&self, other: &Buffer) -> Vec<DiffHunk> {
+        let old = self.rope.to_string();
+        let new = other.rope.to_string();
+        let diff = TextDiff::from_lines(&old, &new);
+        let mut hunks = Vec::new();
+        for op in diff.ops() {
+            match *op {
+                DiffOp::Equal { old_index, len, .. } => {
+                    hunks.push(DiffHunk::Equal {
+                        a: old_index..old_index + len,
+                    })
+                }
+                DiffOp::Delete {
+                    old_index, old_len, ..
+                } => hunks.push(DiffHunk::Delete {
+                    a: old_index..old_index + old_len,
+                }),
+                DiffOp::Insert {
+                    new_index, new_len, ..
+                } => hunks.push(DiffHunk::Insert {
+                    b: new_index..new_index + new_len,
+                }),
+                DiffOp::Replace {
+                    old_index,
+                    old_len,
+                    new_index,
+                    new_len,
+                } => {
+                    hunks.push(DiffHunk::Delete {
+                        a: old_index..old_index + old_len,
+                    });
+                    hunks.push(DiffHunk::Insert {
+                        b: new_index..new_index + new_len,
+                    });
+                }
+            }
+        }
+        hunks
+    }
+
+    /// Applies all operations of the `patch`. Nothing is changed when the
+    /// patch is not valid or is out of the buffer.
+    pub fn apply_patch(&mut self, patch: Patch) -> Result<()> {
+        if !patch.validate() {
+            return Err(eyre!("The patch operations overlap or are not sorted"));
+        }
+        if let Some((range, _)) = patch.ops.last() {
+            if range.end > self.rope.len_chars() {
+                return Err(eyre!(
+                    "The patch range {range:?} is out of the buffer of {} chars",
+                    self.rope.len_chars()
+                ));
+            }
+        }
+        // Going from the end keeps the indices of the preceding operations.
+        // TODO: Create one undo record for the whole patch.
+        for (range, text) in patch.ops.into_iter().rev() {
+            self.rope.remove(range.clone());
+            self.rope.insert(range.start, &text);
+        }
+        self.mark_modified();
+        Ok(())
+    }
+
+    /// Returns `true` when the file of the buffer was changed by another
+    /// program since it was loaded or saved, so the host can offer to
+    /// [`Buffer::revert`] it. The buffers without a file are never changed.
+    /// Failures to read the file status are only logged, as they are usually
+    /// transient (e.g. the file is being replaced).
+    pub fn check_external_change(&self) -> Result<bool> {
+        let (Some(path), Some(file_stamp)) = (&self.path, &self.file_stamp) else {
+            return Ok(false);
+        };
+        match FileStamp::read(path) {
+            Ok(current) => Ok(current != *file_stamp),
+            Err(err) => {
+                warn!("The status of {path:?} can't be read: {err}");
+                Ok(false)
+            }
+        }
+    }
+
+    /// Reloads the buffer from its file dropping the unsaved changes.
+    pub fn revert(&mut self) -> Result<()> {
+        let path = self
+            .path
+            .clone()
+            .ok_or_else(|| eyre!("The buffer has no file to revert to"))?;
+        let mut generation = self.generation;
+        let ensure_trailing_newline = self.ensure_trailing_newline;
+        *self = Buffer::load(path)?;
+        generation.nudge();
+        self.generation = generation;
+        self.ensure_trailing_newline = ensure_trailing_newline;
         Ok(())
     }
 }
 
-// TODO: Build buffer arena and reference it in the `BufferView`.
-// This buffer arena then can be global???
+/// Returns the char range of the whole `lines` including the last line break.
+fn lines_char_range(rope: &Rope, lines: Range<usize>) -> Range<usize> {
+    let end = if lines.end < rope.len_lines() {
+        rope.line_to_char(lines.end)
+    } else {
+        rope.len_chars()
+    };
+    rope.line_to_char(lines.start)..end
+}
+
+/// Returns the char ranges of the non-overlapping occurrences of the `pattern`
+/// starting at the char index `start` or after it.
+fn find_matches(rope: &Rope, pattern: &str, start: usize) -> Vec<Range<usize>> {
+    if pattern.is_empty() || start > rope.len_chars() {
+        return Vec::new();
+    }
+    let start_byte = rope.char_to_byte(start);
+    let text = rope.slice(start..).to_string();
+    let pattern_len = pattern.chars().count();
+    text.match_indices(pattern)
+        .map(|(index, _)| {
+            let match_start = rope.byte_to_char(start_byte + index);
+            match_start..match_start + pattern_len
+        })
+        .collect()
+}
+
+/// Part of the difference between two buffers (see [`Buffer::diff_from`]).
+/// The ranges are line indices of the compared buffers `a` and `b`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffHunk {
+    Equal { a: Range<usize> },
+    Insert { b: Range<usize> },
+    Delete { a: Range<usize> },
+}
+
+/// Edits of a buffer applied at once (e.g. the LSP `textDocument/applyEdit`).
+/// The operations replace the character ranges of the original text, they
+/// must not overlap and must be sorted by the range start.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Patch {
+    ops: Vec<(Range<usize>, String)>,
+}
+
+impl Patch {
+    pub fn new(ops: Vec<(Range<usize>, String)>) -> Patch {
+        Patch { ops }
+    }
+
+    /// Checks that the operations are sorted and don't overlap.
+    pub fn validate(&self) -> bool {
+        self.ops.iter().all(|(range, _)| range.start <= range.end)
+            && self
+                .ops
+                .windows(2)
+                .all(|ops| ops[0].0.end <= ops[1].0.start)
+    }
+}
+
+/// Identifier of a buffer in the [`BufferRegistry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BufferId(usize);
+
+/// Stores the open buffers. Any number of `BufferView`s can reference the
+/// same buffer (e.g. two views of one file scrolled independently).
+#[derive(Debug, Default)]
+pub struct BufferRegistry {
+    // Sorted by the ids, which are never reused.
+    buffers: Vec<(BufferId, Arc<Mutex<Buffer>>)>,
+    next_id: usize,
+}
+
+impl BufferRegistry {
+    pub fn new() -> BufferRegistry {
+        BufferRegistry::default()
+    }
+
+    pub fn insert(&mut self, buffer: Buffer) -> BufferId {
+        let id = BufferId(self.next_id);
+        self.next_id += 1;
+        self.buffers.push((id, Arc::new(Mutex::new(buffer))));
+        id
+    }
+
+    pub fn remove(&mut self, id: BufferId) -> Option<Arc<Mutex<Buffer>>> {
+        let index = self.buffers.iter().position(|(i, _)| *i == id)?;
+        Some(self.buffers.remove(index).1)
+    }
+
+    pub fn get(&self, id: BufferId) -> Option<&Arc<Mutex<Buffer>>> {
+        self.buffers
+            .iter()
+            .find(|(i, _)| *i == id)
+            .map(|(_, buffer)| buffer)
+    }
+
+    /// Creates a new view of the buffer with the `id`.
+    pub fn view(&self, id: BufferId) -> Option<BufferView> {
+        self.get(id).map(BufferView::new)
+    }
+
+    /// Returns the buffer of the file at `path`.
+    pub fn find_by_path(&self, path: &Path) -> Option<BufferId> {
+        self.buffers
+            .iter()
+            .find(|(_, buffer)| {
+                buffer
+                    .lock()
+                    .unwrap()
+                    .path()
+                    .is_some_and(|open| open == path)
+            })
+            .map(|(id, _)| *id)
+    }
+
+    /// Iterates the buffers in the order they were inserted.
+    pub fn iter(&self) -> impl Iterator<Item = (BufferId, &Arc<Mutex<Buffer>>)> {
+        self.buffers.iter().map(|(id, buffer)| (*id, buffer))
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct BufferView {
@@ -89,6 +533,9 @@ pub struct BufferView {
     // will most likely be one ore very few points (curstors) per view so it
     // makes sense to use something that store values on stack.
     point: Point,
+    // The point set by `set_selection_bytes` with the cursor before the
+    // anchor. The selection stays backward while the point doesn't change.
+    backward_point: Option<Point>,
     buffer: Arc<Mutex<Buffer>>,
 }
 
@@ -96,18 +543,23 @@ impl BufferView {
     pub fn new(buffer: &Arc<Mutex<Buffer>>) -> BufferView {
         BufferView {
             point: 0..0,
+            backward_point: None,
             buffer: buffer.clone(),
         }
     }
 
     pub fn move_point_forward_char(&mut self) {
-        if self.point.end < self.buffer.lock().unwrap().rope.len_chars() {
+        let buffer = self.buffer.lock().unwrap();
+        self.point = self.point_in(&buffer.rope);
+        if self.point.end < buffer.rope.len_chars() {
             self.point.end += 1;
             self.point.start = self.point.end;
         }
     }
 
     pub fn move_point_backward_char(&mut self) {
+        let buffer = self.buffer.lock().unwrap();
+        self.point = self.point_in(&buffer.rope);
         if self.point.start > 0 {
             self.point.start -= 1;
             self.point.end = self.point.start;
@@ -115,12 +567,10 @@ impl BufferView {
     }
 
     pub fn move_point_end_of_line(&mut self) {
-        let line_idx = self
-            .buffer
-            .lock()
-            .unwrap()
-            .rope
-            .char_to_line(self.point.end);
+        let line_idx = {
+            let buffer = self.buffer();
+            buffer.rope.char_to_line(self.point_in(&buffer.rope).end)
+        };
         let idx = if line_idx == 0 {
             self.buffer.lock().unwrap().rope.len_chars()
         } else {
@@ -131,12 +581,10 @@ impl BufferView {
     }
 
     pub fn move_point_start_of_line(&mut self) {
-        let line_idx = self
-            .buffer
-            .lock()
-            .unwrap()
-            .rope
-            .char_to_line(self.point.start);
+        let line_idx = {
+            let buffer = self.buffer();
+            buffer.rope.char_to_line(self.point_in(&buffer.rope).start)
+        };
         self.goto_line(line_idx);
     }
 
@@ -159,6 +607,23 @@ impl BufferView {
         self.point.end = idx;
     }
 
+    /// Moves the point to the `column` (in characters) of its line. Columns
+    /// after the end of the line move the point to the end of the line.
+    pub fn goto_column(&mut self, column: usize) {
+        let buffer = self.buffer.lock().unwrap();
+        let line_idx = buffer.rope.char_to_line(self.point_in(&buffer.rope).start);
+        let line = buffer.rope.line(line_idx);
+        let line_break = match (line.len_chars(), line.chars().last()) {
+            (len, Some('\n')) if len > 1 && line.char(len - 2) == '\r' => 2,
+            (_, Some('\n' | '\r')) => 1,
+            _ => 0,
+        };
+        let idx = buffer.rope.line_to_char(line_idx)
+            + min(column, line.len_chars() - line_break);
+        self.point.start = idx;
+        self.point.end = idx;
+    }
+
     pub fn goto_end_of_buffer(&mut self) {
         let len = { self.buffer.lock().unwrap().rope.len_chars() };
         self.goto_char(len);
@@ -174,20 +639,62 @@ impl BufferView {
     pub fn search_backward() {}
     pub fn search_backward_rx() {}
 
+    /// Replaces all occurrences of `from` by `to`. Returns the number of the
+    /// replacements.
+    pub fn replace_all(&mut self, from: &str, to: &str) -> usize {
+        let mut buffer = self.buffer.lock().unwrap();
+        let matches = find_matches(&buffer.rope, from, 0);
+        // Going from the end keeps the positions of the preceding matches.
+        // TODO: Create an undo record.
+        for range in matches.iter().rev() {
+            buffer.rope.remove(range.clone());
+            buffer.rope.insert(range.start, to);
+        }
+        if !matches.is_empty() {
+            let len = buffer.rope.len_chars();
+            self.point = min(self.point.start, len)..min(self.point.end, len);
+            buffer.mark_modified();
+        }
+        matches.len()
+    }
+
+    /// Replaces the first occurrence of `from` after the point by `to` and
+    /// moves the point after the replacement. Returns `false` when there is
+    /// no occurrence.
+    pub fn replace_next(&mut self, from: &str, to: &str) -> bool {
+        let mut buffer = self.buffer.lock().unwrap();
+        self.point = self.point_in(&buffer.rope);
+        let Some(range) = find_matches(&buffer.rope, from, self.point.end)
+            .into_iter()
+            .next()
+        else {
+            return false;
+        };
+        // TODO: Create an undo record.
+        buffer.rope.remove(range.clone());
+        buffer.rope.insert(range.start, to);
+        let end = range.start + to.chars().count();
+        self.point = end..end;
+        buffer.mark_modified();
+        true
+    }
+
     // Basic editing.
     pub fn insert_at_point(&mut self, text: &str) {
         let mut buffer = self.buffer.lock().unwrap();
+        self.point = self.point_in(&buffer.rope);
         buffer.rope.insert(self.point.start, text);
         let off = Rope::from(text).len_chars();
         self.point.start += off;
         self.point.end = self.point.start;
-        buffer.is_modified = true;
+        buffer.mark_modified();
         // TODO: Selection, multiple points, create undo records, ...
     }
 
     // TODO: Think about this function and it's purpose
     pub fn insert_new_line(&mut self) {
         let mut buffer = self.buffer.lock().unwrap();
+        self.point = self.point_in(&buffer.rope);
         let new_line_text = match buffer.new_line_style {
             NewLineStyle::LF => "\n",
             NewLineStyle::CRLF => "\r\n",
@@ -196,13 +703,366 @@ impl BufferView {
         let off = Rope::from(new_line_text).len_chars();
         self.point.start += off;
         self.point.end = self.point.start;
-        buffer.is_modified = true;
+        buffer.mark_modified();
+    }
+
+    /// Joins the next line to the line of the point. The whitespace around
+    /// the line break is replaced by a single space (none when one of the
+    /// lines is blank). The point is moved to the join. Does nothing on the
+    /// last line.
+    pub fn join_next_line(&mut self) {
+        let mut buffer = self.buffer.lock().unwrap();
+        self.point = self.point_in(&buffer.rope);
+        let rope = &buffer.rope;
+        let line_idx = rope.char_to_line(self.point.start);
+        if line_idx + 1 >= rope.len_lines() {
+            return;
+        }
+        let line_start = rope.line_to_char(line_idx);
+        let next_line_start = rope.line_to_char(line_idx + 1);
+        let mut start = next_line_start;
+        while start > line_start && rope.char(start - 1).is_whitespace() {
+            start -= 1;
+        }
+        let mut end = next_line_start;
+        while end < rope.len_chars()
+            && rope.char(end).is_whitespace()
+            && !matches!(rope.char(end), '\n' | '\r')
+        {
+            end += 1;
+        }
+        let next_line_blank =
+            end == rope.len_chars() || matches!(rope.char(end), '\n' | '\r');
+        buffer.rope.remove(start..end);
+        if start > line_start && !next_line_blank {
+            buffer.rope.insert_char(start, ' ');
+        }
+        self.point = start..start;
+        buffer.mark_modified();
+    }
+
+    /// Inserts a copy of the line of the point below it and moves the point
+    /// to the same column of the copy. When there is a selection, the
+    /// selected text is duplicated after it and the copy is selected.
+    pub fn duplicate_line(&mut self) {
+        let mut buffer = self.buffer.lock().unwrap();
+        self.point = self.point_in(&buffer.rope);
+        if !self.point.is_empty() {
+            let text = buffer.rope.slice(self.point.clone()).to_string();
+            buffer.rope.insert(self.point.end, &text);
+            self.point = self.point.end..self.point.end + self.point.len();
+            buffer.mark_modified();
+            return;
+        }
+        let line_idx = buffer.rope.char_to_line(self.point.start);
+        let line_start = buffer.rope.line_to_char(line_idx);
+        let mut line = buffer.rope.line(line_idx).to_string();
+        let column = self.point.start - line_start;
+        let copy_start = line_start + line.chars().count();
+        if line.ends_with('\n') {
+            buffer.rope.insert(copy_start, &line);
+            self.point.start = copy_start + column;
+        } else {
+            // The last line without a line break.
+            let new_line_text = match buffer.new_line_style {
+                NewLineStyle::LF => "\n",
+                NewLineStyle::CRLF => "\r\n",
+            };
+            line.insert_str(0, new_line_text);
+            buffer.rope.insert(copy_start, &line);
+            self.point.start = copy_start + new_line_text.len() + column;
+        }
+        self.point.end = self.point.start;
+        buffer.mark_modified();
+    }
+
+    /// Swaps the characters around the point and moves the point forward.
+    /// At the end of a line the two characters before the point are swapped
+    /// and the point stays. Does nothing at the start of the buffer.
+    pub fn transpose_chars(&mut self) {
+        let mut buffer = self.buffer.lock().unwrap();
+        self.point = self.point_in(&buffer.rope);
+        let p = self.point.start;
+        let len = buffer.rope.len_chars();
+        let at_line_end = p == len || matches!(buffer.rope.char(p), '\n' | '\r');
+        let (first, advance) = match (p, at_line_end) {
+            (0, _) | (1, true) => return,
+            (_, true) => (p - 2, 0),
+            (_, false) => (p - 1, 1),
+        };
+        let swapped: String = [buffer.rope.char(first + 1), buffer.rope.char(first)]
+            .into_iter()
+            .collect();
+        buffer.rope.remove(first..first + 2);
+        buffer.rope.insert(first, &swapped);
+        self.point = p + advance..p + advance;
+        buffer.mark_modified();
+    }
+
+    /// Swaps the word under (or after) the point with the word before it and
+    /// moves the point after both. The words are the runs of alphanumeric
+    /// characters and `_`. Does nothing when one of the words is missing.
+    pub fn transpose_words(&mut self) {
+        let mut buffer = self.buffer.lock().unwrap();
+        self.point = self.point_in(&buffer.rope);
+        let rope = &buffer.rope;
+        let is_word = |i: usize| {
+            let c = rope.char(i);
+            c.is_alphanumeric() || c == '_'
+        };
+        let len = rope.len_chars();
+        let mut second_end = self.point.start;
+        while second_end < len && !is_word(second_end) {
+            second_end += 1;
+        }
+        while second_end < len && is_word(second_end) {
+            second_end += 1;
+        }
+        let mut second_start = second_end;
+        while second_start > 0 && is_word(second_start - 1) {
+            second_start -= 1;
+        }
+        let mut first_end = second_start;
+        while first_end > 0 && !is_word(first_end - 1) {
+            first_end -= 1;
+        }
+        let mut first_start = first_end;
+        while first_start > 0 && is_word(first_start - 1) {
+            first_start -= 1;
+        }
+        // The word has to end after the point, the one before the point is the
+        // first one.
+        if second_start == second_end
+            || second_end == self.point.start
+            || first_start == first_end
+        {
+            return;
+        }
+        let swapped = format!(
+            "{}{}{}",
+            rope.slice(second_start..second_end),
+            rope.slice(first_end..second_start),
+            rope.slice(first_start..first_end)
+        );
+        buffer.rope.remove(first_start..second_end);
+        buffer.rope.insert(first_start, &swapped);
+        self.point = second_end..second_end;
+        buffer.mark_modified();
+    }
+
+    /// Returns the point limited to the end of the `rope`. The buffer can
+    /// get shorter than the point when it's reverted or edited through an
+    /// other view.
+    fn point_in(&self, rope: &Rope) -> Point {
+        let len = rope.len_chars();
+        min(self.point.start, len)..min(self.point.end, len)
+    }
+
+    /// Returns the lines intersecting the selection. The line at which the
+    /// selection ends is left out when no character of it is selected.
+    fn selected_lines(&self, rope: &Rope) -> Range<usize> {
+        let first = rope.char_to_line(self.point.start);
+        let mut last = rope.char_to_line(self.point.end);
+        if last > first && rope.line_to_char(last) == self.point.end {
+            last -= 1;
+        }
+        first..last + 1
+    }
+
+    /// Inserts the `indent` (see [`crate::config::EditorConfig::indent_text`]) at
+    /// the start of the non-blank lines of the selection or of the line of
+    /// the point. The selection is extended over the whole lines.
+    pub fn indent_region(&mut self, indent: &str) {
+        let mut buffer = self.buffer.lock().unwrap();
+        self.point = self.point_in(&buffer.rope);
+        let lines = self.selected_lines(&buffer.rope);
+        let indent_len = indent.chars().count();
+        let mut point_shift = 0;
+        for line_idx in lines.clone().rev() {
+            let line = buffer.rope.line(line_idx);
+            if line.chars().all(|c| matches!(c, '\n' | '\r')) {
+                continue;
+            }
+            let line_start = buffer.rope.line_to_char(line_idx);
+            buffer.rope.insert(line_start, indent);
+            point_shift = indent_len;
+        }
+        if point_shift == 0 {
+            return;
+        }
+        buffer.mark_modified();
+        if self.point.is_empty() {
+            self.point.start += point_shift;
+            self.point.end = self.point.start;
+        } else {
+            self.point = lines_char_range(&buffer.rope, lines);
+        }
+    }
+
+    /// Removes one level of the indentation (a tab or up to `tab_width`
+    /// spaces) of the lines of the selection or of the line of the point. The
+    /// selection is extended over the whole lines.
+    pub fn dedent_region(&mut self, tab_width: usize) {
+        let mut buffer = self.buffer.lock().unwrap();
+        self.point = self.point_in(&buffer.rope);
+        let lines = self.selected_lines(&buffer.rope);
+        let mut modified = false;
+        for line_idx in lines.clone().rev() {
+            let line = buffer.rope.line(line_idx);
+            let indent_len = match line.get_char(0) {
+                Some('\t') => 1,
+                _ => line
+                    .chars()
+                    .take(tab_width)
+                    .take_while(|&c| c == ' ')
+                    .count(),
+            };
+            if indent_len == 0 {
+                continue;
+            }
+            let line_start = buffer.rope.line_to_char(line_idx);
+            buffer.rope.remove(line_start..line_start + indent_len);
+            if self.point.is_empty() {
+                let column = self.point.start - line_start;
+                self.point.start = line_start + column.saturating_sub(indent_len);
+                self.point.end = self.point.start;
+            }
+            modified = true;
+        }
+        if !modified {
+            return;
+        }
+        buffer.mark_modified();
+        if !self.point.is_empty() {
+            self.point = lines_char_range(&buffer.rope, lines);
+        }
+    }
+
+    /// Comments out the non-blank lines of the selection or the line of the
+    /// point by inserting the `line_prefix` (e.g. `"// "`) after their common
+    /// indentation. When all of them are already commented, the prefix is
+    /// removed instead. The point keeps its position relative to the text.
+    pub fn toggle_line_comment(&mut self, line_prefix: &str) {
+        if line_prefix.is_empty() {
+            return;
+        }
+        let mut buffer = self.buffer.lock().unwrap();
+        self.point = self.point_in(&buffer.rope);
+        let lines = self.selected_lines(&buffer.rope);
+        let trimmed_prefix = line_prefix.trim_end();
+        // The non-blank lines with the lengths of their indentations and of
+        // their comment prefixes.
+        let indented_lines: Vec<(usize, usize, Option<usize>)> = lines
+            .filter_map(|line_idx| {
+                let line = buffer.rope.line(line_idx);
+                let indent_len =
+                    line.chars().take_while(|c| matches!(c, ' ' | '\t')).count();
+                if let None | Some('\n' | '\r') = line.get_char(indent_len) {
+                    return None;
+                }
+                let text = line.slice(indent_len..);
+                let comment_len = [line_prefix, trimmed_prefix]
+                    .into_iter()
+                    .filter(|prefix| !prefix.is_empty())
+                    .map(|prefix| prefix.chars().count())
+                    .find(|&len| {
+                        text.chars().take(len).eq(line_prefix.chars().take(len))
+                    });
+                Some((line_idx, indent_len, comment_len))
+            })
+            .collect();
+        if indented_lines.is_empty() {
+            return;
+        }
+        let uncomment = indented_lines
+            .iter()
+            .all(|(_, _, comment_len)| comment_len.is_some());
+        let min_indent_len = indented_lines
+            .iter()
+            .map(|(_, indent_len, _)| *indent_len)
+            .min()
+            .unwrap_or(0);
+
+        let mut point = self.point.clone();
+        for &(line_idx, indent_len, comment_len) in indented_lines.iter().rev() {
+            let line_start = buffer.rope.line_to_char(line_idx);
+            if uncomment {
+                let len = comment_len.unwrap_or(0);
+                let removed = line_start + indent_len..line_start + indent_len + len;
+                buffer.rope.remove(removed.clone());
+                let shift = |index: usize| {
+                    if index >= removed.end {
+                        index - len
+                    } else {
+                        index.min(removed.start)
+                    }
+                };
+                point = shift(point.start)..shift(point.end);
+            } else {
+                let position = line_start + min_indent_len;
+                buffer.rope.insert(position, line_prefix);
+                let len = line_prefix.chars().count();
+                let shift = |index: usize| {
+                    if index >= position {
+                        index + len
+                    } else {
+                        index
+                    }
+                };
+                point = shift(point.start)..shift(point.end);
+            }
+        }
+        self.point = point;
+        buffer.mark_modified();
+    }
+
+    /// Inserts a line break at the point without indenting the new line. The
+    /// point stays at the end of the original line.
+    pub fn split_line_at_point(&mut self) {
+        let mut buffer = self.buffer.lock().unwrap();
+        self.point = self.point_in(&buffer.rope);
+        let new_line_text = match buffer.new_line_style {
+            NewLineStyle::LF => "\n",
+            NewLineStyle::CRLF => "\r\n",
+        };
+        buffer.rope.insert(self.point.start, new_line_text);
+        self.point.end = self.point.start;
+        buffer.mark_modified();
+    }
+
+    /// Returns the character right after the point.
+    pub fn char_at_point(&self) -> Option<char> {
+        self.buffer().rope.get_char(self.point.start)
+    }
+
+    /// Returns the character right before the point.
+    pub fn char_before_point(&self) -> Option<char> {
+        let buffer = self.buffer();
+        let index = self.point_in(&buffer.rope).start.checked_sub(1)?;
+        buffer.rope.get_char(index)
+    }
+
+    /// Returns the spaces and tabs at the start of the line with the point.
+    /// Only the characters before the point are taken into account.
+    pub fn current_line_indent(&self) -> String {
+        let buffer = self.buffer.lock().unwrap();
+        let point = self.point_in(&buffer.rope);
+        let line_start = buffer
+            .rope
+            .line_to_char(buffer.rope.char_to_line(point.start));
+        buffer
+            .rope
+            .slice(line_start..point.start)
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect()
     }
 
     pub fn delete_at_point(&mut self) {
         // Delete, not backspace. For now.
-        let p = &self.point;
         let mut buffer = self.buffer.lock().unwrap();
+        self.point = self.point_in(&buffer.rope);
+        let p = &self.point;
         assert!(p.end <= buffer.rope.len_chars());
         let to = if p.start == p.end {
             min(buffer.rope.len_chars(), p.end + 1)
@@ -210,21 +1070,83 @@ impl BufferView {
             p.end
         };
         buffer.rope.remove(p.start..to);
-        buffer.is_modified = true;
+        buffer.mark_modified();
+    }
+
+    /// Returns the zero-based line of the point.
+    pub fn line(&self) -> usize {
+        let buffer = self.buffer();
+        buffer.rope.char_to_line(self.point_in(&buffer.rope).start)
+    }
+
+    /// Returns the zero-based column of the point in characters, the same
+    /// unit the point moves by. See [`Buffer::visual_column`] for the column
+    /// with expanded tabs.
+    pub fn column(&self) -> usize {
+        let buffer = self.buffer();
+        let start = self.point_in(&buffer.rope).start;
+        let line = buffer.rope.char_to_line(start);
+        start - buffer.rope.line_to_char(line)
     }
 
+    pub fn has_selection(&self) -> bool {
+        !self.point_in(&self.buffer().rope).is_empty()
+    }
+
+    /// Returns the number of the selected characters.
+    pub fn selection_len(&self) -> usize {
+        self.point_in(&self.buffer().rope).len()
+    }
+
+    /// Returns the position of the point as a byte index into the buffer.
     pub fn position_bytes(&self) -> usize {
         let buffer = self.buffer.lock().unwrap();
-        buffer.rope.char_to_byte(self.point.start)
+        buffer.rope.char_to_byte(self.point_in(&buffer.rope).start)
     }
 
+    /// Moves the point to the character containing the `byte`. Bytes outside
+    /// of the buffer are clamped to the end of the buffer.
     pub fn set_position_bytes(&mut self, byte: usize) {
         let buffer = self.buffer.lock().unwrap();
+        let byte = min(byte, buffer.rope.len_bytes());
         let start = buffer.rope.byte_to_char(byte);
         self.point.start = start;
+        self.point.end = start;
         debug!("set_position_bytes start: {start}; {byte}");
     }
 
+    /// Returns the point as a byte range (the start is always before the end).
+    pub fn selection_bytes(&self) -> Range<usize> {
+        let buffer = self.buffer.lock().unwrap();
+        let point = self.point_in(&buffer.rope);
+        buffer.rope.char_to_byte(point.start)..buffer.rope.char_to_byte(point.end)
+    }
+
+    /// Returns the point as a byte range from the anchor to the cursor. The
+    /// start is after the end when the selection was set backward by
+    /// [`BufferView::set_selection_bytes`].
+    pub fn directed_selection_bytes(&self) -> Range<usize> {
+        let bytes = self.selection_bytes();
+        if self.backward_point.as_ref() == Some(&self.point) {
+            bytes.end..bytes.start
+        } else {
+            bytes
+        }
+    }
+
+    /// Sets the point from a byte range from the anchor to the cursor, the
+    /// cursor can be before the anchor (see
+    /// [`BufferView::directed_selection_bytes`]). Bytes outside of the buffer
+    /// are clamped to the end of the buffer.
+    pub fn set_selection_bytes(&mut self, bytes: Range<usize>) {
+        let buffer = self.buffer.lock().unwrap();
+        let len = buffer.rope.len_bytes();
+        let anchor = buffer.rope.byte_to_char(min(bytes.start, len));
+        let cursor = buffer.rope.byte_to_char(min(bytes.end, len));
+        self.point = min(anchor, cursor)..anchor.max(cursor);
+        self.backward_point = (cursor < anchor).then(|| self.point.clone());
+    }
+
     // TODO: Write this in a way that we can have multiple undo implementations: simple undo/redo stack, undo tree, etc.
     pub fn undo() {}
     pub fn redo() {}
@@ -260,9 +1182,13 @@ impl BufferView {
 
 #[cfg(test)]
 mod tests {
-    use std::sync::{Arc, Mutex};
+    use std::{
+        env, fs, process,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
 
-    use super::{Buffer, BufferView};
+    use super::{Buffer, BufferView, DiffHunk, Encoding, Patch};
 
     #[test]
     fn new_buffer() {
@@ -304,4 +1230,450 @@ mod tests {
         buf_view.goto_end_of_buffer();
         assert_point!(buf_view.point);
     }
+
+    #[test]
+    fn current_line_indent() {
+        let text = "fn main() {\n    let a = 1;\n\t\tb();\n \t c\nlast";
+        let buf = Arc::new(Mutex::new(Buffer::from_string(text)));
+        let mut buf_view = BufferView::new(&buf);
+        let line_end = |line: &str| text.find(line).unwrap() + line.len();
+
+        buf_view.goto_char(line_end("fn main() {"));
+        assert_eq!(buf_view.current_line_indent(), "");
+        // Spaces.
+        buf_view.goto_char(line_end("let a = 1;"));
+        assert_eq!(buf_view.current_line_indent(), "    ");
+        // Tabs.
+        buf_view.goto_char(line_end("b();"));
+        assert_eq!(buf_view.current_line_indent(), "\t\t");
+        // Mixed indent.
+        buf_view.goto_char(line_end(" c"));
+        assert_eq!(buf_view.current_line_indent(), " \t ");
+        // The point within the indentation.
+        buf_view.goto_char(text.find("let").unwrap() - 2);
+        assert_eq!(buf_view.current_line_indent(), "  ");
+
+        buf_view.goto_char(line_end("let a = 1;"));
+        let indent = buf_view.current_line_indent();
+        buf_view.insert_new_line();
+        buf_view.insert_at_point(&indent);
+        assert!(buf
+            .lock()
+            .unwrap()
+            .rope
+            .to_string()
+            .contains("1;\n    \n\t\t"));
+    }
+
+    #[test]
+    fn position_bytes_round_trip() {
+        // 1, 2, 3 and 4 bytes long characters.
+        let text = "aé€😀\nx";
+        let buf = Arc::new(Mutex::new(Buffer::from_string(text)));
+        let mut buf_view = BufferView::new(&buf);
+        for (char_idx, (byte_idx, _)) in text.char_indices().enumerate() {
+            buf_view.goto_char(char_idx);
+            assert_eq!(buf_view.position_bytes(), byte_idx);
+            buf_view.set_position_bytes(byte_idx);
+            assert_eq!(buf_view.point, char_idx..char_idx);
+        }
+    }
+
+    #[test]
+    fn set_position_bytes_clamps() {
+        let buf = Arc::new(Mutex::new(Buffer::from_string("a€")));
+        let mut buf_view = BufferView::new(&buf);
+        // The middle of the `€` belongs to it.
+        buf_view.set_position_bytes(2);
+        assert_eq!(buf_view.point, 1..1);
+        buf_view.set_position_bytes(100);
+        assert_eq!(buf_view.point, 2..2);
+        assert_eq!(buf_view.position_bytes(), 4);
+    }
+
+    #[test]
+    fn apply_patch() {
+        let mut buf = Buffer::from_string("let a = 1;\nlet b = 2;\n");
+        let patch = Patch::new(vec![
+            (4..5, "alpha".to_string()),
+            (8..8, "-".to_string()),
+            (15..20, "beta".to_string()),
+        ]);
+        assert!(patch.validate());
+        buf.apply_patch(patch).unwrap();
+        assert_eq!(buf.rope.to_string(), "let alpha = -1;\nlet beta;\n");
+        assert!(buf.is_modified());
+
+        let overlapping =
+            Patch::new(vec![(0..3, String::new()), (2..4, String::new())]);
+        assert!(!overlapping.validate());
+        assert!(buf.apply_patch(overlapping).is_err());
+        let out_of_buffer = Patch::new(vec![(0..100, String::new())]);
+        assert!(buf.apply_patch(out_of_buffer).is_err());
+        assert_eq!(buf.rope.to_string(), "let alpha = -1;\nlet beta;\n");
+    }
+
+    #[test]
+    fn visual_column() {
+        let buf = Buffer::from_string("a\tb\n\t\tc\n");
+        assert_eq!(buf.visual_column(0, 4), 0);
+        assert_eq!(buf.visual_column(2, 4), 4);
+        assert_eq!(buf.visual_column(3, 4), 5);
+        assert_eq!(buf.visual_column(6, 4), 8);
+        assert_eq!(buf.visual_column(6, 2), 4);
+        assert_eq!(buf.visual_column(buf.rope.len_chars(), 4), 0);
+    }
+
+    #[test]
+    fn status_stats() {
+        let buf = Arc::new(Mutex::new(Buffer::from_string("ab\nčd\tx\n")));
+        let mut buf_view = BufferView::new(&buf);
+        {
+            let buffer = buf_view.buffer();
+            assert_eq!(buffer.len_lines(), 3);
+            assert_eq!(buffer.len_chars(), 8);
+            assert_eq!(buffer.len_bytes(), 9);
+            assert_eq!(buffer.word_count(), 3);
+        }
+        buf_view.goto_char(6);
+        assert_eq!((buf_view.line(), buf_view.column()), (1, 3));
+        assert_eq!(buf_view.buffer().visual_column(6, 4), 4);
+        assert!(!buf_view.has_selection());
+        buf_view.set_selection_bytes(0..5);
+        assert!(buf_view.has_selection());
+        assert_eq!(buf_view.selection_len(), 4);
+        assert_eq!((buf_view.line(), buf_view.column()), (0, 0));
+    }
+
+    #[test]
+    fn backward_selection_keeps_direction() {
+        let buf = Arc::new(Mutex::new(Buffer::from_string("ačb\nxy")));
+        let mut buf_view = BufferView::new(&buf);
+        let (anchor, cursor) = (6, 1);
+        buf_view.set_selection_bytes(anchor..cursor);
+        assert_eq!(buf_view.selection_bytes(), cursor..anchor);
+        assert_eq!(buf_view.directed_selection_bytes(), anchor..cursor);
+        buf_view.set_selection_bytes(cursor..anchor);
+        assert_eq!(buf_view.directed_selection_bytes(), cursor..anchor);
+
+        buf_view.set_selection_bytes(anchor..cursor);
+        buf_view.move_point_forward_char();
+        assert_eq!(buf_view.directed_selection_bytes(), 7..7);
+    }
+
+    #[test]
+    fn goto_column() {
+        let buf = Arc::new(Mutex::new(Buffer::from_string("abc\r\nde\nfgh")));
+        let mut buf_view = BufferView::new(&buf);
+        buf_view.goto_column(2);
+        assert_eq!(buf_view.point, 2..2);
+        buf_view.goto_column(10);
+        assert_eq!(buf_view.point, 3..3);
+        buf_view.goto_line(1);
+        buf_view.goto_column(10);
+        assert_eq!(buf_view.point, 7..7);
+        buf_view.goto_line(2);
+        buf_view.goto_column(10);
+        assert_eq!(buf_view.point, 11..11);
+    }
+
+    #[test]
+    fn join_lines() {
+        let buf =
+            Arc::new(Mutex::new(Buffer::from_string("let a =  \n    1;\n\nend")));
+        let mut buf_view = BufferView::new(&buf);
+        buf_view.join_next_line();
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "let a = 1;\n\nend");
+        assert_eq!(buf_view.point, 7..7);
+        // Joining into the empty next line adds no space.
+        buf_view.join_next_line();
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "let a = 1;\nend");
+        // The last line is kept as it is.
+        buf_view.goto_end_of_buffer();
+        buf_view.join_next_line();
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "let a = 1;\nend");
+        assert_eq!(buf_view.point, 14..14);
+    }
+
+    #[test]
+    fn split_line() {
+        let buf = Arc::new(Mutex::new(Buffer::from_string("ab")));
+        let mut buf_view = BufferView::new(&buf);
+        buf_view.goto_char(1);
+        buf_view.split_line_at_point();
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "a\nb");
+        assert_eq!(buf_view.point, 1..1);
+    }
+
+    #[test]
+    fn replace() {
+        let buf = Arc::new(Mutex::new(Buffer::from_string("čaaa a aa")));
+        let mut buf_view = BufferView::new(&buf);
+        assert_eq!(buf_view.replace_all("aa", "b"), 2);
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "čba a b");
+        assert_eq!(buf_view.replace_all("x", "y"), 0);
+
+        buf_view.goto_char(3);
+        assert!(buf_view.replace_next("a", "xyz"));
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "čba xyz b");
+        assert_eq!(buf_view.point, 7..7);
+        assert!(!buf_view.replace_next("a", "xyz"));
+    }
+
+    #[test]
+    fn duplicate_line() {
+        let buf = Arc::new(Mutex::new(Buffer::from_string("ab\ncd")));
+        let mut buf_view = BufferView::new(&buf);
+        buf_view.goto_char(1);
+        buf_view.duplicate_line();
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "ab\nab\ncd");
+        assert_eq!(buf_view.point, 4..4);
+
+        buf_view.goto_char(8);
+        buf_view.duplicate_line();
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "ab\nab\ncd\ncd");
+        assert_eq!(buf_view.point, 11..11);
+
+        buf_view.set_selection_bytes(0..2);
+        buf_view.duplicate_line();
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "abab\nab\ncd\ncd");
+        assert_eq!(buf_view.point, 2..4);
+    }
+
+    #[test]
+    fn diff_lines() {
+        let a = Buffer::from_string("one\ntwo\nthree\nfour\n");
+        let b = Buffer::from_string("one\n2\nthree\nfour\nfive\n");
+        assert_eq!(
+            a.diff_from(&b),
+            vec![
+                DiffHunk::Equal { a: 0..1 },
+                DiffHunk::Delete { a: 1..2 },
+                DiffHunk::Insert { b: 1..2 },
+                DiffHunk::Equal { a: 2..4 },
+                DiffHunk::Insert { b: 4..5 },
+            ]
+        );
+        assert_eq!(a.diff_from(&a), vec![DiffHunk::Equal { a: 0..4 }]);
+    }
+
+    #[test]
+    fn transpose_chars() {
+        let buf = Arc::new(Mutex::new(Buffer::from_string("ačb\nxy")));
+        let mut buf_view = BufferView::new(&buf);
+        buf_view.transpose_chars();
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "ačb\nxy");
+        buf_view.goto_char(1);
+        buf_view.transpose_chars();
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "čab\nxy");
+        assert_eq!(buf_view.point, 2..2);
+        // At the end of the line the two preceding characters are swapped.
+        buf_view.goto_char(3);
+        buf_view.transpose_chars();
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "čba\nxy");
+        assert_eq!(buf_view.point, 3..3);
+        buf_view.goto_end_of_buffer();
+        buf_view.transpose_chars();
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "čba\nyx");
+    }
+
+    #[test]
+    fn transpose_words() {
+        let buf = Arc::new(Mutex::new(Buffer::from_string("žluť kůň, běží")));
+        let mut buf_view = BufferView::new(&buf);
+        buf_view.transpose_words();
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "žluť kůň, běží");
+        buf_view.goto_char(6);
+        buf_view.transpose_words();
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "kůň žluť, běží");
+        assert_eq!(buf_view.point, 8..8);
+        buf_view.transpose_words();
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "kůň běží, žluť");
+        assert_eq!(buf_view.point, 14..14);
+        buf_view.transpose_words();
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "kůň běží, žluť");
+    }
+
+    #[test]
+    fn indent_and_dedent_region() {
+        let buf = Arc::new(Mutex::new(Buffer::from_string("a\n\n  b\nc\n")));
+        let mut buf_view = BufferView::new(&buf);
+        // The line at which the selection ends is not selected.
+        buf_view.set_selection_bytes(1..7);
+        buf_view.indent_region("    ");
+        assert_eq!(
+            buf.lock().unwrap().rope.to_string(),
+            "    a\n\n      b\nc\n"
+        );
+        assert_eq!(buf_view.point, 0..15);
+
+        buf_view.dedent_region(4);
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "a\n\n  b\nc\n");
+        assert_eq!(buf_view.point, 0..7);
+        buf_view.dedent_region(4);
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "a\n\nb\nc\n");
+        // Lines without indentation are kept.
+        buf_view.dedent_region(4);
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "a\n\nb\nc\n");
+
+        buf_view.goto_char(5);
+        buf_view.indent_region("\t");
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "a\n\nb\n\tc\n");
+        assert_eq!(buf_view.point, 6..6);
+        buf_view.dedent_region(4);
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "a\n\nb\nc\n");
+        assert_eq!(buf_view.point, 5..5);
+    }
+
+    #[test]
+    fn toggle_line_comment() {
+        let text = "  a\n    b\n\nc\n";
+        let buf = Arc::new(Mutex::new(Buffer::from_string(text)));
+        let mut buf_view = BufferView::new(&buf);
+        // Selects "a\n    b".
+        buf_view.set_selection_bytes(2..9);
+        buf_view.toggle_line_comment("// ");
+        assert_eq!(
+            buf.lock().unwrap().rope.to_string(),
+            "  // a\n  //   b\n\nc\n"
+        );
+        assert_eq!(buf_view.point, 5..15);
+        buf_view.toggle_line_comment("// ");
+        assert_eq!(buf.lock().unwrap().rope.to_string(), text);
+        assert_eq!(buf_view.point, 2..9);
+
+        // The prefix without the trailing space is recognized too.
+        let buf = Arc::new(Mutex::new(Buffer::from_string("//a\n")));
+        let mut buf_view = BufferView::new(&buf);
+        buf_view.goto_char(3);
+        buf_view.toggle_line_comment("// ");
+        assert_eq!(buf.lock().unwrap().rope.to_string(), "a\n");
+        assert_eq!(buf_view.point, 1..1);
+    }
+
+    #[test]
+    fn load_and_save_encodings() {
+        let path = env::temp_dir()
+            .join(format!("wrenched-encoding-test-{}.txt", process::id()));
+
+        fs::write(&path, b"caf\xE9\n").unwrap();
+        let mut buf = Buffer::load(&path).unwrap();
+        assert_eq!(buf.encoding(), Encoding::Latin1);
+        assert_eq!(buf.rope.to_string(), "café\n");
+        buf.rope.insert(0, "¡");
+        buf.save_as(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"\xA1caf\xE9\n");
+        // The characters outside of Latin-1 can't be saved.
+        buf.rope.insert(0, "€");
+        assert!(buf.save_as(&path).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"\xA1caf\xE9\n");
+
+        fs::write(&path, b"\xEF\xBB\xBFcaf\xC3\xA9\n").unwrap();
+        let mut buf = Buffer::load(&path).unwrap();
+        assert_eq!(buf.encoding(), Encoding::Utf8Bom);
+        assert_eq!(buf.rope.to_string(), "café\n");
+        buf.save_as(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"\xEF\xBB\xBFcaf\xC3\xA9\n");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn trailing_newline_on_save() {
+        let saved = |text: &str| {
+            let mut buf = Buffer::from_string(text);
+            buf.set_ensure_trailing_newline(true);
+            buf.text_to_save().to_string()
+        };
+        assert_eq!(saved("a"), "a\n");
+        assert_eq!(saved("a\n"), "a\n");
+        assert_eq!(saved("a\n\n\r\n\n"), "a\n");
+        assert_eq!(saved(""), "");
+
+        // The buffer itself is not changed and the policy is off by default.
+        let mut buf = Buffer::from_string("a\n\n");
+        assert_eq!(buf.text_to_save().to_string(), "a\n\n");
+        buf.set_ensure_trailing_newline(true);
+        assert_eq!(buf.text_to_save().to_string(), "a\n");
+        assert_eq!(buf.rope.to_string(), "a\n\n");
+    }
+
+    #[test]
+    fn generation_changes_on_edit() {
+        let buf = Arc::new(Mutex::new(Buffer::from_string("text")));
+        let mut buf_view = BufferView::new(&buf);
+        let generation = buf.lock().unwrap().generation();
+        buf_view.goto_char(4);
+        assert_eq!(buf.lock().unwrap().generation(), generation);
+        buf_view.insert_at_point("!");
+        assert!(buf.lock().unwrap().generation().is_newer_than(generation));
+        assert_eq!(buf.lock().unwrap().content(), "text!");
+    }
+
+    #[test]
+    fn word_count_and_reading_time() {
+        let buffer = Buffer::from_string("  one\ttwo\n\nthree,  four \u{a0}five\n");
+        assert_eq!(buffer.word_count(), 5);
+        assert_eq!(Buffer::new().word_count(), 0);
+
+        // The words split between the rope chunks are counted once.
+        let buffer = Buffer::from_string(&"word ".repeat(1000));
+        assert!(buffer.rope.chunks().count() > 1);
+        assert_eq!(buffer.word_count(), 1000);
+        assert_eq!(buffer.reading_time(200), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn external_change() {
+        let path = env::temp_dir().join(format!(
+            "wrenched-external-change-test-{}.txt",
+            process::id()
+        ));
+        assert!(!Buffer::from_string("text").check_external_change().unwrap());
+
+        fs::write(&path, "text").unwrap();
+        let mut buf = Buffer::load(&path).unwrap();
+        assert!(!buf.check_external_change().unwrap());
+        // Own saves are not external changes.
+        buf.rope.insert(4, "!");
+        buf.save_as(&path).unwrap();
+        assert!(!buf.check_external_change().unwrap());
+
+        fs::write(&path, "changed text").unwrap();
+        assert!(buf.check_external_change().unwrap());
+        buf.revert().unwrap();
+        assert!(!buf.check_external_change().unwrap());
+
+        // The removed file is only logged.
+        fs::remove_file(&path).unwrap();
+        assert!(!buf.check_external_change().unwrap());
+    }
+
+    #[test]
+    fn revert_to_shorter_file() {
+        let path = env::temp_dir()
+            .join(format!("wrenched-revert-test-{}.txt", process::id()));
+        fs::write(
+            &path,
+            "a longer text
+with two lines",
+        )
+        .unwrap();
+        let buf = Arc::new(Mutex::new(Buffer::load(&path).unwrap()));
+        let mut view = BufferView::new(&buf);
+        view.goto_end_of_buffer();
+        view.set_selection_bytes(20..27);
+
+        fs::write(&path, "short").unwrap();
+        buf.lock().unwrap().revert().unwrap();
+        // The point is kept inside of the shorter text.
+        assert_eq!(view.position_bytes(), 5);
+        assert_eq!(view.selection_bytes(), 5..5);
+        assert_eq!((view.line(), view.column()), (0, 5));
+        assert!(!view.has_selection());
+        view.insert_at_point("!");
+        assert_eq!(view.buffer().rope.to_string(), "short!");
+
+        fs::remove_file(&path).unwrap();
+    }
 }
@@ -1,12 +1,14 @@
-use core::{f32, f64};
+use core::{f32, f64, ops::Range};
 
+use accesskit::{Node, NodeId, TextSelection, TreeUpdate};
 use kurbo::{Affine, BezPath, Cap, Join, Line, Rect, Size, Stroke, Vec2};
 use parley::{
     fontique::{Collection, CollectionOptions},
-    layout::Cursor,
+    layout::{Cursor, Selection},
     style::{FontFamily, GenericFamily, StyleProperty},
-    Cluster, Decoration, FontContext, FontStack, FontStyle, GlyphRun, Layout,
-    LayoutContext, LineMetrics, PositionedLayoutItem, RangedBuilder, RunMetrics,
+    Affinity, Cluster, Decoration, FontContext, FontStack, FontStyle, GlyphRun,
+    Layout, LayoutAccessibility, LayoutContext, LineMetrics, PositionedLayoutItem,
+    RangedBuilder, RunMetrics,
 };
 use peniko::BlendMode;
 use vello::{
@@ -16,7 +18,10 @@ use vello::{
 };
 use xilem::FontWeight;
 
-use crate::theme::get_theme;
+use crate::{
+    config::get_config, markdown::text::layouted_text::selection_rects,
+    theme::get_theme,
+};
 
 pub struct CodeTextLayout {
     font: FontStack<'static>,
@@ -25,6 +30,7 @@ pub struct CodeTextLayout {
     text_hinting: bool,
     text_layout_ctx: LayoutContext<CodeTextBrush>,
     font_ctx: FontContext,
+    layout_access: LayoutAccessibility,
     scroll: f64,
 }
 
@@ -85,10 +91,16 @@ impl CodeTextLayout {
                 }),
                 source_cache: Default::default(),
             },
+            layout_access: LayoutAccessibility::default(),
             scroll: 0.0,
         }
     }
 
+    /// Sets the font used for the whole text.
+    pub fn set_font(&mut self, font: FontStack<'static>) {
+        self.font = font;
+    }
+
     /// Set the width at which to wrap words.
     ///
     /// You may pass `None` to disable word wrapping
@@ -148,15 +160,72 @@ impl CodeTextLayout {
         self.layout.break_all_lines(self.max_advance);
     }
 
+    /// Builds the accessibility nodes for the laid out `text` as children of
+    /// `node`. The `text` has to be the same one the layout was built from.
+    pub fn accessibility(
+        &mut self,
+        text: &str,
+        update: &mut TreeUpdate,
+        node: &mut Node,
+        next_node_id: impl FnMut() -> NodeId,
+        x_offset: f64,
+        y_offset: f64,
+    ) {
+        self.layout_access.build_nodes(
+            text,
+            &self.layout,
+            update,
+            node,
+            next_node_id,
+            x_offset,
+            y_offset - self.scroll,
+        );
+    }
+
+    /// Converts the byte range of the selection from the anchor to the focus
+    /// into the accessibility selection. The accessibility nodes has to be
+    /// built first.
+    pub fn access_selection(&self, bytes: Range<usize>) -> Option<TextSelection> {
+        let selection = Selection::new(
+            Cursor::from_byte_index(&self.layout, bytes.start, Affinity::Downstream),
+            Cursor::from_byte_index(&self.layout, bytes.end, Affinity::Upstream),
+        );
+        selection.to_access_selection(&self.layout, &self.layout_access)
+    }
+
+    /// Converts the accessibility selection into the byte range within the
+    /// text. The range goes from the anchor to the focus, so its start is
+    /// after its end for the backward selections.
+    pub fn selection_from_access(
+        &self,
+        selection: &TextSelection,
+    ) -> Option<Range<usize>> {
+        Selection::from_access_selection(
+            selection,
+            &self.layout,
+            &self.layout_access,
+        )
+        .map(|selection| selection.anchor().index()..selection.focus().index())
+    }
+
+    /// Scrolls so the line with the byte `index` is in the middle of the view
+    /// of `view_height`.
+    pub fn scroll_to_center(&mut self, index: usize, view_height: f64) {
+        let cursor =
+            Cursor::from_byte_index(&self.layout, index, Affinity::Upstream);
+        let line_center = cursor.geometry(&self.layout, 1.0).center().y;
+        self.scroll = (line_center - view_height / 2.0).max(0.0);
+    }
+
+    /// Scrolls by the mouse wheel `delta` (see
+    /// [`crate::config::EditorConfig::scroll_delta`]).
     pub fn scroll(&mut self, delta: Vec2) {
-        const SCROLLING_SPEED: f64 = 2.0;
+        let config = get_config();
         // TODO: Horizontal scroll
-        let delta =
-            Vec2::new(delta.x * -SCROLLING_SPEED, delta.y * -SCROLLING_SPEED);
-        if self.scroll + delta.y < 0.0 {
-            self.scroll = 0.0;
-        }
-        self.scroll += delta.y;
+        let delta = config.scroll_delta(delta, config.code_scrolling_speed);
+        // The scroll is the offset of the top of the view, moving the content
+        // down decreases it.
+        self.scroll = (self.scroll - delta.y).max(0.0);
     }
 
     fn draw_underline(
@@ -309,17 +378,59 @@ impl CodeTextLayout {
         );
     }
 
-    pub fn draw(&mut self, scene: &mut Scene, cursor_position: usize, size: Size) {
+    /// Highlights the whole width of the visual line with the cursor at the
+    /// byte index `cursor_position`.
+    pub fn draw_current_line(
+        &self,
+        scene: &mut Scene,
+        cursor_position: usize,
+        width: f64,
+    ) {
+        let Some(line) = Cluster::from_byte_index(
+            &self.layout,
+            cursor_position,
+            Affinity::Upstream,
+        )
+        .map(|cluster| cluster.path().line_index())
+        .or_else(|| self.layout.len().checked_sub(1))
+        .and_then(|index| self.layout.get(index)) else {
+            return;
+        };
+        let metrics = line.metrics();
+        let rect = Rect::new(
+            0.0,
+            metrics.min_coord as f64,
+            width,
+            metrics.max_coord as f64,
+        );
+        let color = get_theme().text.selection_color.multiply_alpha(0.2);
+        let transform = Affine::translate((0.0, -self.scroll));
+        scene.fill(Fill::NonZero, transform, color, None, &rect);
+    }
+
+    /// Draws the text with the cursor at the byte index `cursor_position` and
+    /// the `selection` (byte range) background.
+    pub fn draw(
+        &mut self,
+        scene: &mut Scene,
+        cursor_position: usize,
+        selection: Range<usize>,
+        size: Size,
+    ) {
         let cursor = Cursor::from_byte_index(
             &self.layout,
             cursor_position,
-            parley::Affinity::Upstream,
+            Affinity::Upstream,
         );
         let cursor_rect = cursor.geometry(&self.layout, 1.5);
         println!("self.scroll: {}", self.scroll);
         let transform = Affine::translate((0.0, -self.scroll));
-        // TODO: Selection
-        scene.fill(Fill::NonZero, transform, Color::WHITE, None, &cursor_rect);
+        let selection_color = get_theme().text.selection_color;
+        for rect in selection_rects(&self.layout, selection) {
+            scene.fill(Fill::NonZero, transform, selection_color, None, &rect);
+        }
+        let cursor_color = get_theme().text.cursor_color;
+        scene.fill(Fill::NonZero, transform, cursor_color, None, &cursor_rect);
         scene.push_layer(
             BlendMode::default(),
             1.,
@@ -1,19 +1,24 @@
 use std::{
+    collections::HashSet,
+    ops::Range,
     sync::{Arc, Mutex},
     time::Instant,
 };
 
-use accesskit::{Node, Role};
-use kurbo::{Point, Size, Vec2};
+use accesskit::{Action, ActionData, Node, NodeId, Role};
+use kurbo::{Affine, Point, Size, Vec2};
 use masonry::core::{
     AccessCtx, AccessEvent, BoxConstraints, ComposeCtx, EventCtx, LayoutCtx,
     PaintCtx, PointerEvent, PropertiesMut, PropertiesRef, QueryCtx, RegisterCtx,
-    TextEvent, Update, UpdateCtx, Widget, WidgetId,
+    TextEvent, Update, UpdateCtx, Widget, WidgetId, WidgetMut,
 };
 use parley::StyleProperty;
 use smallvec::SmallVec;
-use tracing::debug;
-use vello::{peniko::Color, Scene};
+use tracing::{debug, warn};
+use vello::{
+    peniko::{Color, Fill},
+    Scene,
+};
 use winit::window::CursorIcon;
 use xilem::{
     core::{Message, MessageResult, View, ViewMarker},
@@ -24,6 +29,11 @@ use xilem::{
 use crate::{
     buffer::BufferView,
     code_text_layout::{CodeTextBrush, CodeTextLayout},
+    command::EditorCommand,
+    config::get_config,
+    generation::Generation,
+    keymap::{KeyBinding, KeyPress, Keymap},
+    theme::{get_theme, reset_zoom, toggle_dark_light, zoom_in, zoom_out},
 };
 
 pub struct CodeWidget {
@@ -31,6 +41,25 @@ pub struct CodeWidget {
     text_layout: CodeTextLayout,
     buffer_view: Arc<Mutex<BufferView>>,
     wrap_word: bool,
+    // Non-editable text shown at the byte offset of the buffer (e.g. the
+    // completion suggestion).
+    ghost_text: Option<(usize, String)>,
+    // Generation of the theme the text is laid out with.
+    theme_generation: Generation,
+    // Typing an opening bracket or a quote inserts the closing one too.
+    auto_close_pairs: bool,
+    // Byte offsets of the closing characters inserted automatically. Typing
+    // such character just moves over it.
+    auto_inserted_closes: HashSet<usize>,
+    highlight_current_line: bool,
+    // The view is centered on the cursor at the next layout (e.g. after
+    // jumping to a line).
+    scroll_to_cursor: bool,
+    keymap: Keymap,
+    // Keys pressed so far of a multi-key binding.
+    pending_keys: Vec<KeyBinding>,
+    // Generation of the buffer text which is laid out.
+    buffer_generation: Generation,
 }
 
 impl CodeWidget {
@@ -40,13 +69,354 @@ impl CodeWidget {
             text_changed: false,
             text_layout,
             buffer_view: buffer_view.clone(),
-            wrap_word: true,
+            wrap_word: get_config().word_wrap,
+            ghost_text: None,
+            theme_generation: get_theme().generation,
+            auto_close_pairs: true,
+            auto_inserted_closes: HashSet::new(),
+            highlight_current_line: true,
+            scroll_to_cursor: false,
+            keymap: Keymap::code_editor(),
+            pending_keys: Vec::new(),
+            buffer_generation: Generation::default(),
+        }
+    }
+
+    /// Replaces the key bindings (the default is [`Keymap::code_editor`]).
+    pub fn set_keymap(this: &mut WidgetMut<'_, Self>, keymap: Keymap) {
+        this.widget.keymap = keymap;
+        this.widget.pending_keys.clear();
+    }
+
+    /// Enables inserting the closing bracket or quote together with the
+    /// opening one (enabled by default).
+    pub fn set_auto_close_pairs(this: &mut WidgetMut<'_, Self>, auto_close: bool) {
+        this.widget.auto_close_pairs = auto_close;
+        if !auto_close {
+            this.widget.auto_inserted_closes.clear();
         }
     }
 
     pub fn buffer_view(&self) -> &Arc<Mutex<BufferView>> {
         &self.buffer_view
     }
+
+    /// Shows another view, e.g. of the buffer opened by the
+    /// [`crate::editor::Editor`].
+    pub fn set_buffer_view(
+        this: &mut WidgetMut<'_, Self>,
+        buffer_view: &Arc<Mutex<BufferView>>,
+    ) {
+        this.widget.buffer_view = buffer_view.clone();
+        this.widget.ghost_text = None;
+        this.widget.auto_inserted_closes.clear();
+        this.widget.pending_keys.clear();
+        this.ctx.request_layout();
+        this.ctx.request_accessibility_update();
+    }
+
+    /// Relayouts the text when the buffer was changed outside of the widget,
+    /// e.g. reverted by the [`crate::editor::Editor`].
+    pub fn sync_buffer(this: &mut WidgetMut<'_, Self>) {
+        let generation = this
+            .widget
+            .buffer_view
+            .lock()
+            .unwrap()
+            .buffer()
+            .generation();
+        if this.widget.buffer_generation != generation {
+            this.widget.auto_inserted_closes.clear();
+            this.ctx.request_layout();
+            this.ctx.request_accessibility_update();
+        }
+    }
+
+    /// Moves the cursor to the zero-based `line` and `column` (the start of
+    /// the line when `None`) and centers the view on it.
+    pub fn goto(this: &mut WidgetMut<'_, Self>, line: usize, column: Option<usize>) {
+        this.widget.goto_position(line, column);
+        this.ctx.request_layout();
+    }
+
+    fn goto_position(&mut self, line: usize, column: Option<usize>) {
+        let mut buffer_view = self.buffer_view.lock().unwrap();
+        buffer_view.goto_line(line);
+        if let Some(column) = column {
+            buffer_view.goto_column(column);
+        }
+        self.scroll_to_cursor = true;
+    }
+
+    /// Enables highlighting the line with the cursor (enabled by default).
+    pub fn set_highlight_current_line(
+        this: &mut WidgetMut<'_, Self>,
+        highlight: bool,
+    ) {
+        this.widget.highlight_current_line = highlight;
+        this.ctx.request_paint_only();
+    }
+
+    /// Shows dimmed non-editable text at the byte offset of the buffer. The
+    /// ghost text is accepted by `Tab` and dismissed by any other editing.
+    pub fn set_ghost_text(
+        this: &mut WidgetMut<'_, Self>,
+        ghost_text: Option<(usize, String)>,
+    ) {
+        this.widget.ghost_text = ghost_text;
+        this.ctx.request_layout();
+    }
+
+    /// Relayouts the text when the theme was changed (see
+    /// [`crate::theme::set_theme`]).
+    pub fn sync_theme(this: &mut WidgetMut<'_, Self>) {
+        let generation = get_theme().generation;
+        if this.widget.theme_generation != generation {
+            this.widget.theme_generation = generation;
+            this.ctx.request_layout();
+        }
+    }
+
+    /// Returns the text which is laid out, i.e. the buffer content with the
+    /// ghost text, and the range of the ghost text in it.
+    fn layout_text(&mut self) -> (String, Option<Range<usize>>) {
+        let mut text: String = self
+            .buffer_view
+            .lock()
+            .unwrap()
+            .buffer()
+            .rope
+            .slice(..)
+            .into();
+        let Some((offset, ghost_text)) = &self.ghost_text else {
+            return (text, None);
+        };
+        if !text.is_char_boundary(*offset) {
+            warn!("Ghost text offset {offset} is not valid; removing ghost text");
+            self.ghost_text = None;
+            return (text, None);
+        }
+        text.insert_str(*offset, ghost_text);
+        let range = *offset..*offset + ghost_text.len();
+        (text, Some(range))
+    }
+
+    /// Maps the byte index of the buffer to the byte index of the laid out
+    /// text.
+    fn buffer_to_layout_index(&self, index: usize) -> usize {
+        match &self.ghost_text {
+            Some((offset, ghost_text)) if index > *offset => {
+                index + ghost_text.len()
+            }
+            _ => index,
+        }
+    }
+
+    /// Maps the byte index of the laid out text to the byte index of the
+    /// buffer. Indices within the ghost text are mapped to its offset.
+    fn layout_to_buffer_index(&self, index: usize) -> usize {
+        match &self.ghost_text {
+            Some((offset, ghost_text)) if index > *offset => {
+                index.saturating_sub(ghost_text.len()).max(*offset)
+            }
+            _ => index,
+        }
+    }
+
+    fn accept_ghost_text(&mut self) -> bool {
+        let Some((offset, ghost_text)) = self.ghost_text.take() else {
+            return false;
+        };
+        self.buffer_view()
+            .lock()
+            .unwrap()
+            .set_position_bytes(offset);
+        self.edit_buffer(|buffer_view| buffer_view.insert_at_point(&ghost_text));
+        true
+    }
+
+    /// Runs the `edit` of the buffer and moves the offsets of the auto
+    /// inserted closing characters accordingly. The `edit` is expected to
+    /// insert or remove text at the point.
+    fn edit_buffer<F>(&mut self, edit: F)
+    where
+        F: FnOnce(&mut BufferView),
+    {
+        let mut buffer_view = self.buffer_view.lock().unwrap();
+        let position = buffer_view.position_bytes();
+        let len = buffer_view.buffer().rope.len_bytes();
+        edit(&mut buffer_view);
+        let start = position.min(buffer_view.position_bytes());
+        let new_len = buffer_view.buffer().rope.len_bytes();
+        if new_len == len {
+            return;
+        }
+        self.auto_inserted_closes = self
+            .auto_inserted_closes
+            .drain()
+            .filter_map(|close| {
+                if close < start {
+                    Some(close)
+                } else if new_len > len {
+                    Some(close + new_len - len)
+                } else if close >= start + len - new_len {
+                    Some(close + new_len - len)
+                } else {
+                    // The closing character was removed.
+                    None
+                }
+            })
+            .collect();
+    }
+
+    /// Inserts the typed `text` at the point. With `auto_close_pairs` the
+    /// opening brackets and quotes get their closing pair and typing the auto
+    /// inserted closing character moves over it.
+    fn type_text(&mut self, text: &str) {
+        let mut chars = text.chars();
+        let typed_char = match (chars.next(), chars.next()) {
+            (Some(c), None) if self.auto_close_pairs => c,
+            _ => {
+                self.edit_buffer(|buffer_view| buffer_view.insert_at_point(text));
+                return;
+            }
+        };
+
+        let (position, char_at_point, char_before_point) = {
+            let buffer_view = self.buffer_view.lock().unwrap();
+            (
+                buffer_view.position_bytes(),
+                buffer_view.char_at_point(),
+                buffer_view.char_before_point(),
+            )
+        };
+        if char_at_point == Some(typed_char)
+            && self.auto_inserted_closes.remove(&position)
+        {
+            self.buffer_view.lock().unwrap().move_point_forward_char();
+            return;
+        }
+
+        let closing_char = closing_pair(typed_char).filter(|_| {
+            // Apostrophes in words (e.g. "don't") are not quotes.
+            !(typed_char == '\'' || typed_char == '"')
+                || !char_before_point.is_some_and(char::is_alphanumeric)
+        });
+        match closing_char {
+            Some(closing_char) => {
+                self.edit_buffer(|buffer_view| {
+                    buffer_view.insert_at_point(text);
+                    buffer_view.insert_at_point(&closing_char.to_string());
+                    buffer_view.move_point_backward_char();
+                });
+                self.auto_inserted_closes.insert(position + text.len());
+            }
+            None => {
+                self.edit_buffer(|buffer_view| buffer_view.insert_at_point(text));
+            }
+        }
+    }
+
+    /// Executes the `command` bound to the pressed keys.
+    fn execute_command(&mut self, ctx: &mut EventCtx, command: &EditorCommand) {
+        match command {
+            EditorCommand::ZoomIn
+            | EditorCommand::ZoomOut
+            | EditorCommand::ResetZoom
+            | EditorCommand::ToggleDarkLight => {
+                match command {
+                    EditorCommand::ZoomIn => zoom_in(),
+                    EditorCommand::ZoomOut => zoom_out(),
+                    EditorCommand::ResetZoom => reset_zoom(),
+                    _ => toggle_dark_light(),
+                }
+                self.theme_generation = get_theme().generation;
+                ctx.request_layout();
+                ctx.set_handled();
+                return;
+            }
+            EditorCommand::NewFile
+            | EditorCommand::OpenFile(_)
+            | EditorCommand::Save
+            | EditorCommand::SaveAs(_)
+            | EditorCommand::Revert
+            | EditorCommand::Quit => {
+                // The file commands are executed by the editor, see
+                // `CodeView::on_editor_command`.
+                ctx.submit_action(masonry::core::Action::Other(Box::new(
+                    command.clone(),
+                )));
+                ctx.set_handled();
+                return;
+            }
+            EditorCommand::InsertTab if self.accept_ghost_text() => {}
+            EditorCommand::InsertTab => {
+                let indent = get_config().indent_text();
+                self.edit_buffer(|buffer_view| buffer_view.insert_at_point(&indent))
+            }
+            EditorCommand::InsertNewLine => self.edit_buffer(|buffer_view| {
+                // The new line keeps the indentation of the current one.
+                let indent = buffer_view.current_line_indent();
+                buffer_view.insert_new_line();
+                buffer_view.insert_at_point(&indent);
+            }),
+            EditorCommand::InsertText(text) => self.type_text(text),
+            EditorCommand::MoveForwardChar => {
+                self.edit_buffer(BufferView::move_point_forward_char)
+            }
+            EditorCommand::MoveBackwardChar => {
+                self.edit_buffer(BufferView::move_point_backward_char)
+            }
+            EditorCommand::MoveForwardLine => {
+                self.edit_buffer(BufferView::move_point_forward_line)
+            }
+            EditorCommand::MoveBackwardLine => {
+                self.edit_buffer(BufferView::move_point_backward_line)
+            }
+            EditorCommand::DeleteAtPoint => {
+                self.edit_buffer(BufferView::delete_at_point)
+            }
+            EditorCommand::DeleteBackward => self.delete_backward(),
+        }
+        self.text_changed = true;
+        self.ghost_text = None;
+        ctx.request_layout();
+        ctx.request_accessibility_update();
+        ctx.set_handled();
+    }
+
+    /// Deletes the character before the point. When the point is between an
+    /// opening character and its auto inserted closing pair both are deleted.
+    fn delete_backward(&mut self) {
+        let delete_pair = {
+            let buffer_view = self.buffer_view.lock().unwrap();
+            self.auto_close_pairs
+                && self
+                    .auto_inserted_closes
+                    .contains(&buffer_view.position_bytes())
+                && buffer_view.char_before_point().and_then(closing_pair)
+                    == buffer_view.char_at_point()
+        };
+        self.edit_buffer(|buffer_view| {
+            buffer_view.move_point_backward_char();
+            buffer_view.delete_at_point();
+            if delete_pair {
+                buffer_view.delete_at_point();
+            }
+        });
+    }
+}
+
+/// Returns the closing character for the opening bracket or quote `c`.
+fn closing_pair(c: char) -> Option<char> {
+    match c {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '"' => Some('"'),
+        '\'' => Some('\''),
+        _ => None,
+    }
 }
 
 // TODO: List of decorations for code editor:
@@ -80,12 +450,14 @@ impl Widget for CodeWidget {
             let cursor_point = self.text_layout.cursor_for_point(
                 (point.x - window_origin.x, point.y - window_origin.y).into(),
             );
+            let index = self.layout_to_buffer_index(cursor_point.index());
             let mut buffer_view = self.buffer_view().lock().unwrap();
 
             debug!("CodeWidget::on_pointer_event; cursor_point: {cursor_point:?}");
-            buffer_view.set_position_bytes(cursor_point.index());
+            buffer_view.set_position_bytes(index);
             ctx.request_focus();
             ctx.request_paint_only();
+            ctx.request_accessibility_update();
             ctx.set_handled();
         } else if let PointerEvent::MouseWheel(delta, _) = event {
             self.text_layout.scroll(Vec2::new(delta.x, delta.y));
@@ -101,75 +473,48 @@ impl Widget for CodeWidget {
         event: &TextEvent,
     ) {
         debug!("CodeWidget::on_text_event: {event:?}");
-        macro_rules! process_key {
-            ($action:ident) => {
-                self.text_changed = true;
-                let mut buffer_view = self.buffer_view().lock().unwrap();
-                buffer_view.$action();
-                ctx.request_layout();
-                ctx.set_handled();
-            };
-            ($action:ident, $param:expr) => {
-                self.text_changed = true;
-                let mut buffer_view = self.buffer_view().lock().unwrap();
-                buffer_view.$action($param);
-                ctx.request_layout();
-                ctx.set_handled();
-            };
-        }
         match event {
-            TextEvent::KeyboardKey(key_event, _modifiers_state) => {
+            TextEvent::KeyboardKey(key_event, modifiers_state) => {
                 if !key_event.state.is_pressed() {
                     return;
                 }
-                match &key_event.logical_key {
+                let key = &key_event.logical_key;
+                if matches!(key, winit::keyboard::Key::Named(_)) {
+                    debug!("winit::keyboard::Key::Named: {:?}", key);
+                }
+                let binding = KeyBinding::new(key.clone(), *modifiers_state);
+                match self.keymap.press(&mut self.pending_keys, binding) {
+                    KeyPress::Command(command) => {
+                        self.execute_command(ctx, &command);
+                        return;
+                    }
+                    KeyPress::Prefix => {
+                        // Waiting for the next key of the sequence.
+                        ctx.set_handled();
+                        return;
+                    }
+                    KeyPress::Modifier => return,
+                    KeyPress::Unbound(pending_keys) if pending_keys.len() > 1 => {
+                        debug!("CodeView unbound key sequence: {:?}", pending_keys);
+                        ctx.set_handled();
+                        return;
+                    }
+                    KeyPress::Unbound(_) => {}
+                }
+                match key {
                     winit::keyboard::Key::Named(named_key) => {
-                        debug!("winit::keyboard::Key::Named: {:?}", named_key);
-                        match named_key {
-                            winit::keyboard::NamedKey::Enter => {
-                                process_key!(insert_new_line);
-                            }
-                            winit::keyboard::NamedKey::Tab => {
-                                process_key!(insert_at_point, "\t");
-                            }
-                            winit::keyboard::NamedKey::Space => {
-                                process_key!(insert_at_point, " ");
-                            }
-                            winit::keyboard::NamedKey::ArrowUp => {
-                                process_key!(move_point_forward_line);
-                            }
-                            winit::keyboard::NamedKey::ArrowDown => {
-                                process_key!(move_point_backward_line);
-                            }
-                            winit::keyboard::NamedKey::ArrowLeft => {
-                                process_key!(move_point_backward_char);
-                            }
-                            winit::keyboard::NamedKey::ArrowRight => {
-                                process_key!(move_point_forward_char);
-                            }
-                            winit::keyboard::NamedKey::Delete => {
-                                process_key!(delete_at_point);
-                            }
-                            winit::keyboard::NamedKey::Backspace => {
-                                self.text_changed = true;
-                                let mut buffer_view =
-                                    self.buffer_view().lock().unwrap();
-                                buffer_view.move_point_backward_char();
-                                buffer_view.delete_at_point();
-                                ctx.request_layout();
-                                ctx.set_handled();
-                            }
-                            _ => {
-                                debug!(
-                                    "CodeView unimplemented Key::Named: {:?}",
-                                    named_key
-                                )
-                            }
-                        }
+                        debug!("CodeView unimplemented Key::Named: {:?}", named_key)
                     }
                     winit::keyboard::Key::Character(str) => {
                         debug!("winit::keyboard::Key::Character: {}", str);
-                        process_key!(insert_at_point, str);
+                        if !modifiers_state.control_key()
+                            && !modifiers_state.super_key()
+                        {
+                            self.execute_command(
+                                ctx,
+                                &EditorCommand::InsertText(str.to_string()),
+                            );
+                        }
                     }
                     winit::keyboard::Key::Unidentified(native_key) => {
                         debug!(
@@ -196,11 +541,26 @@ impl Widget for CodeWidget {
 
     fn on_access_event(
         &mut self,
-        _ctx: &mut EventCtx,
+        ctx: &mut EventCtx,
         _props: &mut PropertiesMut<'_>,
         event: &AccessEvent,
     ) {
         debug!("CodeWidget::on_access_event: {event:?}");
+        if event.action == Action::SetTextSelection {
+            if let Some(ActionData::SetTextSelection(selection)) = &event.data {
+                if let Some(bytes) =
+                    self.text_layout.selection_from_access(selection)
+                {
+                    let bytes = self.layout_to_buffer_index(bytes.start)
+                        ..self.layout_to_buffer_index(bytes.end);
+                    let mut buffer_view = self.buffer_view().lock().unwrap();
+                    buffer_view.set_selection_bytes(bytes);
+                    ctx.request_paint_only();
+                    ctx.request_accessibility_update();
+                    ctx.set_handled();
+                }
+            }
+        }
     }
 
     fn register_children(&mut self, _ctx: &mut RegisterCtx) {
@@ -224,14 +584,17 @@ impl Widget for CodeWidget {
         _props: &mut PropertiesMut<'_>,
         bc: &BoxConstraints,
     ) -> Size {
-        let text: String = self
-            .buffer_view
-            .lock()
-            .unwrap()
-            .buffer()
-            .rope
-            .slice(..)
-            .into();
+        self.buffer_generation =
+            self.buffer_view.lock().unwrap().buffer().generation();
+        let (text, ghost_range) = self.layout_text();
+        let (ghost_text_color, font) = {
+            let theme = get_theme();
+            (
+                theme.text.ghost_text_color,
+                theme.text.monospace_font_stack.clone(),
+            )
+        };
+        self.text_layout.set_font(font);
         let size = bc.max();
         self.text_layout.set_max_advance(Some(size.width as f32));
         let start = Instant::now();
@@ -271,6 +634,9 @@ impl Widget for CodeWidget {
             );
             b.push(StyleProperty::Underline(true), 300..332);
             b.push(StyleProperty::UnderlineBrush(curly_brush), 300..332);
+            if let Some(ghost_range) = ghost_range {
+                b.push(StyleProperty::Brush(ghost_text_color.into()), ghost_range);
+            }
             b
         });
         let since_the_epoch = start.elapsed();
@@ -278,6 +644,12 @@ impl Widget for CodeWidget {
             "Time of text layouting: {:?}s",
             since_the_epoch.as_secs_f32()
         );
+        if self.scroll_to_cursor {
+            self.scroll_to_cursor = false;
+            let position = self.buffer_view().lock().unwrap().position_bytes();
+            let position = self.buffer_to_layout_index(position);
+            self.text_layout.scroll_to_center(position, size.height);
+        }
         size
     }
 
@@ -288,11 +660,26 @@ impl Widget for CodeWidget {
         scene: &mut Scene,
     ) {
         debug!("CodeWidget::paint");
-        let position = {
+        let (position, selection) = {
             let buffer_view = self.buffer_view().lock().unwrap();
-            buffer_view.position_bytes()
+            (buffer_view.position_bytes(), buffer_view.selection_bytes())
         };
-        self.text_layout.draw(scene, position, ctx.size());
+        let position = self.buffer_to_layout_index(position);
+        let selection = self.buffer_to_layout_index(selection.start)
+            ..self.buffer_to_layout_index(selection.end);
+        scene.fill(
+            Fill::NonZero,
+            Affine::IDENTITY,
+            get_theme().background_color,
+            None,
+            &ctx.size().to_rect(),
+        );
+        if self.highlight_current_line {
+            self.text_layout
+                .draw_current_line(scene, position, ctx.size().width);
+        }
+        self.text_layout
+            .draw(scene, position, selection, ctx.size());
     }
 
     fn children_ids(&self) -> SmallVec<[WidgetId; 16]> {
@@ -338,39 +725,112 @@ impl Widget for CodeWidget {
 
     fn accessibility_role(&self) -> Role {
         debug!("CodeWidget::accessibility_role");
-        Role::TextInput
+        Role::MultilineTextInput
     }
 
     fn accessibility(
         &mut self,
-        _ctx: &mut AccessCtx,
+        ctx: &mut AccessCtx,
         _props: &PropertiesRef<'_>,
-        _node: &mut Node,
+        node: &mut Node,
     ) {
         debug!("CodeWidget::accessibility");
+        let (text, _) = self.layout_text();
+        let selection = self
+            .buffer_view()
+            .lock()
+            .unwrap()
+            .directed_selection_bytes();
+        let selection = self.buffer_to_layout_index(selection.start)
+            ..self.buffer_to_layout_index(selection.end);
+        let window_origin = ctx.window_origin();
+        self.text_layout.accessibility(
+            &text,
+            ctx.tree_update(),
+            node,
+            || NodeId::from(WidgetId::next()),
+            window_origin.x,
+            window_origin.y,
+        );
+        if let Some(selection) = self.text_layout.access_selection(selection) {
+            node.set_text_selection(selection);
+        }
     }
 }
 
-pub struct CodeView<F> {
+type EditorCommandCallback<State, Action> =
+    Box<dyn Fn(&mut State, EditorCommand) -> Action + Send + Sync + 'static>;
+
+pub struct CodeView<State, Action, F> {
     buffer_view: Arc<Mutex<BufferView>>,
     code_updated: F,
+    on_editor_command: Option<EditorCommandCallback<State, Action>>,
+    ghost_text: Option<(usize, String)>,
+    highlight_current_line: bool,
+    goto: Option<(usize, Option<usize>)>,
+}
+
+impl<State, Action, F> CodeView<State, Action, F> {
+    /// Sets the callback executing the file commands (new, open, save, save
+    /// as, revert and quit) bound to the keys, most likely by passing them to
+    /// [`crate::editor::Editor::execute`]. The commands are ignored without
+    /// the callback.
+    pub fn on_editor_command(
+        mut self,
+        on_editor_command: impl Fn(&mut State, EditorCommand) -> Action
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.on_editor_command = Some(Box::new(on_editor_command));
+        self
+    }
+
+    /// Shows dimmed non-editable text at the byte offset of the buffer (see
+    /// [`CodeWidget::set_ghost_text`]).
+    pub fn ghost_text(mut self, ghost_text: Option<(usize, String)>) -> Self {
+        self.ghost_text = ghost_text;
+        self
+    }
+
+    /// Moves the cursor to the line and the column (see [`CodeWidget::goto`]).
+    /// The cursor is moved when the value changes, so the user can move the
+    /// cursor elsewhere afterwards.
+    pub fn goto(mut self, goto: Option<(usize, Option<usize>)>) -> Self {
+        self.goto = goto;
+        self
+    }
+
+    /// Highlights the line with the cursor (see
+    /// [`CodeWidget::set_highlight_current_line`]).
+    pub fn highlight_current_line(mut self, highlight: bool) -> Self {
+        self.highlight_current_line = highlight;
+        self
+    }
 }
 
 pub fn code_view<State, Action>(
     buffer_view: &Arc<Mutex<BufferView>>,
     code_updated: impl Fn(&mut State) -> Action + Send + 'static,
-) -> CodeView<impl for<'a> Fn(&'a mut State) -> MessageResult<Action> + Send + 'static>
-{
+) -> CodeView<
+    State,
+    Action,
+    impl for<'a> Fn(&'a mut State) -> MessageResult<Action> + Send + 'static,
+> {
     CodeView {
         buffer_view: buffer_view.clone(),
         code_updated: move |state: &mut State| {
             MessageResult::Action(code_updated(state))
         },
+        on_editor_command: None,
+        ghost_text: None,
+        highlight_current_line: true,
+        goto: None,
     }
 }
 
-impl<F> ViewMarker for CodeView<F> {}
-impl<F, State, Action> View<State, Action, ViewCtx> for CodeView<F>
+impl<State, Action, F> ViewMarker for CodeView<State, Action, F> {}
+impl<F, State, Action> View<State, Action, ViewCtx> for CodeView<State, Action, F>
 where
     State: 'static,
     Action: 'static,
@@ -383,18 +843,43 @@ where
     fn build(&self, ctx: &mut ViewCtx) -> (Self::Element, Self::ViewState) {
         debug!("CodeView::build");
         ctx.with_leaf_action_widget(|ctx| {
-            ctx.new_pod(CodeWidget::new(&self.buffer_view))
+            let mut widget = CodeWidget::new(&self.buffer_view);
+            widget.ghost_text = self.ghost_text.clone();
+            widget.highlight_current_line = self.highlight_current_line;
+            if let Some((line, column)) = self.goto {
+                widget.goto_position(line, column);
+            }
+            ctx.new_pod(widget)
         })
     }
 
     fn rebuild(
         &self,
-        _prev: &Self,
+        prev: &Self,
         _view_state: &mut Self::ViewState,
         _ctx: &mut ViewCtx,
-        _element: xilem::core::Mut<Self::Element>,
+        mut element: xilem::core::Mut<Self::Element>,
     ) {
         debug!("CodeView::rebuild");
+        if !Arc::ptr_eq(&prev.buffer_view, &self.buffer_view) {
+            CodeWidget::set_buffer_view(&mut element, &self.buffer_view);
+        }
+        if prev.ghost_text != self.ghost_text {
+            CodeWidget::set_ghost_text(&mut element, self.ghost_text.clone());
+        }
+        if prev.goto != self.goto {
+            if let Some((line, column)) = self.goto {
+                CodeWidget::goto(&mut element, line, column);
+            }
+        }
+        if prev.highlight_current_line != self.highlight_current_line {
+            CodeWidget::set_highlight_current_line(
+                &mut element,
+                self.highlight_current_line,
+            );
+        }
+        CodeWidget::sync_theme(&mut element);
+        CodeWidget::sync_buffer(&mut element);
     }
 
     fn teardown(
@@ -416,16 +901,37 @@ where
     ) -> xilem::core::MessageResult<Action, Box<dyn Message>> {
         debug!("CodeView::message");
         match message.downcast::<masonry::core::Action>() {
-            Ok(action) => {
-                if let masonry::core::Action::TextChanged(_text) = *action {
+            Ok(action) => match *action {
+                masonry::core::Action::TextChanged(_text) => {
                     (self.code_updated)(app_state)
-                } else {
+                }
+                masonry::core::Action::Other(other) => {
+                    match other.downcast::<EditorCommand>() {
+                        Ok(command) => match &self.on_editor_command {
+                            Some(on_editor_command) => MessageResult::Action(
+                                on_editor_command(app_state, *command),
+                            ),
+                            None => {
+                                warn!("{command:?} has no handler, it is ignored");
+                                MessageResult::Nop
+                            }
+                        },
+                        Err(other) => {
+                            let action = masonry::core::Action::Other(other);
+                            tracing::error!(
+                                "Wrong action type in CodeView::message: {action:?}"
+                            );
+                            MessageResult::Stale(Box::new(action))
+                        }
+                    }
+                }
+                action => {
                     tracing::error!(
                         "Wrong action type in CodeView::message: {action:?}"
                     );
-                    MessageResult::Stale(action)
+                    MessageResult::Stale(Box::new(action))
                 }
-            }
+            },
             Err(message) => {
                 tracing::error!(
                     "Wrong message type in Button::message: {message:?}"
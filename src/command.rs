@@ -1,6 +1,29 @@
-pub struct Command {}
-// new file
-// save file, save as file
-// open file
-// revert file
-// quit editor
+use std::path::PathBuf;
+
+/// Commands executed by the editor widgets or by the [`crate::editor::Editor`]
+/// (the file commands). The keys are bound to them by the
+/// [`crate::keymap::Keymap`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EditorCommand {
+    InsertNewLine,
+    // Accepts the ghost text if there is one, otherwise inserts a tab.
+    InsertTab,
+    InsertText(String),
+    MoveForwardChar,
+    MoveBackwardChar,
+    MoveForwardLine,
+    MoveBackwardLine,
+    DeleteAtPoint,
+    DeleteBackward,
+    ZoomIn,
+    ZoomOut,
+    ResetZoom,
+    // Switches between the dark and light theme presets.
+    ToggleDarkLight,
+    NewFile,
+    OpenFile(PathBuf),
+    Save,
+    SaveAs(PathBuf),
+    Revert,
+    Quit,
+}
@@ -0,0 +1,257 @@
+//! The user's configuration: the theme overrides loaded from `config.toml`
+//! (see [`Config`] and [`Theme::user_config_path`]) and the editor settings
+//! loaded from `editor.toml` next to it. All fields of the editor settings
+//! are optional, the missing ones keep the defaults:
+//!
+//! ```toml
+//! tab_width = 4
+//! use_spaces = false
+//! word_wrap = true
+//! # theme_path = "/path/to/theme.toml"
+//! font_dirs = ["fonts", "fonts/nerd-fonts"]
+//! code_scrolling_speed = 2.0
+//! markdown_scrolling_speed = 3.0
+//! natural_scrolling = true
+//! ```
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{LazyLock, RwLock, RwLockReadGuard},
+};
+
+use eyre::Result;
+use kurbo::Vec2;
+use serde::Deserialize;
+
+use crate::theme::{PartialTheme, Theme};
+
+static CONFIG: LazyLock<RwLock<EditorConfig>> =
+    LazyLock::new(|| RwLock::new(EditorConfig::default()));
+
+/// The theme configuration. It has the fields of [`Theme`] (see
+/// [`PartialTheme`] for the format), the ones missing in the file keep the
+/// values of the current theme.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Config {
+    pub theme: PartialTheme,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config> {
+        let content = fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// Overwrites the fields of the current theme set in the configuration
+    /// and bumps the theme generation (see [`PartialTheme::apply`]).
+    pub fn apply(&self) {
+        self.theme.apply();
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EditorConfig {
+    /// Number of spaces inserted by `Tab` when `use_spaces` is set.
+    pub tab_width: usize,
+    pub use_spaces: bool,
+    /// Whether the code editor wraps the long lines.
+    pub word_wrap: bool,
+    /// Theme configuration used instead of the default one.
+    pub theme_path: Option<PathBuf>,
+    /// Directories with the fonts loaded at startup (see
+    /// [`EditorConfig::font_dirs`]).
+    pub font_dirs: Vec<PathBuf>,
+    /// Multipliers of the mouse wheel scroll deltas.
+    pub code_scrolling_speed: f64,
+    pub markdown_scrolling_speed: f64,
+    /// Moves the content in the direction of the wheel deltas reported by
+    /// the system, the opposite direction when unset.
+    pub natural_scrolling: bool,
+    /// Saves the files with exactly one line break at the end. Off by default
+    /// so saving doesn't touch the lines the user didn't edit.
+    pub ensure_trailing_newline: bool,
+}
+
+impl Default for EditorConfig {
+    fn default() -> Self {
+        EditorConfig {
+            tab_width: 4,
+            use_spaces: false,
+            word_wrap: true,
+            theme_path: None,
+            font_dirs: vec![
+                PathBuf::from("fonts"),
+                PathBuf::from("fonts/nerd-fonts"),
+            ],
+            code_scrolling_speed: 2.0,
+            markdown_scrolling_speed: 3.0,
+            natural_scrolling: true,
+            ensure_trailing_newline: false,
+        }
+    }
+}
+
+impl EditorConfig {
+    /// Returns the path of the user's editor settings, `editor.toml` in the
+    /// directory of [`Theme::user_config_path`].
+    pub fn user_config_path() -> Option<PathBuf> {
+        Some(Theme::user_config_path()?.with_file_name("editor.toml"))
+    }
+
+    pub fn from_toml<P: AsRef<Path>>(path: P) -> Result<EditorConfig> {
+        let content = fs::read_to_string(&path)?;
+        EditorConfig::from_toml_str(&content)
+    }
+
+    pub fn from_toml_str(content: &str) -> Result<EditorConfig> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// Returns the text inserted by `Tab`.
+    pub fn indent_text(&self) -> String {
+        if self.use_spaces {
+            " ".repeat(self.tab_width)
+        } else {
+            "\t".to_string()
+        }
+    }
+
+    /// Returns the distance the content is moved by the mouse wheel `delta`
+    /// multiplied by the `speed` (one of the `*_scrolling_speed` fields). The
+    /// positive `y` moves the content down.
+    pub fn scroll_delta(&self, delta: Vec2, speed: f64) -> Vec2 {
+        let direction = if self.natural_scrolling { 1.0 } else { -1.0 };
+        delta * speed * direction
+    }
+
+    /// Returns the font directories. The relative ones which don't exist in
+    /// the current directory are looked up in the `font_roots`; they are kept
+    /// as they are when they are not found there either.
+    pub fn font_dirs(&self) -> Vec<PathBuf> {
+        let roots = font_roots();
+        self.font_dirs
+            .iter()
+            .map(|dir| {
+                if dir.is_absolute() || dir.exists() {
+                    return dir.clone();
+                }
+                roots
+                    .iter()
+                    .map(|root| root.join(dir))
+                    .find(|dir| dir.exists())
+                    .unwrap_or_else(|| dir.clone())
+            })
+            .collect()
+    }
+
+    /// Returns the path of the theme configuration to load.
+    pub fn theme_path(&self) -> Option<PathBuf> {
+        self.theme_path.clone().or_else(Theme::user_config_path)
+    }
+}
+
+/// Returns the directories the relative font directories are looked up in:
+/// the directory of the executable (the fonts bundled with the editor), the
+/// directory of the user's configuration and, in the debug builds only, the
+/// source tree so `cargo run` finds the fonts of the repository.
+fn font_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if let Some(dir) = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        roots.push(dir);
+    }
+    if let Some(dir) = Theme::user_config_path()
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+    {
+        roots.push(dir);
+    }
+    #[cfg(debug_assertions)]
+    roots.push(PathBuf::from(env!("CARGO_MANIFEST_DIR")));
+    roots
+}
+
+pub fn get_config<'a>() -> RwLockReadGuard<'a, EditorConfig> {
+    (*CONFIG).read().unwrap()
+}
+
+pub fn set_config(config: EditorConfig) {
+    *(*CONFIG).write().unwrap() = config;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, process};
+
+    use kurbo::Vec2;
+
+    use super::{Config, EditorConfig};
+
+    #[test]
+    fn missing_fields_keep_defaults() {
+        let config = EditorConfig::from_toml_str("use_spaces = true\n").unwrap();
+        assert_eq!(
+            config,
+            EditorConfig {
+                use_spaces: true,
+                ..EditorConfig::default()
+            }
+        );
+        assert_eq!(config.indent_text(), "    ");
+        assert_eq!(EditorConfig::default().indent_text(), "\t");
+    }
+
+    #[test]
+    fn parse_all_fields() {
+        let config = EditorConfig::from_toml_str(
+            "tab_width = 2\nword_wrap = false\ntheme_path = \"theme.toml\"\n\
+             font_dirs = []\ncode_scrolling_speed = 1.5\n",
+        )
+        .unwrap();
+        assert_eq!(config.tab_width, 2);
+        assert!(!config.word_wrap);
+        assert_eq!(config.theme_path(), Some(PathBuf::from("theme.toml")));
+        assert!(config.font_dirs.is_empty());
+        assert_eq!(config.code_scrolling_speed, 1.5);
+        assert!(EditorConfig::from_toml_str("unknown = 1\n").is_err());
+    }
+
+    #[test]
+    fn scroll_direction() {
+        let mut config = EditorConfig::default();
+        let delta = Vec2::new(0.0, 1.0);
+        assert_eq!(config.scroll_delta(delta, 2.0), Vec2::new(0.0, 2.0));
+        config.natural_scrolling = false;
+        assert_eq!(config.scroll_delta(delta, 2.0), Vec2::new(0.0, -2.0));
+    }
+
+    #[test]
+    fn missing_font_dirs_are_kept() {
+        let absolute = env::temp_dir().join("wrenched-no-such-fonts");
+        let config = EditorConfig {
+            font_dirs: vec![PathBuf::from("no-such-fonts"), absolute.clone()],
+            ..EditorConfig::default()
+        };
+        assert_eq!(
+            config.font_dirs(),
+            vec![PathBuf::from("no-such-fonts"), absolute]
+        );
+    }
+
+    #[test]
+    fn load_theme_config() {
+        let path = env::temp_dir()
+            .join(format!("wrenched-config-test-{}.toml", process::id()));
+        fs::write(&path, "scale = 1.5\n[markdown]\nlink_color = \"#4d9df0\"\n")
+            .unwrap();
+        let config = Config::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(config.theme.scale, Some(1.5));
+        assert!(config.theme.text.is_none());
+        assert!(config.theme.markdown.unwrap().link_color.is_some());
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use eyre::{bail, eyre, Result};
+
+use crate::{
+    buffer::{Buffer, BufferId, BufferRegistry, BufferView},
+    command::EditorCommand,
+};
+
+/// Owns the open buffers and executes the file commands (new, open, save,
+/// save as, revert and quit) on them. The editing commands are executed by
+/// the widgets.
+#[derive(Debug, Default)]
+pub struct Editor {
+    buffers: BufferRegistry,
+    // The buffer the commands apply to.
+    current: Option<BufferId>,
+    quit_requested: bool,
+    // See `Buffer::set_ensure_trailing_newline`.
+    ensure_trailing_newline: bool,
+}
+
+impl Editor {
+    pub fn new() -> Editor {
+        Editor::default()
+    }
+
+    pub fn buffers(&self) -> &BufferRegistry {
+        &self.buffers
+    }
+
+    pub fn current_buffer_id(&self) -> Option<BufferId> {
+        self.current
+    }
+
+    pub fn current_buffer(&self) -> Option<&Arc<Mutex<Buffer>>> {
+        self.buffers.get(self.current?)
+    }
+
+    /// Makes the buffer with the `id` the one the commands apply to.
+    pub fn switch_to(&mut self, id: BufferId) -> Result<()> {
+        if self.buffers.get(id).is_none() {
+            bail!("There is no buffer with id {id:?}");
+        }
+        self.current = Some(id);
+        Ok(())
+    }
+
+    /// Creates a new view of the current buffer. The views of one buffer
+    /// have their own points.
+    pub fn new_view(&self) -> Option<BufferView> {
+        self.buffers.view(self.current?)
+    }
+
+    /// Sets the trailing line break policy (see
+    /// [`Buffer::set_ensure_trailing_newline`]) of the open buffers and of the
+    /// buffers opened later.
+    pub fn set_ensure_trailing_newline(&mut self, ensure: bool) {
+        self.ensure_trailing_newline = ensure;
+        for (_, buffer) in self.buffers.iter() {
+            buffer.lock().unwrap().set_ensure_trailing_newline(ensure);
+        }
+    }
+
+    /// Returns `true` when the `Quit` command succeeded.
+    pub fn quit_requested(&self) -> bool {
+        self.quit_requested
+    }
+
+    pub fn execute(&mut self, command: EditorCommand) -> Result<()> {
+        match command {
+            EditorCommand::NewFile => {
+                self.push_buffer(Buffer::new());
+            }
+            EditorCommand::OpenFile(path) => self.open(&path)?,
+            EditorCommand::Save => {
+                let mut buffer = self.lock_current()?;
+                if buffer.path().is_none() {
+                    bail!("The buffer has no file yet, use save as");
+                }
+                buffer.save()?;
+            }
+            EditorCommand::SaveAs(path) => self.lock_current()?.save_as(path)?,
+            EditorCommand::Revert => self.lock_current()?.revert()?,
+            EditorCommand::Quit => {
+                let modified = self
+                    .buffers
+                    .iter()
+                    .filter(|(_, buffer)| buffer.lock().unwrap().is_modified())
+                    .count();
+                if modified > 0 {
+                    bail!("Refusing to quit with {modified} modified buffer(s)");
+                }
+                self.quit_requested = true;
+            }
+            command => bail!("{command:?} is not executed by the editor"),
+        }
+        Ok(())
+    }
+
+    /// Makes the buffer of the file at `path` current. The file is loaded
+    /// when it's not open yet.
+    fn open(&mut self, path: &Path) -> Result<()> {
+        match self.buffers.find_by_path(path) {
+            Some(id) => self.current = Some(id),
+            None => self.push_buffer(Buffer::load(path)?),
+        }
+        Ok(())
+    }
+
+    fn push_buffer(&mut self, mut buffer: Buffer) {
+        buffer.set_ensure_trailing_newline(self.ensure_trailing_newline);
+        self.current = Some(self.buffers.insert(buffer));
+    }
+
+    fn lock_current(&self) -> Result<std::sync::MutexGuard<'_, Buffer>> {
+        let buffer = self
+            .current_buffer()
+            .ok_or_else(|| eyre!("There is no open buffer"))?;
+        Ok(buffer.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::Editor;
+    use crate::command::EditorCommand;
+
+    #[test]
+    fn quit_refuses_modified_buffers() {
+        let mut editor = Editor::new();
+        editor.execute(EditorCommand::NewFile).unwrap();
+        editor.new_view().unwrap().insert_at_point("text");
+
+        assert!(editor.execute(EditorCommand::Quit).is_err());
+        assert!(!editor.quit_requested());
+        assert!(editor.execute(EditorCommand::Save).is_err());
+    }
+
+    #[test]
+    fn save_as_open_and_revert() {
+        let path = env::temp_dir()
+            .join(format!("wrenched-editor-test-{}.txt", process::id()));
+        let mut editor = Editor::new();
+        editor.execute(EditorCommand::NewFile).unwrap();
+        let saved = editor.current_buffer_id().unwrap();
+        editor.new_view().unwrap().insert_at_point("saved");
+        editor.execute(EditorCommand::SaveAs(path.clone())).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "saved");
+
+        editor.new_view().unwrap().insert_at_point("unsaved ");
+        editor.execute(EditorCommand::Revert).unwrap();
+        let buffer = editor.current_buffer().unwrap();
+        assert_eq!(buffer.lock().unwrap().rope.to_string(), "saved");
+
+        // The open file is not loaded twice.
+        editor.execute(EditorCommand::NewFile).unwrap();
+        editor
+            .execute(EditorCommand::OpenFile(path.clone()))
+            .unwrap();
+        assert_eq!(editor.buffers().len(), 2);
+        assert_eq!(editor.current_buffer_id(), Some(saved));
+
+        fs::remove_file(path).unwrap();
+        editor.execute(EditorCommand::Quit).unwrap();
+        assert!(editor.quit_requested());
+    }
+
+    #[test]
+    fn views_share_buffer() {
+        let mut editor = Editor::new();
+        editor.execute(EditorCommand::NewFile).unwrap();
+        let first = editor.current_buffer_id().unwrap();
+        let mut view = editor.new_view().unwrap();
+        let other_view = editor.new_view().unwrap();
+        view.insert_at_point("shared");
+        assert_eq!(other_view.buffer().rope.to_string(), "shared");
+
+        editor.execute(EditorCommand::NewFile).unwrap();
+        assert_ne!(editor.current_buffer_id(), Some(first));
+        editor.switch_to(first).unwrap();
+        assert_eq!(editor.current_buffer_id(), Some(first));
+    }
+
+    #[test]
+    fn delete_through_other_view() {
+        let mut editor = Editor::new();
+        editor.execute(EditorCommand::NewFile).unwrap();
+        let mut view = editor.new_view().unwrap();
+        let mut other_view = editor.new_view().unwrap();
+        view.insert_at_point("shared text");
+        other_view.goto_end_of_buffer();
+
+        view.set_selection_bytes(6..11);
+        view.delete_at_point();
+        assert_eq!(other_view.buffer().rope.to_string(), "shared");
+        // The point of the other view is kept inside of the shorter text.
+        assert_eq!(other_view.position_bytes(), 6);
+        other_view.move_point_backward_char();
+        other_view.insert_at_point("!");
+        assert_eq!(view.buffer().rope.to_string(), "share!d");
+    }
+}
@@ -13,4 +13,45 @@ impl Generation {
     pub fn nudge(&mut self) {
         self.0 = self.0.wrapping_add(1);
     }
+
+    /// Returns `true` when `self` was nudged from `other` (and less than half
+    /// of the `u32` range of nudges happened in between, so the wrapping
+    /// doesn't matter). A generation isn't newer than itself.
+    pub fn is_newer_than(&self, other: Generation) -> bool {
+        let age = self.age_since(other);
+        age != 0 && age < u32::MAX / 2
+    }
+
+    /// Returns the number of nudges from the `baseline` to `self`.
+    pub fn age_since(&self, baseline: Generation) -> u32 {
+        self.0.wrapping_sub(baseline.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Generation;
+
+    #[test]
+    fn newer_generation() {
+        let old = Generation::default();
+        let mut new = old;
+        assert!(!new.is_newer_than(old));
+        new.nudge();
+        new.nudge();
+        assert!(new.is_newer_than(old));
+        assert!(!old.is_newer_than(new));
+        assert_eq!(new.age_since(old), 2);
+    }
+
+    #[test]
+    fn newer_generation_after_wrapping() {
+        let old = Generation(u32::MAX);
+        let mut new = old;
+        new.nudge();
+        assert_eq!(new, Generation(0));
+        assert!(new.is_newer_than(old));
+        assert!(!old.is_newer_than(new));
+        assert_eq!(new.age_since(old), 1);
+    }
 }
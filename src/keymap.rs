@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+
+use winit::keyboard::{Key, ModifiersState, NamedKey};
+
+use crate::command::EditorCommand;
+
+/// Key together with the modifiers held while pressing it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+    key: Key,
+    modifiers: ModifiersState,
+}
+
+impl KeyBinding {
+    /// The shift is ignored for the character keys, it's already reflected by
+    /// the character (e.g. `+` instead of `=`).
+    pub fn new(key: Key, mut modifiers: ModifiersState) -> KeyBinding {
+        if matches!(key, Key::Character(_)) {
+            modifiers.remove(ModifiersState::SHIFT);
+        }
+        KeyBinding { key, modifiers }
+    }
+
+    pub fn named(key: NamedKey) -> KeyBinding {
+        KeyBinding::new(Key::Named(key), ModifiersState::empty())
+    }
+
+    pub fn control(character: &str) -> KeyBinding {
+        KeyBinding::new(Key::Character(character.into()), ModifiersState::CONTROL)
+    }
+
+    /// Returns `true` for the modifier keys pressed alone (e.g. `Control`).
+    pub fn is_modifier(&self) -> bool {
+        matches!(
+            self.key,
+            Key::Named(
+                NamedKey::Control
+                    | NamedKey::Shift
+                    | NamedKey::Alt
+                    | NamedKey::AltGraph
+                    | NamedKey::Super
+                    | NamedKey::Meta
+                    | NamedKey::Hyper
+            )
+        )
+    }
+}
+
+/// Result of pressing a key, see [`Keymap::press`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyPress {
+    /// The pressed keys are bound to the command.
+    Command(EditorCommand),
+    /// The pressed keys are a prefix of a multi-key binding.
+    Prefix,
+    /// The pressed keys are not bound.
+    Unbound(Vec<KeyBinding>),
+    /// A modifier key alone, the keys pressed so far are kept.
+    Modifier,
+}
+
+#[derive(Clone, Debug)]
+pub enum KeymapEntry {
+    Command(EditorCommand),
+    // The key is a prefix, the command is looked up by the next key in the
+    // nested keymap.
+    Keymap(Keymap),
+}
+
+/// Maps the keys to the editor commands. The multi-key sequences (e.g.
+/// `C-x C-s`) are done by nested keymaps.
+#[derive(Clone, Debug, Default)]
+pub struct Keymap {
+    bindings: HashMap<KeyBinding, KeymapEntry>,
+}
+
+impl Keymap {
+    pub fn new() -> Keymap {
+        Keymap::default()
+    }
+
+    /// Binds the `command` to the sequence of `keys`. The nested keymaps are
+    /// created for the prefix keys. A prefix bound to a command is replaced
+    /// by a nested keymap.
+    pub fn bind(&mut self, keys: &[KeyBinding], command: EditorCommand) {
+        let Some((last, prefix)) = keys.split_last() else {
+            return;
+        };
+        let mut keymap = self;
+        for key in prefix {
+            let entry = keymap
+                .bindings
+                .entry(key.clone())
+                .or_insert_with(|| KeymapEntry::Keymap(Keymap::new()));
+            if let KeymapEntry::Command(_) = entry {
+                *entry = KeymapEntry::Keymap(Keymap::new());
+            }
+            let KeymapEntry::Keymap(nested) = entry else {
+                unreachable!();
+            };
+            keymap = nested;
+        }
+        keymap
+            .bindings
+            .insert(last.clone(), KeymapEntry::Command(command));
+    }
+
+    /// Returns the entry bound to the sequence of `keys`. Returns `None` when
+    /// the sequence is not bound.
+    pub fn lookup(&self, keys: &[KeyBinding]) -> Option<&KeymapEntry> {
+        let (first, rest) = keys.split_first()?;
+        let entry = self.bindings.get(first)?;
+        match (entry, rest.is_empty()) {
+            (_, true) => Some(entry),
+            (KeymapEntry::Keymap(nested), false) => nested.lookup(rest),
+            (KeymapEntry::Command(_), false) => None,
+        }
+    }
+
+    /// Like [`Keymap::lookup`], but when the sequence is not bound and its
+    /// last key is a named key with modifiers, the key without the modifiers
+    /// is looked up instead (e.g. `Shift+Enter` works like `Enter`).
+    pub fn lookup_or_unmodified(&self, keys: &[KeyBinding]) -> Option<&KeymapEntry> {
+        self.lookup(keys).or_else(|| {
+            let (last, prefix) = keys.split_last()?;
+            if !matches!(last.key, Key::Named(_)) || last.modifiers.is_empty() {
+                return None;
+            }
+            let mut keys = prefix.to_vec();
+            keys.push(KeyBinding::new(last.key.clone(), ModifiersState::empty()));
+            self.lookup(&keys)
+        })
+    }
+
+    /// Appends the `key` to the `pending_keys` and looks the sequence up (see
+    /// [`Keymap::lookup_or_unmodified`]). The `pending_keys` are cleared unless
+    /// they are a prefix of a binding. The modifier keys are skipped, so
+    /// pressing `Control` again between `C-x` and `C-s` keeps the sequence.
+    pub fn press(
+        &self,
+        pending_keys: &mut Vec<KeyBinding>,
+        key: KeyBinding,
+    ) -> KeyPress {
+        if key.is_modifier() {
+            return KeyPress::Modifier;
+        }
+        pending_keys.push(key);
+        match self.lookup_or_unmodified(pending_keys) {
+            Some(KeymapEntry::Command(command)) => {
+                pending_keys.clear();
+                KeyPress::Command(command.clone())
+            }
+            Some(KeymapEntry::Keymap(_)) => KeyPress::Prefix,
+            None => KeyPress::Unbound(std::mem::take(pending_keys)),
+        }
+    }
+
+    /// Returns the keymap with the default bindings of the code editor.
+    pub fn code_editor() -> Keymap {
+        let mut keymap = Keymap::new();
+        let named = [
+            (NamedKey::Enter, EditorCommand::InsertNewLine),
+            (NamedKey::Tab, EditorCommand::InsertTab),
+            (NamedKey::Space, EditorCommand::InsertText(" ".to_string())),
+            (NamedKey::ArrowUp, EditorCommand::MoveBackwardLine),
+            (NamedKey::ArrowDown, EditorCommand::MoveForwardLine),
+            (NamedKey::ArrowLeft, EditorCommand::MoveBackwardChar),
+            (NamedKey::ArrowRight, EditorCommand::MoveForwardChar),
+            (NamedKey::Delete, EditorCommand::DeleteAtPoint),
+            (NamedKey::Backspace, EditorCommand::DeleteBackward),
+        ];
+        for (key, command) in named {
+            keymap.bind(&[KeyBinding::named(key)], command);
+        }
+        let control = [
+            ("+", EditorCommand::ZoomIn),
+            ("=", EditorCommand::ZoomIn),
+            ("-", EditorCommand::ZoomOut),
+            ("0", EditorCommand::ResetZoom),
+            ("s", EditorCommand::Save),
+        ];
+        for (character, command) in control {
+            keymap.bind(&[KeyBinding::control(character)], command);
+        }
+        keymap.bind(
+            &[KeyBinding::control("x"), KeyBinding::control("s")],
+            EditorCommand::Save,
+        );
+        // Ctrl+Shift+L, the shift is reflected by the character.
+        keymap.bind(&[KeyBinding::control("L")], EditorCommand::ToggleDarkLight);
+        keymap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::keyboard::{Key, ModifiersState, NamedKey};
+
+    use super::{KeyBinding, KeyPress, Keymap, KeymapEntry};
+    use crate::command::EditorCommand;
+
+    fn command(keymap: &Keymap, keys: &[KeyBinding]) -> Option<EditorCommand> {
+        match keymap.lookup(keys)? {
+            KeymapEntry::Command(command) => Some(command.clone()),
+            KeymapEntry::Keymap(_) => None,
+        }
+    }
+
+    #[test]
+    fn lookup_key_sequences() {
+        let keymap = Keymap::code_editor();
+        assert_eq!(
+            command(&keymap, &[KeyBinding::named(NamedKey::Backspace)]),
+            Some(EditorCommand::DeleteBackward)
+        );
+        let prefix = [KeyBinding::control("x")];
+        assert!(matches!(
+            keymap.lookup(&prefix),
+            Some(KeymapEntry::Keymap(_))
+        ));
+        assert_eq!(
+            command(&keymap, &[prefix[0].clone(), KeyBinding::control("s")]),
+            Some(EditorCommand::Save)
+        );
+        assert!(keymap
+            .lookup(&[prefix[0].clone(), KeyBinding::control("q")])
+            .is_none());
+    }
+
+    #[test]
+    fn shift_is_ignored_for_characters() {
+        let keymap = Keymap::code_editor();
+        let plus = KeyBinding::new(
+            Key::Character("+".into()),
+            ModifiersState::CONTROL | ModifiersState::SHIFT,
+        );
+        assert_eq!(command(&keymap, &[plus]), Some(EditorCommand::ZoomIn));
+    }
+
+    #[test]
+    fn toggle_dark_light_binding() {
+        let keymap = Keymap::code_editor();
+        let control_shift_l = KeyBinding::new(
+            Key::Character("L".into()),
+            ModifiersState::CONTROL | ModifiersState::SHIFT,
+        );
+        assert_eq!(
+            command(&keymap, &[control_shift_l]),
+            Some(EditorCommand::ToggleDarkLight)
+        );
+        assert!(command(&keymap, &[KeyBinding::control("l")]).is_none());
+    }
+
+    #[test]
+    fn modifiers_fall_back_for_named_keys() {
+        let keymap = Keymap::code_editor();
+        let shift = |key| KeyBinding::new(Key::Named(key), ModifiersState::SHIFT);
+        assert!(keymap.lookup(&[shift(NamedKey::Space)]).is_none());
+        let lookup = |keys: &[KeyBinding]| match keymap.lookup_or_unmodified(keys)? {
+            KeymapEntry::Command(command) => Some(command.clone()),
+            KeymapEntry::Keymap(_) => None,
+        };
+        assert_eq!(
+            lookup(&[shift(NamedKey::Space)]),
+            Some(EditorCommand::InsertText(" ".to_string()))
+        );
+        assert_eq!(
+            lookup(&[shift(NamedKey::Enter)]),
+            Some(EditorCommand::InsertNewLine)
+        );
+        // The characters don't fall back.
+        assert!(lookup(&[KeyBinding::control("q")]).is_none());
+    }
+
+    #[test]
+    fn modifier_keys_keep_sequence() {
+        let keymap = Keymap::code_editor();
+        let control_key =
+            KeyBinding::new(Key::Named(NamedKey::Control), ModifiersState::CONTROL);
+        let mut pending_keys = Vec::new();
+        assert_eq!(
+            keymap.press(&mut pending_keys, KeyBinding::control("x")),
+            KeyPress::Prefix
+        );
+        assert_eq!(
+            keymap.press(&mut pending_keys, control_key),
+            KeyPress::Modifier
+        );
+        assert_eq!(
+            keymap.press(&mut pending_keys, KeyBinding::control("s")),
+            KeyPress::Command(EditorCommand::Save)
+        );
+        assert!(pending_keys.is_empty());
+
+        keymap.press(&mut pending_keys, KeyBinding::control("x"));
+        assert_eq!(
+            keymap.press(&mut pending_keys, KeyBinding::control("q")),
+            KeyPress::Unbound(vec![
+                KeyBinding::control("x"),
+                KeyBinding::control("q")
+            ])
+        );
+        assert!(pending_keys.is_empty());
+    }
+
+    #[test]
+    fn rebinding_prefix() {
+        let mut keymap = Keymap::new();
+        let [a, b] = [KeyBinding::control("a"), KeyBinding::control("b")];
+        keymap.bind(std::slice::from_ref(&a), EditorCommand::Save);
+        keymap.bind(&[a.clone(), b.clone()], EditorCommand::ZoomIn);
+        assert_eq!(command(&keymap, &[a, b]), Some(EditorCommand::ZoomIn));
+    }
+}
@@ -2,12 +2,17 @@ use std::{
     cmp::Ordering,
     ops::{Deref, DerefMut},
     slice::Iter,
+    vec::IntoIter,
 };
 
 use kurbo::Point;
 
 use crate::mouse_event::Click;
 
+// Tolerance of the offset comparisons. The offsets are sums of many heights so
+// they accumulate rounding errors.
+const OFFSET_EPSILON: f64 = 1e-6;
+
 #[derive(Clone, Debug)]
 pub struct LayoutElement<Data> {
     // TODO: Change to Rect which has all offset(x,y), height, and width.
@@ -97,7 +102,8 @@ impl<Data: LayoutData> LayoutFlow<Data> {
         }
     }
 
-    // TODO: Think about making it a `Result`
+    /// Returns the elements which are at least partially visible in the
+    /// window starting at `offset` with the given `height`.
     pub fn get_visible_parts(
         &self,
         // TODO: Change it to Rect
@@ -105,25 +111,44 @@ impl<Data: LayoutData> LayoutFlow<Data> {
         height: f64,
     ) -> &[LayoutElement<Data>] {
         let bottom = offset + height;
-        if let Ok(index) = self.flow.binary_search_by(|v| {
-            // TODO: This comparison should probably use epsilon
-            if v.offset <= offset && v.offset + v.height >= offset {
-                Ordering::Equal
-            } else if v.offset < offset {
+        let first = if offset <= 0.0 {
+            0
+        } else {
+            match self.find_index(offset) {
+                Some(index) => index,
+                None => return &[],
+            }
+        };
+        // The elements starting above the bottom are visible.
+        let last = first
+            + self.flow[first..]
+                .partition_point(|v| v.offset < bottom - OFFSET_EPSILON);
+        &self.flow[first..last]
+    }
+
+    /// Returns the index of the element containing the `offset`. An offset
+    /// on the boundary of two elements (within `OFFSET_EPSILON`) belongs to
+    /// the later one, the bottom of the last element belongs to it.
+    fn find_index(&self, offset: f64) -> Option<usize> {
+        let res = self.flow.binary_search_by(|v| {
+            if offset < v.offset - OFFSET_EPSILON {
+                Ordering::Greater
+            } else if offset >= v.offset + v.height - OFFSET_EPSILON {
                 Ordering::Less
             } else {
-                Ordering::Greater
+                Ordering::Equal
             }
-        }) {
-            let last_index = self.flow[index..]
-                .iter()
-                .position(|v| v.offset <= bottom && v.offset + v.height >= bottom)
-                .map(|index| index + self.flow[index..].len())
-                // TODO: Maybe this should return an error???
-                .unwrap_or(self.flow.len());
-            &self.flow[index..last_index]
-        } else {
-            &[]
+        });
+        match res {
+            Ok(index) => Some(index),
+            Err(index)
+                if index == self.flow.len()
+                    && index > 0
+                    && offset <= self.height + OFFSET_EPSILON =>
+            {
+                Some(index - 1)
+            }
+            Err(_) => None,
         }
     }
 
@@ -136,6 +161,7 @@ impl<Data: LayoutData> LayoutFlow<Data> {
         };
         self.height += elem.height;
         self.flow.push(elem);
+        self.debug_check_heights();
     }
 
     pub fn insert(&mut self, index: usize, element: Data) {
@@ -152,6 +178,58 @@ impl<Data: LayoutData> LayoutFlow<Data> {
             e.offset = offset;
             offset += e.height;
         }
+        self.debug_check_heights();
+    }
+
+    /// Removes the element at `index` and shifts all elements after it up.
+    pub fn remove(&mut self, index: usize) -> Data {
+        let removed = self.flow.remove(index);
+        if index < self.flow.len() {
+            self.flow[index].offset = removed.offset;
+            self.recompute_from_index(index);
+        } else {
+            self.height = removed.offset;
+        }
+        self.debug_check_heights();
+        removed.data
+    }
+
+    /// Exchanges the data of the elements at `a` and `b`.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        if a == b {
+            return;
+        }
+        let (low, high) = (a.min(b), a.max(b));
+        let (head, tail) = self.flow.split_at_mut(high);
+        std::mem::swap(&mut head[low].data, &mut tail[0].data);
+        self.recompute_from_index(low);
+    }
+
+    /// Moves the element at `from` in front of the element which was at `to`
+    /// before the move. `to` equal to `len()` moves the element to the end.
+    pub fn move_element(&mut self, from: usize, to: usize) {
+        // The removal shifts the elements after `from` up by one.
+        let to = if to > from { to - 1 } else { to };
+        if from == to {
+            return;
+        }
+        let index = from.min(to);
+        let offset = self.flow[index].offset;
+        let element = self.flow.remove(from);
+        self.flow.insert(to, element);
+        self.flow[index].offset = offset;
+        self.recompute_from_index(index);
+    }
+
+    pub fn pop(&mut self) -> Option<Data> {
+        let removed = self.flow.pop()?;
+        self.height = removed.offset;
+        Some(removed.data)
+    }
+
+    pub fn clear(&mut self) {
+        self.flow.clear();
+        self.height = 0.0;
     }
 
     pub fn get_mutable(&mut self, index: usize) -> MutableData<'_, Data> {
@@ -161,26 +239,19 @@ impl<Data: LayoutData> LayoutFlow<Data> {
         }
     }
 
+    /// This return an index of the element with correlated coordinates within
+    /// the element
+    pub fn get_index_at_offset(&self, offset: f64) -> Option<(usize, f64)> {
+        self.find_index(offset)
+            .map(|index| (index, offset - self.flow[index].offset))
+    }
+
     /// This return an element with correlated coordinates within the element
     pub fn get_element_at_offset(&self, offset: f64) -> Option<(&Data, f64)> {
-        let res = self
-            .flow
-            .binary_search_by(|v| {
-                // TODO: This comparison should probably use epsilon
-                if v.offset <= offset && v.offset + v.height >= offset {
-                    Ordering::Equal
-                } else if v.offset < offset {
-                    Ordering::Less
-                } else {
-                    Ordering::Greater
-                }
+        self.get_index_at_offset(offset)
+            .map(|(index, corelated_offset)| {
+                (&self.flow[index].data, corelated_offset)
             })
-            .ok();
-        res.map(|index| {
-            let element = &self.flow[index];
-            let corelated_offset = offset - element.offset;
-            (&element.data, corelated_offset)
-        })
     }
 
     /// This return an element with correlated coordinates within the element
@@ -188,30 +259,16 @@ impl<Data: LayoutData> LayoutFlow<Data> {
         &mut self,
         offset: f64,
     ) -> Option<(MutableData<'_, Data>, f64)> {
-        let res = self
-            .flow
-            .binary_search_by(|v| {
-                // TODO: This comparison should probably use epsilon
-                if v.offset <= offset && v.offset + v.height >= offset {
-                    Ordering::Equal
-                } else if v.offset < offset {
-                    Ordering::Less
-                } else {
-                    Ordering::Greater
-                }
+        self.get_index_at_offset(offset)
+            .map(|(index, corelated_offset)| {
+                (
+                    MutableData {
+                        index,
+                        layout_flow: self,
+                    },
+                    corelated_offset,
+                )
             })
-            .ok();
-        res.map(|index| {
-            let element = &self.flow[index];
-            let corelated_offset = offset - element.offset;
-            (
-                MutableData {
-                    index,
-                    layout_flow: self,
-                },
-                corelated_offset,
-            )
-        })
     }
 
     pub fn recopute_all(&mut self) {
@@ -229,6 +286,22 @@ impl<Data: LayoutData> LayoutFlow<Data> {
             offset += element.height;
         }
         self.height = offset;
+        self.debug_check_heights();
+    }
+
+    /// Runs `f` on the data of the element at `index`. Unlike `apply_to_all`
+    /// only the offsets of the elements from the `index` down are recomputed
+    /// and only when the height of the element changed.
+    pub fn relayout_one<F>(&mut self, index: usize, f: F)
+    where
+        F: FnOnce(&mut Data),
+    {
+        let element = &mut self.flow[index];
+        f(&mut element.data);
+        let height_diff = element.data.height() - element.height;
+        if height_diff.abs() > f64::EPSILON {
+            self.recompute_from_index(index);
+        }
     }
 
     pub fn apply_to_all<F>(&mut self, mut f: F)
@@ -239,12 +312,60 @@ impl<Data: LayoutData> LayoutFlow<Data> {
             f((i, &mut e.data))
         }
         self.recopute_all();
+        self.debug_check_heights();
+    }
+
+    /// Checks that every offset is the sum of the heights above it and the
+    /// height of the flow is the sum of all heights. Compiled out in release
+    /// builds.
+    fn debug_check_heights(&self) {
+        #[cfg(debug_assertions)]
+        {
+            let mut offset = 0.0;
+            for (index, e) in self.flow.iter().enumerate() {
+                debug_assert!(
+                    f64::abs(e.offset - offset) < OFFSET_EPSILON,
+                    "Element {index} has offset {} but it should be {offset}",
+                    e.offset
+                );
+                offset += e.height;
+            }
+            let heights = self.flow.iter().map(|e| e.height).sum::<f64>();
+            debug_assert!(
+                f64::abs(self.height - heights) < OFFSET_EPSILON,
+                "The flow has height {} but the sum of heights is {heights}",
+                self.height
+            );
+        }
     }
 
     pub fn iter(&self) -> Iter<'_, LayoutElement<Data>> {
         self.flow.iter()
     }
 
+    /// Iterates over the elements as `(offset, height, data)` triples. The
+    /// offsets are absolute from the top of the flow.
+    pub fn iter_positioned(&self) -> impl Iterator<Item = (f64, f64, &Data)> {
+        self.flow
+            .iter()
+            .map(|element| (element.offset, element.height, &element.data))
+    }
+
+    /// The same as `iter_positioned` but with mutable data. The offsets and
+    /// heights aren't updated when the data changes their height, call
+    /// `recopute_all` afterwards.
+    pub fn iter_positioned_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (f64, f64, &mut Data)> {
+        self.flow
+            .iter_mut()
+            .map(|element| (element.offset, element.height, &mut element.data))
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Data> {
+        self.flow.get(index).map(|element| &element.data)
+    }
+
     pub fn len(&self) -> usize {
         self.flow.len()
     }
@@ -256,4 +377,260 @@ impl<Data: LayoutData> LayoutFlow<Data> {
     pub fn height(&self) -> f64 {
         self.height
     }
+
+    /// Returns the offset of the top of the element at `index`.
+    pub fn offset_of(&self, index: usize) -> f64 {
+        self.flow[index].offset
+    }
+
+    /// Returns the height of the elements in the range `[start, end)`.
+    pub fn height_between(&self, start: usize, end: usize) -> f64 {
+        self.flow.get(end).map_or(self.height, |e| e.offset)
+            - self.flow.get(start).map_or(self.height, |e| e.offset)
+    }
+}
+
+impl<Data: LayoutData> IntoIterator for LayoutFlow<Data> {
+    type Item = LayoutElement<Data>;
+    type IntoIter = IntoIter<LayoutElement<Data>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.flow.into_iter()
+    }
+}
+
+impl<'a, Data: LayoutData> IntoIterator for &'a LayoutFlow<Data> {
+    type Item = &'a LayoutElement<Data>;
+    type IntoIter = Iter<'a, LayoutElement<Data>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<Data: LayoutData> FromIterator<Data> for LayoutFlow<Data> {
+    fn from_iter<I: IntoIterator<Item = Data>>(iter: I) -> Self {
+        let mut flow = LayoutFlow::new();
+        flow.extend(iter);
+        flow
+    }
+}
+
+impl<Data: LayoutData> Extend<Data> for LayoutFlow<Data> {
+    fn extend<I: IntoIterator<Item = Data>>(&mut self, iter: I) {
+        for element in iter {
+            self.push(element);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LayoutData, LayoutFlow};
+
+    #[derive(Debug, PartialEq)]
+    struct Block(f64);
+
+    impl LayoutData for Block {
+        fn height(&self) -> f64 {
+            self.0
+        }
+    }
+
+    fn flow_with_heights(heights: &[f64]) -> LayoutFlow<Block> {
+        heights.iter().map(|height| Block(*height)).collect()
+    }
+
+    fn offsets(flow: &LayoutFlow<Block>) -> Vec<f64> {
+        flow.iter().map(|element| element.offset).collect()
+    }
+
+    #[test]
+    fn remove_middle_element() {
+        let mut flow = flow_with_heights(&[10.0, 20.0, 30.0, 40.0]);
+        assert_eq!(flow.remove(1), Block(20.0));
+        assert_eq!(flow.len(), 3);
+        assert_eq!(offsets(&flow), vec![0.0, 10.0, 40.0]);
+        assert_eq!(flow.height(), 80.0);
+    }
+
+    #[test]
+    fn remove_first_and_last_element() {
+        let mut flow = flow_with_heights(&[10.0, 20.0, 30.0]);
+        assert_eq!(flow.remove(0), Block(10.0));
+        assert_eq!(offsets(&flow), vec![0.0, 20.0]);
+        assert_eq!(flow.height(), 50.0);
+        assert_eq!(flow.remove(1), Block(30.0));
+        assert_eq!(offsets(&flow), vec![0.0]);
+        assert_eq!(flow.height(), 20.0);
+    }
+
+    #[test]
+    fn offset_on_boundary_after_many_elements() {
+        let flow = flow_with_heights(&[0.1; 1000]);
+        for i in 1..1000 {
+            // The accumulated offset of the element differs from `i * 0.1`.
+            let (index, _) = flow.get_index_at_offset(i as f64 * 0.1).unwrap();
+            assert_eq!(index, i);
+            let offset = flow.iter().nth(i).unwrap().offset;
+            let (index, _) = flow.get_index_at_offset(offset).unwrap();
+            assert_eq!(index, i);
+        }
+        let (index, _) = flow.get_index_at_offset(flow.height()).unwrap();
+        assert_eq!(index, 999);
+        assert!(flow.get_index_at_offset(flow.height() + 1.0).is_none());
+    }
+
+    fn data_heights(flow: &LayoutFlow<Block>) -> Vec<f64> {
+        flow.iter().map(|element| element.data.0).collect()
+    }
+
+    fn assert_layout_invariant(flow: &LayoutFlow<Block>) {
+        let mut offset = 0.0;
+        for element in flow {
+            assert_eq!(element.offset, offset);
+            assert_eq!(element.height, element.data.height());
+            offset += element.height;
+        }
+        assert_eq!(flow.height(), offset);
+    }
+
+    #[test]
+    fn swap_elements() {
+        let mut flow = flow_with_heights(&[10.0, 20.0, 30.0, 40.0]);
+        flow.swap(0, 2);
+        assert_eq!(data_heights(&flow), vec![30.0, 20.0, 10.0, 40.0]);
+        assert_eq!(offsets(&flow), vec![0.0, 30.0, 50.0, 60.0]);
+        assert_layout_invariant(&flow);
+        flow.swap(3, 1);
+        assert_eq!(data_heights(&flow), vec![30.0, 40.0, 10.0, 20.0]);
+        assert_layout_invariant(&flow);
+        flow.swap(2, 2);
+        assert_eq!(data_heights(&flow), vec![30.0, 40.0, 10.0, 20.0]);
+        assert_layout_invariant(&flow);
+        assert_eq!(flow.height(), 100.0);
+    }
+
+    #[test]
+    fn move_element_down_and_up() {
+        let mut flow = flow_with_heights(&[10.0, 20.0, 30.0, 40.0]);
+        flow.move_element(0, 2);
+        assert_eq!(data_heights(&flow), vec![20.0, 10.0, 30.0, 40.0]);
+        assert_eq!(offsets(&flow), vec![0.0, 20.0, 30.0, 60.0]);
+        assert_layout_invariant(&flow);
+        flow.move_element(1, 4);
+        assert_eq!(data_heights(&flow), vec![20.0, 30.0, 40.0, 10.0]);
+        assert_layout_invariant(&flow);
+        flow.move_element(3, 0);
+        assert_eq!(data_heights(&flow), vec![10.0, 20.0, 30.0, 40.0]);
+        assert_layout_invariant(&flow);
+        flow.move_element(2, 1);
+        assert_eq!(data_heights(&flow), vec![10.0, 30.0, 20.0, 40.0]);
+        assert_layout_invariant(&flow);
+        // Moving in front of itself or the next element doesn't change anything.
+        flow.move_element(1, 1);
+        flow.move_element(1, 2);
+        assert_eq!(data_heights(&flow), vec![10.0, 30.0, 20.0, 40.0]);
+        assert_layout_invariant(&flow);
+        assert_eq!(flow.height(), 100.0);
+    }
+
+    #[test]
+    fn height_between_elements() {
+        let flow = flow_with_heights(&[10.0, 20.0, 30.0, 40.0]);
+        assert_eq!(flow.offset_of(2), 30.0);
+        assert_eq!(flow.height_between(0, 4), 100.0);
+        assert_eq!(flow.height_between(1, 3), 50.0);
+        assert_eq!(flow.height_between(3, 4), 40.0);
+        assert_eq!(flow.height_between(2, 2), 0.0);
+        assert_eq!(flow.height_between(4, 4), 0.0);
+    }
+
+    #[test]
+    fn relayout_one_element() {
+        let mut flow = flow_with_heights(&[10.0, 20.0, 30.0]);
+        flow.relayout_one(1, |block| block.0 = 5.0);
+        assert_eq!(offsets(&flow), vec![0.0, 10.0, 15.0]);
+        assert_eq!(flow.height(), 45.0);
+    }
+
+    #[test]
+    fn pop_and_clear() {
+        let mut flow = flow_with_heights(&[10.0, 20.0]);
+        assert_eq!(flow.pop(), Some(Block(20.0)));
+        assert_eq!(flow.height(), 10.0);
+        assert_eq!(flow.pop(), Some(Block(10.0)));
+        assert_eq!(flow.height(), 0.0);
+        assert_eq!(flow.pop(), None);
+
+        let mut flow = flow_with_heights(&[10.0, 20.0]);
+        flow.clear();
+        assert!(flow.is_empty());
+        assert_eq!(flow.height(), 0.0);
+        flow.push(Block(5.0));
+        assert_eq!(offsets(&flow), vec![0.0]);
+        assert_eq!(flow.height(), 5.0);
+    }
+
+    #[test]
+    fn extend_and_into_iter() {
+        let mut flow = flow_with_heights(&[10.0]);
+        flow.extend([Block(20.0), Block(30.0)]);
+        assert_eq!(offsets(&flow), vec![0.0, 10.0, 30.0]);
+        assert_eq!(flow.height(), 60.0);
+
+        let mut heights = Vec::new();
+        for element in &flow {
+            heights.push(element.height);
+        }
+        assert_eq!(heights, vec![10.0, 20.0, 30.0]);
+
+        let data: Vec<Block> =
+            flow.into_iter().map(|element| element.data).collect();
+        assert_eq!(data, vec![Block(10.0), Block(20.0), Block(30.0)]);
+    }
+
+    fn visible_heights(
+        flow: &LayoutFlow<Block>,
+        offset: f64,
+        height: f64,
+    ) -> Vec<f64> {
+        flow.get_visible_parts(offset, height)
+            .iter()
+            .map(|element| element.height)
+            .collect()
+    }
+
+    #[test]
+    fn visible_parts_on_boundary() {
+        let flow = flow_with_heights(&[10.0, 20.0, 30.0]);
+        assert_eq!(visible_heights(&flow, 10.0, 20.0), vec![20.0]);
+        assert_eq!(visible_heights(&flow, 0.0, 10.0), vec![10.0]);
+        assert_eq!(visible_heights(&flow, 5.0, 10.0), vec![10.0, 20.0]);
+        assert_eq!(visible_heights(&flow, 0.0, 100.0), vec![10.0, 20.0, 30.0]);
+    }
+
+    #[test]
+    fn visible_parts_inside_one_element() {
+        let flow = flow_with_heights(&[10.0, 100.0, 10.0]);
+        assert_eq!(visible_heights(&flow, 20.0, 30.0), vec![100.0]);
+    }
+
+    #[test]
+    fn visible_parts_with_zero_height_elements() {
+        let flow = flow_with_heights(&[10.0, 0.0, 0.0, 10.0]);
+        assert_eq!(visible_heights(&flow, 0.0, 10.0), vec![10.0]);
+        assert_eq!(
+            visible_heights(&flow, 0.0, 15.0),
+            vec![10.0, 0.0, 0.0, 10.0]
+        );
+        assert_eq!(visible_heights(&flow, 10.0, 5.0), vec![10.0]);
+    }
+
+    #[test]
+    fn visible_parts_of_empty_flow() {
+        let flow = flow_with_heights(&[]);
+        assert!(flow.get_visible_parts(0.0, 100.0).is_empty());
+        assert!(flow.get_visible_parts(10.0, 100.0).is_empty());
+    }
 }
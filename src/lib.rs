@@ -3,7 +3,10 @@ pub mod buffer;
 pub mod code_text_layout;
 pub mod code_widget;
 pub mod command;
+pub mod config;
+pub mod editor;
 pub mod generation;
+pub mod keymap;
 pub mod layout_flow;
 pub mod markdown;
 pub mod mouse_event;
@@ -1,13 +1,19 @@
 use std::sync::Arc;
 
+use eyre::{eyre, Result};
 use masonry::core::BrushIndex;
 use parley::FontContext;
 use usvg::fontdb;
 
+use super::image_cache::ImageCache;
 use crate::theme::Theme;
 
+#[derive(Clone)]
 pub struct SvgContext {
     pub fontdb: Arc<fontdb::Database>,
+    // Used to limit the size of the loaded images.
+    pub viewport_height: f64,
+    pub image_cache: ImageCache,
 }
 
 pub struct MarkdownContext<'a, 'b> {
@@ -40,8 +46,37 @@ pub struct TextContext<'a, 'b> {
 }
 
 impl SvgContext {
-    pub fn new(fontdb: Arc<fontdb::Database>) -> SvgContext {
-        SvgContext { fontdb }
+    pub fn new(
+        fontdb: Arc<fontdb::Database>,
+        viewport_height: f64,
+        image_cache: ImageCache,
+    ) -> SvgContext {
+        SvgContext {
+            fontdb,
+            viewport_height,
+            image_cache,
+        }
+    }
+
+    /// Renders the SVG document into a bitmap of the SVG's size.
+    pub fn render_svg_str(&self, svg_str: &str) -> Result<image::RgbaImage> {
+        let options = usvg::Options {
+            fontdb: self.fontdb.clone(),
+            ..usvg::Options::default()
+        };
+
+        let svg_tree = usvg::Tree::from_str(svg_str, &options)?;
+        let width = svg_tree.size().width().ceil() as u32;
+        let height = svg_tree.size().height().ceil() as u32;
+        let mut pixmap = tiny_skia::Pixmap::new(width, height)
+            .ok_or_else(|| eyre!("Invalid SVG size {width}x{height}"))?;
+        resvg::render(
+            &svg_tree,
+            tiny_skia::Transform::identity(),
+            &mut pixmap.as_mut(),
+        );
+        image::ImageBuffer::from_raw(width, height, pixmap.take())
+            .ok_or_else(|| eyre!("Rendered SVG has unexpected size"))
     }
 }
 
@@ -1,9 +1,10 @@
 use core::fmt;
-use std::ops::DerefMut;
+use std::ops::{DerefMut, Range};
 
+use accesskit::{Node, NodeId, Role, Toggled, TreeUpdate};
 use kurbo::{Affine, Cap, Insets, Join, Line, Point, Rect, Size, Stroke, Vec2};
 use masonry::core::BrushIndex;
-use parley::{Alignment, FontFamily, FontStack, StyleProperty};
+use parley::{Alignment, FontFamily, FontStack, FontStyle, StyleProperty};
 use peniko::Color;
 use pulldown_cmark::HeadingLevel;
 use vello::Scene;
@@ -13,13 +14,14 @@ use super::{
     context::{MarkdownContext, TextContext},
     text::{
         layouted_text::LayoutedText, simple::SimpleText, styles::BrushPalete,
-        MarkdownText,
+        InlinedImage, Link, MarkdownText,
     },
 };
 use crate::{
     basic_types::{Height, Width},
     layout_flow::{LayoutData, LayoutFlow},
     mouse_event::Click,
+    scene_utils::SizedScene,
     theme::{self, MarkdowTheme},
 };
 
@@ -68,12 +70,39 @@ impl Margin {
         }
     }
 
-    pub const ZERO: Margin = Margin {
-        top: 0.0,
-        right: 0.0,
-        bottom: 0.0,
-        left: 0.0,
-    };
+    pub const ZERO: Margin = Margin::uniform(0.0);
+
+    pub const fn uniform(value: f64) -> Margin {
+        Margin {
+            top: value,
+            right: value,
+            bottom: value,
+            left: value,
+        }
+    }
+
+    pub const fn symmetric(vertical: f64, horizontal: f64) -> Margin {
+        Margin {
+            top: vertical,
+            right: horizontal,
+            bottom: vertical,
+            left: horizontal,
+        }
+    }
+
+    /// Returns the margin multiplied by the `factor` (e.g. the zoom level).
+    pub fn scale(&self, factor: f64) -> Margin {
+        Margin {
+            top: self.top * factor,
+            right: self.right * factor,
+            bottom: self.bottom * factor,
+            left: self.left * factor,
+        }
+    }
+
+    pub fn insets(&self) -> Insets {
+        Insets::new(self.left, self.top, self.right, self.bottom)
+    }
 
     /// This function takes width and `f` function that layouts the inside of
     /// the `Margin` based on inner width. The `f` function will get the width
@@ -95,13 +124,7 @@ impl Margin {
     where
         F: FnOnce(&Rect),
     {
-        let element_box = Rect::new(
-            element_box.x0 + self.left,
-            element_box.y0 + self.top,
-            element_box.x1 - self.right,
-            element_box.y1 - self.bottom,
-        );
-        f(&element_box);
+        f(&(*element_box - self.insets()));
     }
 
     fn local_position<F, R>(&self, mut position: Point, f: F) -> R
@@ -117,6 +140,10 @@ impl Margin {
         self.top + self.bottom
     }
 
+    fn offset(&self) -> Vec2 {
+        Vec2::new(self.left, self.top)
+    }
+
     fn width(&self) -> Width {
         self.left + self.right
     }
@@ -148,23 +175,89 @@ impl From<theme::Padding> for Margin {
 pub struct MarkdownList {
     margin: Margin,
     list: Vec<LayoutFlow<MarkdownContent>>,
+    // Checked state of the task list items; `None` for the ordinary items.
+    tasks: Vec<Option<bool>>,
     marker: ListMarker,
+    // The checkboxes are drawn instead of the marker for the task list items.
+    unchecked_box: LayoutedText,
+    checked_box: LayoutedText,
+    // Number of the lists this list is nested in.
+    nesting_level: usize,
+    // Extra indentation of a nested list, the markers are drawn after it. The
+    // nested list already starts at the text column of its parent item, so
+    // the indentation is the same for all levels.
+    level_indentation: f64,
+    // Full indentation of the item content.
     indentation: f64,
-    height: f64,
+    // Height computed by the last layout, see `computed_height`.
+    cached_height: Height,
 }
 
 impl MarkdownList {
     pub fn new(
         list: Vec<LayoutFlow<MarkdownContent>>,
+        tasks: Vec<Option<bool>>,
         marker: ListMarker,
     ) -> MarkdownList {
         Self {
             margin: Margin::ZERO,
             list,
+            tasks,
             marker,
+            unchecked_box: LayoutedText::empty(),
+            checked_box: LayoutedText::empty(),
+            nesting_level: 0,
+            level_indentation: 0.0,
             indentation: 0.0,
-            height: 0.0,
+            cached_height: 0.0,
+        }
+    }
+
+    fn is_task_list(&self) -> bool {
+        self.tasks.iter().any(Option::is_some)
+    }
+
+    /// Returns the items with the offsets of their tops from the top of the
+    /// list.
+    fn nested_flows(&self) -> Vec<(f64, &LayoutFlow<MarkdownContent>)> {
+        let mut offset = self.margin.top;
+        let mut flows = Vec::with_capacity(self.list.len());
+        for flow in self.list.iter() {
+            flows.push((offset, flow));
+            offset += flow.height();
+        }
+        flows
+    }
+
+    /// Toggles the checkbox of the task list item under the `point`. Returns
+    /// `true` when some checkbox was toggled. The `point` is in the element
+    /// coordinates.
+    pub fn toggle_task_at_point(&mut self, point: Point) -> bool {
+        let mut y = point.y - self.margin.top;
+        for (index, flow) in self.list.iter_mut().enumerate() {
+            if y < 0.0 {
+                return false;
+            }
+            if y < flow.height() {
+                if point.x < self.indentation {
+                    return match &mut self.tasks[index] {
+                        Some(checked) => {
+                            *checked = !*checked;
+                            true
+                        }
+                        None => false,
+                    };
+                }
+                return match flow.get_mut_element_at_offset(y) {
+                    Some((mut element, y)) => element.toggle_task_at_point(
+                        Point::new(point.x - self.indentation, y),
+                    ),
+                    None => false,
+                };
+            }
+            y -= flow.height();
         }
+        false
     }
 
     pub fn on_mouse_move(
@@ -184,6 +277,11 @@ impl MarkdownList {
     ) -> Height {
         let mut text_ctx: TextContext =
             TextContext::new(ctx.svg_ctx, ctx.layout_ctx, ctx.theme);
+        self.level_indentation = if self.nesting_level > 0 {
+            ctx.theme.markdown.list_nesting_indentation
+        } else {
+            0.0
+        };
         self.indentation = match &mut self.marker {
             ListMarker::Symbol { symbol } => {
                 symbol.build_layout(&mut text_ctx, None);
@@ -201,7 +299,8 @@ impl MarkdownList {
                     // Not ideal way to layout the numbered list, but works for now.
                     let mut str = (k as u32 + *start_number).to_string();
                     str.push('.');
-                    let mut symbol: SimpleText = str.into();
+                    let mut symbol = SimpleText::new(str)
+                        .with_brush(BrushPalete::INDENTATION_BRUSH);
                     symbol.align(None, Alignment::End, false);
                     symbol.build_layout(&mut text_ctx, None);
                     let marker_width = symbol.full_width()
@@ -216,15 +315,33 @@ impl MarkdownList {
             }
         };
 
+        if self.is_task_list() {
+            let theme = ctx.theme;
+            self.unchecked_box =
+                layout_symbol(ctx, &theme.markdown.task_unchecked_sign);
+            self.checked_box = layout_symbol(ctx, &theme.markdown.task_checked_sign);
+            let checkbox_width = self
+                .unchecked_box
+                .full_width()
+                .max(self.checked_box.full_width())
+                + theme.markdown.bullet_list_indentation
+                + theme.markdown.list_after_indentation;
+            self.indentation = self.indentation.max(checkbox_width);
+        }
+        self.indentation += self.level_indentation;
+
         self.margin.top = ctx.theme.markdown.list_top_margin;
         if reduce_top_margin {
             self.margin.top = 0.0;
         }
 
-        self.height = self.margin.layout_by_width(width, |width| {
+        self.margin.layout_by_width(width, |width| {
             let mut height = 0.0;
             for element in self.list.iter_mut() {
                 element.apply_to_all(|(i, data)| {
+                    if let MarkdownContent::List(list) = data {
+                        list.nesting_level = self.nesting_level + 1;
+                    }
                     data.layout(
                         ctx,
                         width - self.indentation,
@@ -235,11 +352,18 @@ impl MarkdownList {
             }
             height
         });
-        self.height
+        self.cached_height = self.computed_height();
+        self.cached_height
+    }
+
+    fn height(&self) -> Height {
+        debug_assert_eq!(self.cached_height, self.computed_height());
+        self.cached_height
     }
 
-    fn height(&self) -> f64 {
-        self.height
+    fn computed_height(&self) -> Height {
+        self.margin.height()
+            + self.list.iter().map(|flow| flow.height()).sum::<f64>()
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -252,18 +376,87 @@ impl MarkdownList {
         index: usize,
         brush_palete: &BrushPalete,
         flow: &LayoutFlow<MarkdownContent>,
+    ) {
+        // The marker is aligned with the first line of the item text.
+        let item_baseline = match flow.iter().next().map(|element| &element.data) {
+            Some(MarkdownContent::Paragraph(paragraph)) => {
+                Some(paragraph.baseline_offset())
+            }
+            _ => None,
+        };
+        let marker_offset = |marker_baseline: f64| {
+            Vec2::new(
+                0.0,
+                item_baseline.map_or(0.0, |baseline| baseline - marker_baseline),
+            )
+        };
+        if let Some(checked) = self.tasks.get(index).copied().flatten() {
+            let checkbox = if checked {
+                &self.checked_box
+            } else {
+                &self.unchecked_box
+            };
+            let marker_position = element_box.origin().to_vec2()
+                + Vec2::new(
+                    self.level_indentation
+                        + ctx.theme.markdown.bullet_list_indentation,
+                    0.0,
+                )
+                + marker_offset(checkbox.baseline_offset());
+            checkbox.draw_text(
+                scene,
+                scene_size,
+                &marker_position,
+                |_| None,
+                &brush_palete.palete,
+            );
+        } else {
+            self.paint_marker(
+                scene,
+                scene_size,
+                ctx,
+                element_box,
+                index,
+                brush_palete,
+                marker_offset,
+            );
+        }
+        // The item content starts at the text column of the list.
+        let element_box =
+            *element_box - Insets::new(self.indentation, 0.0, 0.0, 0.0);
+        draw_flow(scene, scene_size, ctx, &element_box, brush_palete, flow);
+    }
+
+    /// The `marker_offset` returns the offset of the marker with the given
+    /// baseline, so it's aligned with the item text.
+    #[allow(clippy::too_many_arguments)]
+    fn paint_marker(
+        &self,
+        scene: &mut Scene,
+        scene_size: &Size,
+        ctx: &mut MarkdownContext,
+        element_box: &Rect,
+        index: usize,
+        brush_palete: &BrushPalete,
+        marker_offset: impl Fn(f64) -> Vec2,
     ) {
         match &self.marker {
             ListMarker::Symbol { symbol } => {
                 let marker_position = element_box.origin().to_vec2()
-                    + Vec2::new(ctx.theme.markdown.bullet_list_indentation, 0.0);
+                    + Vec2::new(
+                        self.level_indentation
+                            + ctx.theme.markdown.bullet_list_indentation,
+                        0.0,
+                    )
+                    + marker_offset(symbol.baseline_offset());
                 symbol.draw_text(scene, scene_size, &marker_position, brush_palete);
             }
             ListMarker::Numbers {
                 start_number: _,
                 layouted,
             } => {
-                let mut marker_position = element_box.origin().to_vec2();
+                let mut marker_position = element_box.origin().to_vec2()
+                    + marker_offset(layouted[index].baseline_offset());
                 marker_position.x += self.indentation
                     - layouted[index].full_width()
                     - ctx.theme.markdown.list_after_indentation;
@@ -275,13 +468,6 @@ impl MarkdownList {
                 );
             }
         }
-        let element_box = element_box.inset(Insets::new(
-            self.indentation,
-            0.0,
-            self.indentation,
-            0.0,
-        ));
-        draw_flow(scene, scene_size, ctx, &element_box, brush_palete, flow);
     }
 
     fn paint(
@@ -343,6 +529,8 @@ impl fmt::Debug for ListMarker {
 pub struct Paragraph {
     text: MarkdownText,
     margin: Margin,
+    // Height computed by the last layout, see `computed_height`.
+    cached_height: Height,
 }
 
 impl Paragraph {
@@ -350,6 +538,7 @@ impl Paragraph {
         Paragraph {
             text,
             margin: Margin::ZERO,
+            cached_height: 0.0,
         }
     }
 
@@ -375,6 +564,38 @@ impl Paragraph {
         self.text.on_mouse_move(text_ctx, &[], &[], width, point)
     }
 
+    pub fn text(&self) -> &MarkdownText {
+        &self.text
+    }
+
+    pub fn link_at_point(&self, point: Point) -> Option<Link> {
+        self.margin
+            .local_position(point, |point| self.text.link_at_point(point))
+    }
+
+    pub fn char_index_at_point(&self, point: Point) -> Option<usize> {
+        self.margin
+            .local_position(point, |point| self.text.char_index_at_point(point))
+    }
+
+    pub fn cursor_index_at_point(&self, point: Point) -> usize {
+        self.margin
+            .local_position(point, |point| self.text.cursor_index_at_point(point))
+    }
+
+    pub fn range_rects(&self, range: Range<usize>) -> Vec<Rect> {
+        let offset = self.margin.offset();
+        self.text
+            .range_rects(range)
+            .into_iter()
+            .map(|rect| rect + offset)
+            .collect()
+    }
+
+    pub fn plain_text(&self) -> &str {
+        self.text.plain_text()
+    }
+
     pub fn on_mouse_leave(&mut self, text_ctx: &mut TextContext, width: f64) {
         self.text.on_mouse_leave(text_ctx, &[], &[], width)
     }
@@ -396,13 +617,25 @@ impl Paragraph {
             self.text
                 .load_and_layout_text(&mut text_ctx, &[], &[], width);
             self.text.height()
-        })
+        });
+        self.cached_height = self.computed_height();
+        self.cached_height
     }
 
     fn height(&self) -> Height {
+        debug_assert_eq!(self.cached_height, self.computed_height());
+        self.cached_height
+    }
+
+    fn computed_height(&self) -> Height {
         self.margin.height() + self.text.height()
     }
 
+    /// Distance of the first line baseline from the top of the paragraph.
+    fn baseline_offset(&self) -> f64 {
+        self.margin.top + self.text.baseline_offset()
+    }
+
     fn paint(
         &self,
         scene: &mut Scene,
@@ -426,16 +659,21 @@ impl Paragraph {
 pub struct CodeBlock {
     text: MarkdownText,
     margin: Margin,
+    // Height computed by the last layout, see `computed_height`.
+    cached_height: Height,
     // TODO: Use the language to do some syntax highlighting
-    _language: Option<String>,
+    language: Option<String>,
+    language_label: Option<SimpleText>,
 }
 
 impl CodeBlock {
     pub fn new(str: String, language: Option<String>) -> CodeBlock {
         CodeBlock {
-            text: MarkdownText::new(str, Vec::new(), Vec::new(), Vec::new()),
+            text: MarkdownText::new(str, Vec::new(), Vec::new()),
             margin: Margin::ZERO,
-            _language: language,
+            cached_height: 0.0,
+            language,
+            language_label: None,
         }
     }
 
@@ -448,9 +686,32 @@ impl CodeBlock {
         // TODO: ...
     }
 
+    pub fn char_index_at_point(&self, point: Point) -> Option<usize> {
+        self.margin
+            .local_position(point, |point| self.text.char_index_at_point(point))
+    }
+
+    pub fn cursor_index_at_point(&self, point: Point) -> usize {
+        self.margin
+            .local_position(point, |point| self.text.cursor_index_at_point(point))
+    }
+
+    pub fn range_rects(&self, range: Range<usize>) -> Vec<Rect> {
+        let offset = self.margin.offset();
+        self.text
+            .range_rects(range)
+            .into_iter()
+            .map(|rect| rect + offset)
+            .collect()
+    }
+
+    pub fn plain_text(&self) -> &str {
+        self.text.plain_text()
+    }
+
     fn layout(&mut self, ctx: &mut MarkdownContext, width: Width) -> Height {
         let margin = ctx.theme.markdown.code_block_margin;
-        self.margin = Margin::new(margin, margin, margin, margin);
+        self.margin = Margin::uniform(margin);
 
         let extra_default_styles = vec![
             StyleProperty::FontStack(ctx.theme.text.monospace_font_stack.clone()),
@@ -463,6 +724,14 @@ impl CodeBlock {
             theme: ctx.theme,
         };
 
+        self.language_label = self.language.as_ref().map(|language| {
+            let mut label = SimpleText::new(language.clone())
+                .with_font_size(ctx.theme.markdown.code_block_language_label_size)
+                .with_brush(BrushPalete::CODE_LANGUAGE_BRUSH);
+            label.build_layout(&mut text_ctx, None);
+            label
+        });
+
         self.margin.layout_by_width(width, |width| {
             self.text.load_and_layout_text(
                 &mut text_ctx,
@@ -471,10 +740,111 @@ impl CodeBlock {
                 width,
             );
             self.text.height()
-        })
+        });
+        self.cached_height = self.computed_height();
+        self.cached_height
+    }
+
+    fn height(&self) -> Height {
+        debug_assert_eq!(self.cached_height, self.computed_height());
+        self.cached_height
+    }
+
+    fn computed_height(&self) -> Height {
+        self.margin.height() + self.text.height()
+    }
+
+    fn content_width(&self) -> Width {
+        self.margin.left + self.text.full_width() + self.margin.right
+    }
+
+    fn paint(
+        &self,
+        scene: &mut Scene,
+        scene_size: &Size,
+        ctx: &mut MarkdownContext,
+        element_box: &Rect,
+        brush_palete: &BrushPalete,
+    ) {
+        self.margin.paint(element_box, |element_box: &Rect| {
+            self.text.draw_text(
+                scene,
+                scene_size,
+                &element_box.origin().to_vec2(),
+                brush_palete,
+            );
+        });
+        if let Some(label) = &self.language_label {
+            let padding = ctx.theme.markdown.code_block_margin / 2.0;
+            let position = Vec2::new(
+                element_box.x1 - label.full_width() - padding,
+                element_box.y0 + padding,
+            );
+            label.draw_text(scene, scene_size, &position, brush_palete);
+        }
+    }
+}
+
+/// Display math (`$$...$$`). For now the TeX source is shown centered in the
+/// monospace font. A math layout engine can be plugged into `layout` and
+/// `paint` without changes to the parser.
+#[derive(Clone, Debug)]
+pub struct MathBlock {
+    text: MarkdownText,
+    margin: Margin,
+    // Height computed by the last layout, see `computed_height`.
+    cached_height: Height,
+}
+
+impl MathBlock {
+    pub fn new(source: String) -> MathBlock {
+        MathBlock {
+            text: MarkdownText::new(source, Vec::new(), Vec::new()),
+            margin: Margin::ZERO,
+            cached_height: 0.0,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        self.text.plain_text()
+    }
+
+    fn layout(&mut self, ctx: &mut MarkdownContext, width: Width) -> Height {
+        let margin = ctx.theme.markdown.code_block_margin;
+        self.margin = Margin::uniform(margin);
+
+        let extra_default_styles = vec![
+            StyleProperty::FontStack(ctx.theme.text.monospace_font_stack.clone()),
+            StyleProperty::FontStyle(FontStyle::Italic),
+            StyleProperty::Brush(BrushPalete::CODE_BRUSH),
+        ];
+
+        let mut text_ctx: TextContext = TextContext {
+            layout_ctx: ctx.layout_ctx,
+            svg_ctx: ctx.svg_ctx,
+            theme: ctx.theme,
+        };
+
+        self.margin.layout_by_width(width, |width| {
+            self.text.load_and_layout_text(
+                &mut text_ctx,
+                &extra_default_styles,
+                &[],
+                width,
+            );
+            self.text.align(width, Alignment::Middle);
+            self.text.height()
+        });
+        self.cached_height = self.computed_height();
+        self.cached_height
     }
 
     fn height(&self) -> Height {
+        debug_assert_eq!(self.cached_height, self.computed_height());
+        self.cached_height
+    }
+
+    fn computed_height(&self) -> Height {
         self.margin.height() + self.text.height()
     }
 
@@ -544,7 +914,9 @@ pub struct Indented {
     decoration: IndentationDecoration,
     flow: LayoutFlow<MarkdownContent>,
     symbol: LayoutedText,
-    height: Height,
+    symbol_padding: Margin,
+    // Height computed by the last layout, see `computed_height`.
+    cached_height: Height,
 }
 
 impl std::fmt::Debug for Indented {
@@ -573,7 +945,8 @@ impl Indented {
             padding: Margin::ZERO,
             decoration_margin: Margin::ZERO,
             symbol: LayoutedText::empty(),
-            height: 0.0,
+            symbol_padding: Margin::ZERO,
+            cached_height: 0.0,
         }
     }
 
@@ -656,23 +1029,26 @@ impl Indented {
             })
         });
 
-        let symbol_padding: Margin =
-            theme.box_quotation.symbol_padding.clone().into();
+        self.symbol_padding = theme.box_quotation.symbol_padding.clone().into();
+        self.cached_height = self.computed_height();
+        self.cached_height
+    }
+
+    fn height(&self) -> Height {
+        debug_assert_eq!(self.cached_height, self.computed_height());
+        self.cached_height
+    }
 
+    fn computed_height(&self) -> Height {
         let box_height = self.padding.height()
             + self.flow.height()
             + self.decoration_margin.height()
             + self.margin.height();
-        let symbol_height = symbol_padding.height()
+        let symbol_height = self.symbol_padding.height()
             + self.symbol.height()
             + self.decoration_margin.height()
             + self.margin.height();
-        self.height = box_height.max(symbol_height);
-        self.height
-    }
-
-    fn height(&self) -> Height {
-        self.height
+        box_height.max(symbol_height)
     }
 
     fn paint(
@@ -726,7 +1102,7 @@ impl Indented {
                     let y0 = half_line_width;
                     let x1 = element_box.width() - half_line_width;
                     let y1 = element_box.height() - half_line_width;
-                    let box_shape = Rect::new(x0, y0, x1, y1);
+                    let box_rect = Rect::new(x0, y0, x1, y1);
 
                     let stroke = Stroke {
                         width: theme.box_line_width,
@@ -741,12 +1117,8 @@ impl Indented {
                     let transform =
                         Affine::translate(element_box.origin().to_vec2());
 
-                    scene.stroke(
-                        &stroke,
-                        transform,
-                        color,
-                        Some(Affine::IDENTITY),
-                        &box_shape,
+                    SizedScene::new(scene, *scene_size).stroke_rounded_rect(
+                        transform, box_rect, 0.0, &stroke, color,
                     );
 
                     let x0 = theme.box_line_width
@@ -807,6 +1179,8 @@ pub struct Header {
     margin: Margin,
     text: MarkdownText,
     level: HeadingLevel,
+    // Height computed by the last layout, see `computed_height`.
+    cached_height: Height,
 }
 
 impl Header {
@@ -815,6 +1189,7 @@ impl Header {
             margin: Margin::ZERO,
             text,
             level,
+            cached_height: 0.0,
         }
     }
 
@@ -827,6 +1202,53 @@ impl Header {
         // TODO: ...
     }
 
+    pub fn link_at_point(&self, point: Point) -> Option<Link> {
+        self.margin
+            .local_position(point, |point| self.text.link_at_point(point))
+    }
+
+    pub fn char_index_at_point(&self, point: Point) -> Option<usize> {
+        self.margin
+            .local_position(point, |point| self.text.char_index_at_point(point))
+    }
+
+    pub fn cursor_index_at_point(&self, point: Point) -> usize {
+        self.margin
+            .local_position(point, |point| self.text.cursor_index_at_point(point))
+    }
+
+    pub fn range_rects(&self, range: Range<usize>) -> Vec<Rect> {
+        let offset = self.margin.offset();
+        self.text
+            .range_rects(range)
+            .into_iter()
+            .map(|rect| rect + offset)
+            .collect()
+    }
+
+    pub fn text(&self) -> &MarkdownText {
+        &self.text
+    }
+
+    pub fn level(&self) -> HeadingLevel {
+        self.level
+    }
+
+    /// Returns the anchor of the header in the same form as GitHub generates
+    /// them. The text is lowercased, spaces are replaced with `-` and the
+    /// punctuation is removed.
+    pub fn anchor(&self) -> String {
+        let mut anchor = String::new();
+        for c in self.text.plain_text().trim().chars() {
+            if c == ' ' {
+                anchor.push('-');
+            } else if c.is_alphanumeric() || c == '-' || c == '_' {
+                anchor.extend(c.to_lowercase());
+            }
+        }
+        anchor
+    }
+
     fn layout(
         &mut self,
         ctx: &mut MarkdownContext,
@@ -861,10 +1283,17 @@ impl Header {
                 width,
             );
             self.text.height()
-        })
+        });
+        self.cached_height = self.computed_height();
+        self.cached_height
     }
 
     fn height(&self) -> Height {
+        debug_assert_eq!(self.cached_height, self.computed_height());
+        self.cached_height
+    }
+
+    fn computed_height(&self) -> Height {
         self.margin.height() + self.text.height()
     }
 
@@ -891,6 +1320,8 @@ impl Header {
 pub struct HorizontalLine {
     height: f64,
     margin: Margin,
+    // Height computed by the last layout, see `computed_height`.
+    cached_height: Height,
 }
 
 impl HorizontalLine {
@@ -898,6 +1329,7 @@ impl HorizontalLine {
         HorizontalLine {
             margin: Margin::ZERO,
             height: 0.0,
+            cached_height: 0.0,
         }
     }
 
@@ -907,21 +1339,25 @@ impl HorizontalLine {
         width: Width,
         reduce_top_margin: bool,
     ) -> Height {
-        self.margin = Margin::new(
-            if reduce_top_margin {
-                0.0
-            } else {
-                ctx.theme.markdown.horizontal_line_vertical_margin
-            },
-            ctx.theme.markdown.horizontal_line_horizontal_margin,
+        self.margin = Margin::symmetric(
             ctx.theme.markdown.horizontal_line_vertical_margin,
             ctx.theme.markdown.horizontal_line_horizontal_margin,
         );
+        if reduce_top_margin {
+            self.margin.top = 0.0;
+        }
         self.height = ctx.theme.markdown.horizontal_line_height;
-        self.margin.layout_by_width(width, |_width| self.height)
+        self.margin.layout_by_width(width, |_width| self.height);
+        self.cached_height = self.computed_height();
+        self.cached_height
     }
 
     fn height(&self) -> Height {
+        debug_assert_eq!(self.cached_height, self.computed_height());
+        self.cached_height
+    }
+
+    fn computed_height(&self) -> Height {
         self.margin.height() + self.height
     }
 
@@ -966,55 +1402,690 @@ impl Default for HorizontalLine {
     }
 }
 
+/// Image which is the only content of a paragraph. It's shown centered and
+/// scaled down (keeping the aspect ratio) to fit the content width.
 #[derive(Clone, Debug)]
-pub enum MarkdownContent {
-    Indented(Indented),
-    Header(Header),
-    List(MarkdownList),
-    Paragraph(Paragraph),
-    CodeBlock(CodeBlock),
-    HorizontalLine(HorizontalLine),
+pub struct ImageBlock {
+    image: InlinedImage,
+    // Link the image is wrapped in (`[![alt](image)](url)`).
+    link: Option<String>,
+    margin: Margin,
+    // Width the image is centered in.
+    width: Width,
+    // Size of the shown image.
+    size: Size,
+    // Loading the image failed, the alt text is shown under the broken image
+    // placeholder.
+    broken: bool,
+    alt_text: Option<SimpleText>,
+    // Height computed by the last layout, see `computed_height`.
+    cached_height: Height,
 }
 
-impl MarkdownContent {
-    pub fn on_mouse_click(
-        &mut self,
-        text_ctx: &mut TextContext,
-        width: f64,
-        position: Point,
-        click: Click,
-    ) {
-        match self {
-            MarkdownContent::Indented(indented) => {
-                indented.on_mouse_click(text_ctx, width, position, click)
-            }
-            MarkdownContent::Header(header) => {
-                //header.on_mouse_move(text_ctx, width, point)
-                todo!()
-            }
-            MarkdownContent::List(markdown_list) => {
-                //markdown_list.on_mouse_move(text_ctx, width, point)
-                todo!()
-            }
-            MarkdownContent::Paragraph(paragraph) => {
-                //paragraph.on_mouse_click(text_ctx, width, position, click)
-                todo!()
-            }
-            MarkdownContent::CodeBlock(code_block) => {
-                //code_block.on_mouse_move(text_ctx, width, point)
-                todo!()
-            }
-            MarkdownContent::HorizontalLine(_horizontal_line) => {}
+impl ImageBlock {
+    pub fn new(image: InlinedImage) -> ImageBlock {
+        ImageBlock {
+            image,
+            link: None,
+            margin: Margin::ZERO,
+            width: 0.0,
+            size: Size::ZERO,
+            broken: false,
+            alt_text: None,
+            cached_height: 0.0,
         }
     }
-    pub fn on_mouse_move(
-        &mut self,
-        text_ctx: &mut TextContext,
-        width: f64,
-        position: Point,
-    ) {
-        match self {
-            MarkdownContent::Indented(indented) => {
+
+    /// Makes the image a link to the `url`.
+    pub fn with_link(mut self, url: Option<String>) -> Self {
+        self.link = url;
+        self
+    }
+
+    pub fn image(&self) -> &InlinedImage {
+        &self.image
+    }
+
+    pub fn link(&self) -> Option<&str> {
+        self.link.as_deref()
+    }
+
+    /// Returns the link of the image when the `point` is over it. The `point`
+    /// is in the element coordinates.
+    pub fn link_at_point(&self, point: Point) -> Option<Link> {
+        let url = self.link.as_ref()?;
+        self.margin.local_position(point, |point| {
+            let width = self.size.width;
+            let x = point.x - (self.width - width).max(0.0) / 2.0;
+            let over_image = (0.0..width).contains(&x)
+                && (0.0..self.size.height).contains(&point.y);
+            over_image.then(|| Link::new(url.clone(), 0..0))
+        })
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut MarkdownContext,
+        width: Width,
+        reduce_top_margin: bool,
+    ) -> Height {
+        self.margin.top = if reduce_top_margin {
+            0.0
+        } else {
+            ctx.theme.markdown.paragraph_top_margin
+        };
+        self.margin.layout_by_width(width, |width| {
+            self.width = width;
+            if self.image.load(ctx.svg_ctx, width) {
+                self.broken = true;
+            }
+            self.size = self.image.data().map_or(Size::ZERO, |data| {
+                let size = Size::new(data.width as f64, data.height as f64);
+                // The image is scaled down to the width it was loaded with,
+                // but the width can get smaller later.
+                if size.width > width && size.width > 0.0 {
+                    size * (width.max(0.0) / size.width)
+                } else {
+                    size
+                }
+            });
+            // Same style as the alt text of the broken inline images.
+            self.alt_text =
+                (self.broken && !self.image.alt().is_empty()).then(|| {
+                    let mut text_ctx = TextContext {
+                        layout_ctx: ctx.layout_ctx,
+                        svg_ctx: ctx.svg_ctx,
+                        theme: ctx.theme,
+                    };
+                    let mut alt_text = SimpleText::new(self.image.alt().to_string())
+                        .with_brush(BrushPalete::INDENTATION_BRUSH);
+                    alt_text.build_layout(&mut text_ctx, Some(width));
+                    alt_text.align(Some(width as f32), Alignment::Middle, false);
+                    alt_text
+                });
+            self.size.height + self.alt_text.as_ref().map_or(0.0, SimpleText::height)
+        });
+        self.cached_height = self.computed_height();
+        self.cached_height
+    }
+
+    fn height(&self) -> Height {
+        debug_assert_eq!(self.cached_height, self.computed_height());
+        self.cached_height
+    }
+
+    fn computed_height(&self) -> Height {
+        self.margin.height()
+            + self.size.height
+            + self.alt_text.as_ref().map_or(0.0, SimpleText::height)
+    }
+
+    fn paint(
+        &self,
+        scene: &mut Scene,
+        scene_size: &Size,
+        element_box: &Rect,
+        brush_palete: &BrushPalete,
+    ) {
+        let Some(data) = self.image.data() else {
+            return;
+        };
+        if data.width == 0 {
+            return;
+        }
+        self.margin.paint(element_box, |element_box: &Rect| {
+            let x = (element_box.width() - self.size.width).max(0.0) / 2.0;
+            let transform = Affine::translate(element_box.origin().to_vec2())
+                * Affine::translate((x, 0.0))
+                * Affine::scale(self.size.width / data.width as f64);
+            scene.draw_image(data, transform);
+            if let Some(alt_text) = &self.alt_text {
+                let position = element_box.origin().to_vec2()
+                    + Vec2::new(0.0, self.size.height);
+                alt_text.draw_text(scene, scene_size, &position, brush_palete);
+            }
+        });
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Table {
+    margin: Margin,
+    head: Vec<MarkdownText>,
+    rows: Vec<Vec<MarkdownText>>,
+    alignments: Vec<pulldown_cmark::Alignment>,
+    // Widths of the cell texts (without the padding).
+    column_widths: Vec<f64>,
+    // Heights of the head row followed by the body rows (with the padding).
+    row_heights: Vec<f64>,
+    border_width: f64,
+    cell_padding: f64,
+    // Height computed by the last layout, see `computed_height`.
+    cached_height: Height,
+}
+
+impl Table {
+    pub fn new(
+        head: Vec<MarkdownText>,
+        rows: Vec<Vec<MarkdownText>>,
+        alignments: Vec<pulldown_cmark::Alignment>,
+    ) -> Table {
+        Table {
+            margin: Margin::ZERO,
+            head,
+            rows,
+            alignments,
+            column_widths: Vec::new(),
+            row_heights: Vec::new(),
+            border_width: 0.0,
+            cell_padding: 0.0,
+            cached_height: 0.0,
+        }
+    }
+
+    fn column_count(&self) -> usize {
+        self.rows
+            .iter()
+            .map(Vec::len)
+            .chain([self.head.len(), self.alignments.len()])
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Iterates over the head row followed by the body rows.
+    fn all_rows(&self) -> impl Iterator<Item = &Vec<MarkdownText>> {
+        std::iter::once(&self.head).chain(self.rows.iter())
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut MarkdownContext,
+        width: Width,
+        reduce_top_margin: bool,
+    ) -> Height {
+        let margin = ctx.theme.markdown.table_margin;
+        self.margin = Margin::new(
+            if reduce_top_margin { 0.0 } else { margin },
+            0.0,
+            margin,
+            0.0,
+        );
+        let padding = ctx.theme.markdown.table_cell_padding;
+        let border = ctx.theme.markdown.table_border_width;
+        self.border_width = border;
+        self.cell_padding = padding;
+        let column_count = self.column_count();
+        let head_styles = [StyleProperty::FontWeight(FontWeight::BOLD)];
+
+        let mut text_ctx: TextContext =
+            TextContext::new(ctx.svg_ctx, ctx.layout_ctx, ctx.theme);
+
+        self.margin.layout_by_width(width, |width| {
+            // Space taken by the borders and the paddings.
+            let decorations = border * (column_count + 1) as f64
+                + 2.0 * padding * column_count as f64;
+            let available_width = (width - decorations).max(0.0);
+
+            // The columns are as wide as their widest cell without wrapping.
+            let mut natural_widths = vec![0.0; column_count];
+            let all_rows = std::iter::once((&head_styles[..], &mut self.head))
+                .chain(self.rows.iter_mut().map(|row| (&[][..], row)));
+            for (styles, row) in all_rows {
+                for (column, cell) in row.iter_mut().enumerate() {
+                    cell.load_and_layout_text(
+                        &mut text_ctx,
+                        styles,
+                        &[],
+                        f32::MAX as f64,
+                    );
+                    natural_widths[column] =
+                        f64::max(natural_widths[column], cell.full_width());
+                }
+            }
+            // When the table doesn't fit all the columns shrink proportionally.
+            let natural_width: f64 = natural_widths.iter().sum();
+            self.column_widths = if natural_width > available_width {
+                natural_widths
+                    .iter()
+                    .map(|width| width * available_width / natural_width)
+                    .collect()
+            } else {
+                natural_widths
+            };
+
+            self.row_heights.clear();
+            let all_rows = std::iter::once((&head_styles[..], &mut self.head))
+                .chain(self.rows.iter_mut().map(|row| (&[][..], row)));
+            for (styles, row) in all_rows {
+                let mut row_height: f64 = 0.0;
+                for (column, cell) in row.iter_mut().enumerate() {
+                    let column_width = self.column_widths[column];
+                    cell.load_and_layout_text(
+                        &mut text_ctx,
+                        styles,
+                        &[],
+                        column_width,
+                    );
+                    let alignment = match self.alignments.get(column) {
+                        Some(pulldown_cmark::Alignment::Center) => Alignment::Middle,
+                        Some(pulldown_cmark::Alignment::Right) => Alignment::End,
+                        _ => Alignment::Start,
+                    };
+                    cell.align(column_width, alignment);
+                    row_height = row_height.max(cell.height());
+                }
+                self.row_heights.push(row_height + 2.0 * padding);
+            }
+            self.row_heights.iter().sum::<f64>()
+                + border * (self.row_heights.len() + 1) as f64
+        });
+        self.cached_height = self.computed_height();
+        self.cached_height
+    }
+
+    fn height(&self) -> Height {
+        debug_assert_eq!(self.cached_height, self.computed_height());
+        self.cached_height
+    }
+
+    fn computed_height(&self) -> Height {
+        self.margin.height()
+            + self.row_heights.iter().sum::<f64>()
+            + self.border_width * (self.row_heights.len() + 1) as f64
+    }
+
+    fn content_width(&self) -> Width {
+        let column_count = self.column_widths.len();
+        self.margin.left
+            + self.column_widths.iter().sum::<f64>()
+            + self.border_width * (column_count + 1) as f64
+            + 2.0 * self.cell_padding * column_count as f64
+            + self.margin.right
+    }
+
+    fn paint(
+        &self,
+        scene: &mut Scene,
+        scene_size: &Size,
+        ctx: &mut MarkdownContext,
+        element_box: &Rect,
+        brush_palete: &BrushPalete,
+    ) {
+        let padding = ctx.theme.markdown.table_cell_padding;
+        let border = ctx.theme.markdown.table_border_width;
+        let stroke = Stroke::new(border);
+        self.margin.paint(element_box, |element_box: &Rect| {
+            let origin = element_box.origin().to_vec2();
+            let transform = Affine::translate(origin);
+            let mut y = border;
+            for (row, row_height) in self.all_rows().zip(self.row_heights.iter()) {
+                let mut x = border;
+                for (column, column_width) in self.column_widths.iter().enumerate() {
+                    let cell_width = column_width + 2.0 * padding;
+                    // The border is centered on the cell edges.
+                    let cell_box = Rect::new(x, y, x + cell_width, y + row_height)
+                        .inflate(border / 2.0, border / 2.0);
+                    scene.stroke(
+                        &stroke,
+                        transform,
+                        ctx.theme.markdown.table_border_color,
+                        None,
+                        &cell_box,
+                    );
+                    if let Some(cell) = row.get(column) {
+                        cell.draw_text(
+                            scene,
+                            scene_size,
+                            &(origin + Vec2::new(x + padding, y + padding)),
+                            brush_palete,
+                        );
+                    }
+                    x += cell_width + border;
+                }
+                y += row_height + border;
+            }
+        });
+    }
+
+    fn accessibility(
+        &self,
+        update: &mut TreeUpdate,
+        next_node_id: &mut dyn FnMut() -> NodeId,
+    ) -> Node {
+        let mut node = Node::new(Role::Table);
+        for (row_index, row) in self.all_rows().enumerate() {
+            let mut row_node = Node::new(Role::Row);
+            for cell in row {
+                let mut cell_node = Node::new(if row_index == 0 {
+                    Role::ColumnHeader
+                } else {
+                    Role::Cell
+                });
+                cell_node.set_value(cell.plain_text());
+                let cell_id = next_node_id();
+                update.nodes.push((cell_id, cell_node));
+                row_node.push_child(cell_id);
+            }
+            let row_id = next_node_id();
+            update.nodes.push((row_id, row_node));
+            node.push_child(row_id);
+        }
+        node
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Footnote {
+    margin: Margin,
+    label: String,
+    marker: SimpleText,
+    flow: LayoutFlow<MarkdownContent>,
+    indentation: f64,
+    // Height computed by the last layout, see `computed_height`.
+    cached_height: Height,
+}
+
+impl Footnote {
+    /// Creates the footnote definition. The back reference to the footnote
+    /// reference is appended to the last paragraph of the `flow`.
+    pub fn new(
+        label: String,
+        number: usize,
+        mut flow: LayoutFlow<MarkdownContent>,
+    ) -> Footnote {
+        let back_reference = format!("#fnref-{label}");
+        match flow.pop() {
+            Some(MarkdownContent::Paragraph(mut paragraph)) => {
+                paragraph.text.push_link("↩", back_reference);
+                flow.push(MarkdownContent::Paragraph(paragraph));
+            }
+            last => {
+                if let Some(last) = last {
+                    flow.push(last);
+                }
+                let mut text =
+                    MarkdownText::new(String::new(), Vec::new(), Vec::new());
+                text.push_link("↩", back_reference);
+                flow.push(MarkdownContent::Paragraph(Paragraph::new(text)));
+            }
+        }
+        Footnote {
+            margin: Margin::ZERO,
+            label,
+            marker: format!("{number}.").into(),
+            flow,
+            indentation: 0.0,
+            cached_height: 0.0,
+        }
+    }
+
+    /// Returns the anchor the footnote references link to.
+    pub fn anchor(&self) -> String {
+        format!("fn-{}", self.label)
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut MarkdownContext,
+        width: Width,
+        reduce_top_margin: bool,
+    ) -> Height {
+        let mut text_ctx: TextContext =
+            TextContext::new(ctx.svg_ctx, ctx.layout_ctx, ctx.theme);
+        self.marker.build_layout(&mut text_ctx, None);
+        self.indentation = self.marker.full_width()
+            + ctx.theme.markdown.numbered_list_indentation
+            + ctx.theme.markdown.list_after_indentation;
+
+        self.margin.top = if reduce_top_margin {
+            0.0
+        } else {
+            ctx.theme.markdown.paragraph_top_margin
+        };
+        self.margin.layout_by_width(width, |width| {
+            self.flow.apply_to_all(|(i, data)| {
+                data.layout(ctx, width - self.indentation, i == 0);
+            });
+            self.flow.height()
+        });
+        self.cached_height = self.computed_height();
+        self.cached_height
+    }
+
+    fn height(&self) -> Height {
+        debug_assert_eq!(self.cached_height, self.computed_height());
+        self.cached_height
+    }
+
+    fn computed_height(&self) -> Height {
+        self.margin.height() + self.flow.height()
+    }
+
+    fn paint(
+        &self,
+        scene: &mut Scene,
+        scene_size: &Size,
+        ctx: &mut MarkdownContext,
+        element_box: &Rect,
+        brush_palete: &BrushPalete,
+    ) {
+        self.margin.paint(element_box, |element_box: &Rect| {
+            let marker_position = element_box.origin().to_vec2()
+                + Vec2::new(ctx.theme.markdown.numbered_list_indentation, 0.0);
+            self.marker
+                .draw_text(scene, scene_size, &marker_position, brush_palete);
+            let element_box = Rect::new(
+                element_box.x0 + self.indentation,
+                element_box.y0,
+                element_box.x1,
+                element_box.y1,
+            );
+            draw_flow(
+                scene,
+                scene_size,
+                ctx,
+                &element_box,
+                brush_palete,
+                &self.flow,
+            );
+        });
+    }
+}
+
+/// The titles (terms) with their definitions. The definitions are indented
+/// by the `definition_indent` from the theme.
+#[derive(Clone, Debug)]
+pub struct DefinitionList {
+    margin: Margin,
+    items: Vec<(MarkdownText, LayoutFlow<MarkdownContent>)>,
+    // Top margin of the titles except the first one.
+    title_margin: f64,
+    indentation: f64,
+    // Height computed by the last layout, see `computed_height`.
+    cached_height: Height,
+}
+
+impl DefinitionList {
+    pub fn new(items: Vec<(MarkdownText, LayoutFlow<MarkdownContent>)>) -> Self {
+        Self {
+            margin: Margin::ZERO,
+            items,
+            title_margin: 0.0,
+            indentation: 0.0,
+            cached_height: 0.0,
+        }
+    }
+
+    pub fn items(&self) -> &[(MarkdownText, LayoutFlow<MarkdownContent>)] {
+        &self.items
+    }
+
+    /// Returns the definitions with the offsets of their tops from the top of
+    /// the list.
+    fn nested_flows(&self) -> Vec<(f64, &LayoutFlow<MarkdownContent>)> {
+        let mut offset = self.margin.top;
+        let mut flows = Vec::with_capacity(self.items.len());
+        for (index, (title, definition)) in self.items.iter().enumerate() {
+            if index > 0 {
+                offset += self.title_margin;
+            }
+            offset += title.height();
+            flows.push((offset, definition));
+            offset += definition.height();
+        }
+        flows
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut MarkdownContext,
+        width: Width,
+        reduce_top_margin: bool,
+    ) -> Height {
+        self.margin.top = if reduce_top_margin {
+            0.0
+        } else {
+            ctx.theme.markdown.paragraph_top_margin
+        };
+        self.title_margin = ctx.theme.markdown.paragraph_top_margin;
+        self.indentation = ctx.theme.markdown.definition_indent;
+        self.margin.layout_by_width(width, |width| {
+            let mut height = 0.0;
+            for (index, (title, definition)) in self.items.iter_mut().enumerate() {
+                let mut text_ctx: TextContext =
+                    TextContext::new(ctx.svg_ctx, ctx.layout_ctx, ctx.theme);
+                title.load_and_layout_text(&mut text_ctx, &[], &[], width);
+                definition.apply_to_all(|(i, data)| {
+                    data.layout(ctx, width - self.indentation, i == 0);
+                });
+                if index > 0 {
+                    height += self.title_margin;
+                }
+                height += title.height() + definition.height();
+            }
+            height
+        });
+        self.cached_height = self.computed_height();
+        self.cached_height
+    }
+
+    fn height(&self) -> Height {
+        debug_assert_eq!(self.cached_height, self.computed_height());
+        self.cached_height
+    }
+
+    fn computed_height(&self) -> Height {
+        let titles_margin =
+            self.title_margin * self.items.len().saturating_sub(1) as f64;
+        self.margin.height()
+            + titles_margin
+            + self
+                .items
+                .iter()
+                .map(|(title, definition)| title.height() + definition.height())
+                .sum::<f64>()
+    }
+
+    fn paint(
+        &self,
+        scene: &mut Scene,
+        scene_size: &Size,
+        ctx: &mut MarkdownContext,
+        element_box: &Rect,
+        brush_palete: &BrushPalete,
+    ) {
+        self.margin.paint(element_box, |element_box: &Rect| {
+            let mut y = element_box.y0;
+            for (index, (title, definition)) in self.items.iter().enumerate() {
+                if index > 0 {
+                    y += self.title_margin;
+                }
+                title.draw_text(
+                    scene,
+                    scene_size,
+                    &Vec2::new(element_box.x0, y),
+                    brush_palete,
+                );
+                y += title.height();
+                let definition_box = Rect::new(
+                    element_box.x0 + self.indentation,
+                    y,
+                    element_box.x1,
+                    y + definition.height(),
+                );
+                draw_flow(
+                    scene,
+                    scene_size,
+                    ctx,
+                    &definition_box,
+                    brush_palete,
+                    definition,
+                );
+                y += definition.height();
+            }
+        });
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum MarkdownContent {
+    Indented(Indented),
+    Header(Header),
+    List(MarkdownList),
+    Paragraph(Paragraph),
+    CodeBlock(CodeBlock),
+    HorizontalLine(HorizontalLine),
+    Table(Table),
+    Footnote(Footnote),
+    Math(MathBlock),
+    Image(ImageBlock),
+    DefinitionList(DefinitionList),
+}
+
+impl MarkdownContent {
+    pub fn on_mouse_click(
+        &mut self,
+        text_ctx: &mut TextContext,
+        width: f64,
+        position: Point,
+        click: Click,
+    ) {
+        match self {
+            MarkdownContent::Indented(indented) => {
+                indented.on_mouse_click(text_ctx, width, position, click)
+            }
+            MarkdownContent::Header(header) => {
+                //header.on_mouse_move(text_ctx, width, point)
+                todo!()
+            }
+            MarkdownContent::List(markdown_list) => {
+                //markdown_list.on_mouse_move(text_ctx, width, point)
+                todo!()
+            }
+            MarkdownContent::Paragraph(paragraph) => {
+                //paragraph.on_mouse_click(text_ctx, width, position, click)
+                todo!()
+            }
+            MarkdownContent::CodeBlock(code_block) => {
+                //code_block.on_mouse_move(text_ctx, width, point)
+                todo!()
+            }
+            MarkdownContent::HorizontalLine(_horizontal_line) => {}
+            MarkdownContent::Table(_table) => {}
+            MarkdownContent::Footnote(_footnote) => {}
+            MarkdownContent::Math(_math_block) => {}
+            MarkdownContent::Image(_image_block) => {}
+            MarkdownContent::DefinitionList(_definition_list) => {}
+        }
+    }
+    pub fn on_mouse_move(
+        &mut self,
+        text_ctx: &mut TextContext,
+        width: f64,
+        position: Point,
+    ) {
+        match self {
+            MarkdownContent::Indented(indented) => {
                 indented.on_mouse_move(text_ctx, width, position)
             }
             MarkdownContent::Header(header) => {
@@ -1033,6 +2104,11 @@ impl MarkdownContent {
                 todo!()
             }
             MarkdownContent::HorizontalLine(_horizontal_line) => {}
+            MarkdownContent::Table(_table) => {}
+            MarkdownContent::Footnote(_footnote) => {}
+            MarkdownContent::Math(_math_block) => {}
+            MarkdownContent::Image(_image_block) => {}
+            MarkdownContent::DefinitionList(_definition_list) => {}
         }
     }
 
@@ -1059,9 +2135,248 @@ impl MarkdownContent {
                 todo!()
             }
             MarkdownContent::HorizontalLine(_horizontal_line) => {}
+            MarkdownContent::Table(_table) => {}
+            MarkdownContent::Footnote(_footnote) => {}
+            MarkdownContent::Math(_math_block) => {}
+            MarkdownContent::Image(_image_block) => {}
+            MarkdownContent::DefinitionList(_definition_list) => {}
+        }
+    }
+
+    /// Returns the link under the `point`. The `point` is in the element
+    /// coordinates.
+    pub fn link_at_point(&self, point: Point) -> Option<Link> {
+        match self {
+            MarkdownContent::Paragraph(paragraph) => paragraph.link_at_point(point),
+            MarkdownContent::Header(header) => header.link_at_point(point),
+            MarkdownContent::Image(image_block) => image_block.link_at_point(point),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` when the `anchor` (the part of the link after `#`)
+    /// points to this element.
+    pub fn has_anchor(&self, anchor: &str) -> bool {
+        match self {
+            MarkdownContent::Header(header) => header.anchor() == anchor,
+            MarkdownContent::Footnote(footnote) => footnote.anchor() == anchor,
+            // The footnote back references point to the text with the
+            // footnote reference.
+            MarkdownContent::Paragraph(paragraph) => {
+                anchor.strip_prefix("fnref-").is_some_and(|label| {
+                    paragraph.text.has_link(&format!("#fn-{label}"))
+                })
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the flows nested in the element with the offsets of their tops
+    /// from the top of the element.
+    pub fn nested_flows(&self) -> Vec<(f64, &LayoutFlow<MarkdownContent>)> {
+        match self {
+            MarkdownContent::Indented(indented) => {
+                let offset = indented.margin.top
+                    + indented.decoration_margin.top
+                    + indented.padding.top;
+                vec![(offset, &indented.flow)]
+            }
+            MarkdownContent::List(markdown_list) => markdown_list.nested_flows(),
+            MarkdownContent::DefinitionList(definition_list) => {
+                definition_list.nested_flows()
+            }
+            MarkdownContent::Footnote(footnote) => {
+                vec![(footnote.margin.top, &footnote.flow)]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Toggles the task list checkbox under the `point`. Returns `true` when
+    /// some checkbox was toggled. The `point` is in the element coordinates.
+    pub fn toggle_task_at_point(&mut self, point: Point) -> bool {
+        match self {
+            MarkdownContent::List(markdown_list) => {
+                markdown_list.toggle_task_at_point(point)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the byte index of the selectable text under the `point`. The
+    /// `point` is in the element coordinates.
+    pub fn char_index_at_point(&self, point: Point) -> Option<usize> {
+        match self {
+            MarkdownContent::Paragraph(paragraph) => {
+                paragraph.char_index_at_point(point)
+            }
+            MarkdownContent::Header(header) => header.char_index_at_point(point),
+            MarkdownContent::CodeBlock(code_block) => {
+                code_block.char_index_at_point(point)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the byte index of the cursor nearest to the `point` for the
+    /// elements with selectable text. The `point` is in the element
+    /// coordinates.
+    pub fn cursor_index_at_point(&self, point: Point) -> Option<usize> {
+        match self {
+            MarkdownContent::Paragraph(paragraph) => {
+                Some(paragraph.cursor_index_at_point(point))
+            }
+            MarkdownContent::Header(header) => {
+                Some(header.cursor_index_at_point(point))
+            }
+            MarkdownContent::CodeBlock(code_block) => {
+                Some(code_block.cursor_index_at_point(point))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the bounding boxes of the text in the byte `range` in the
+    /// element coordinates.
+    pub fn range_rects(&self, range: Range<usize>) -> Vec<Rect> {
+        match self {
+            MarkdownContent::Paragraph(paragraph) => paragraph.range_rects(range),
+            MarkdownContent::Header(header) => header.range_rects(range),
+            MarkdownContent::CodeBlock(code_block) => code_block.range_rects(range),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the text of the elements with the selectable text.
+    pub fn text_mut(&mut self) -> Option<&mut MarkdownText> {
+        match self {
+            MarkdownContent::Paragraph(paragraph) => Some(&mut paragraph.text),
+            MarkdownContent::Header(header) => Some(&mut header.text),
+            MarkdownContent::CodeBlock(code_block) => Some(&mut code_block.text),
+            _ => None,
         }
     }
 
+    /// Returns the selectable text of the element.
+    pub fn plain_text(&self) -> Option<&str> {
+        match self {
+            MarkdownContent::Paragraph(paragraph) => Some(paragraph.plain_text()),
+            MarkdownContent::Header(header) => Some(header.text().plain_text()),
+            MarkdownContent::CodeBlock(code_block) => Some(code_block.plain_text()),
+            _ => None,
+        }
+    }
+
+    /// Adds the accessibility nodes of the element and of its children to the
+    /// `update`. Returns the id of the element node.
+    pub fn accessibility(
+        &self,
+        update: &mut TreeUpdate,
+        next_node_id: &mut dyn FnMut() -> NodeId,
+    ) -> Option<NodeId> {
+        let node = match self {
+            MarkdownContent::Header(header) => {
+                let mut node = Node::new(Role::Heading);
+                node.set_level(header.level() as usize);
+                node.set_value(header.text().plain_text());
+                node
+            }
+            MarkdownContent::Paragraph(paragraph) => {
+                let mut node = Node::new(Role::Paragraph);
+                node.set_value(paragraph.plain_text());
+                node
+            }
+            MarkdownContent::CodeBlock(code_block) => {
+                let mut node = Node::new(Role::Code);
+                node.set_value(code_block.plain_text());
+                node
+            }
+            MarkdownContent::List(markdown_list) => {
+                let mut node = Node::new(Role::List);
+                for (item, task) in
+                    markdown_list.list.iter().zip(markdown_list.tasks.iter())
+                {
+                    let mut item_node = Node::new(Role::ListItem);
+                    if let Some(checked) = task {
+                        item_node.set_toggled(if *checked {
+                            Toggled::True
+                        } else {
+                            Toggled::False
+                        });
+                    }
+                    item_node.set_children(flow_accessibility(
+                        item,
+                        update,
+                        next_node_id,
+                    ));
+                    let item_id = next_node_id();
+                    update.nodes.push((item_id, item_node));
+                    node.push_child(item_id);
+                }
+                node
+            }
+            MarkdownContent::Indented(indented) => {
+                let mut node = Node::new(Role::Blockquote);
+                node.set_children(flow_accessibility(
+                    &indented.flow,
+                    update,
+                    next_node_id,
+                ));
+                node
+            }
+            MarkdownContent::HorizontalLine(_horizontal_line) => return None,
+            MarkdownContent::Table(table) => {
+                table.accessibility(update, next_node_id)
+            }
+            MarkdownContent::Math(math_block) => {
+                let mut node = Node::new(Role::Math);
+                node.set_value(math_block.source());
+                node
+            }
+            MarkdownContent::Image(image_block) => {
+                let mut node = Node::new(Role::Image);
+                node.set_label(image_block.image().alt());
+                if let Some(title) = image_block.image().title() {
+                    node.set_description(title);
+                }
+                node
+            }
+            MarkdownContent::Footnote(footnote) => {
+                let mut node = Node::new(Role::Note);
+                node.set_children(flow_accessibility(
+                    &footnote.flow,
+                    update,
+                    next_node_id,
+                ));
+                node
+            }
+            MarkdownContent::DefinitionList(definition_list) => {
+                let mut node = Node::new(Role::DescriptionList);
+                for (title, definition) in definition_list.items() {
+                    let mut title_node = Node::new(Role::DescriptionListTerm);
+                    title_node.set_value(title.plain_text());
+                    let title_id = next_node_id();
+                    update.nodes.push((title_id, title_node));
+                    node.push_child(title_id);
+
+                    let mut definition_node = Node::new(Role::DescriptionListDetail);
+                    definition_node.set_children(flow_accessibility(
+                        definition,
+                        update,
+                        next_node_id,
+                    ));
+                    let definition_id = next_node_id();
+                    update.nodes.push((definition_id, definition_node));
+                    node.push_child(definition_id);
+                }
+                node
+            }
+        };
+        let id = next_node_id();
+        update.nodes.push((id, node));
+        Some(id)
+    }
+
     pub fn on_mouse_leave(&mut self, text_ctx: &mut TextContext, width: f64) {
         //match self {
         //MarkdownContent::Indented(indented) => indented.on_mouse_leave(text_ctx, width),
@@ -1094,6 +2409,19 @@ impl MarkdownContent {
             MarkdownContent::Header(header) => {
                 header.layout(ctx, width, reduce_top_margin)
             }
+            MarkdownContent::Table(table) => {
+                table.layout(ctx, width, reduce_top_margin)
+            }
+            MarkdownContent::Footnote(footnote) => {
+                footnote.layout(ctx, width, reduce_top_margin)
+            }
+            MarkdownContent::Math(math_block) => math_block.layout(ctx, width),
+            MarkdownContent::Image(image_block) => {
+                image_block.layout(ctx, width, reduce_top_margin)
+            }
+            MarkdownContent::DefinitionList(definition_list) => {
+                definition_list.layout(ctx, width, reduce_top_margin)
+            }
         }
     }
 
@@ -1127,6 +2455,27 @@ impl MarkdownContent {
                 //header.paint(scene, scene_size, ctx, element_box, brush_palete);
                 todo!()
             }
+            MarkdownContent::Table(table) => {
+                table.paint(scene, scene_size, ctx, element_box, brush_palete);
+            }
+            MarkdownContent::Footnote(footnote) => {
+                footnote.paint(scene, scene_size, ctx, element_box, brush_palete);
+            }
+            MarkdownContent::Math(math_block) => {
+                math_block.paint(scene, scene_size, ctx, element_box, brush_palete);
+            }
+            MarkdownContent::Image(image_block) => {
+                image_block.paint(scene, scene_size, element_box, brush_palete);
+            }
+            MarkdownContent::DefinitionList(definition_list) => {
+                definition_list.paint(
+                    scene,
+                    scene_size,
+                    ctx,
+                    element_box,
+                    brush_palete,
+                );
+            }
         }
     }
 
@@ -1140,6 +2489,59 @@ impl MarkdownContent {
             MarkdownContent::HorizontalLine(horizontal_line) => {
                 horizontal_line.height()
             }
+            MarkdownContent::Table(table) => table.height(),
+            MarkdownContent::Footnote(footnote) => footnote.height(),
+            MarkdownContent::Math(math_block) => math_block.height(),
+            MarkdownContent::Image(image_block) => image_block.height(),
+            MarkdownContent::DefinitionList(definition_list) => {
+                definition_list.height()
+            }
+        }
+    }
+
+    /// Width the element needs to be shown without clipping. Only code blocks
+    /// and tables can be wider than the layout width (long unbreakable lines
+    /// or cells), so the elements with nested flows measure the nested ones
+    /// with their indentation and the other elements return zero.
+    pub fn content_width(&self) -> Width {
+        // The space around the nested flows, left and right.
+        let (left, right) = match self {
+            MarkdownContent::CodeBlock(code_block) => {
+                return code_block.content_width()
+            }
+            MarkdownContent::Table(table) => return table.content_width(),
+            MarkdownContent::Indented(indented) => (
+                indented.margin.left
+                    + indented.decoration_margin.left
+                    + indented.padding.left,
+                indented.margin.right
+                    + indented.decoration_margin.right
+                    + indented.padding.right,
+            ),
+            MarkdownContent::List(markdown_list) => (
+                markdown_list.margin.left + markdown_list.indentation,
+                markdown_list.margin.right,
+            ),
+            MarkdownContent::DefinitionList(definition_list) => (
+                definition_list.margin.left + definition_list.indentation,
+                definition_list.margin.right,
+            ),
+            MarkdownContent::Footnote(footnote) => (
+                footnote.margin.left + footnote.indentation,
+                footnote.margin.right,
+            ),
+            _ => return 0.0,
+        };
+        let nested_width = self
+            .nested_flows()
+            .into_iter()
+            .flat_map(|(_, flow)| flow.iter())
+            .map(|element| element.data.content_width())
+            .fold(0.0, f64::max);
+        if nested_width > 0.0 {
+            left + nested_width + right
+        } else {
+            0.0
         }
     }
 
@@ -1154,6 +2556,31 @@ impl LayoutData for MarkdownContent {
     }
 }
 
+/// Layouts the `symbol` with the nerd font used for the decoration symbols.
+fn layout_symbol(ctx: &mut MarkdownContext, symbol: &str) -> LayoutedText {
+    let mut text: LayoutedText = symbol.to_string().into();
+    text.build_layout(ctx.layout_ctx, ctx.theme.scale, None, |builder| {
+        BrushPalete::fill_default_styles(ctx.theme, builder);
+        builder.push_default(StyleProperty::FontStack(FontStack::Single(
+            // TODO: This should be sourced from theme
+            FontFamily::Named("Symbols Nerd Font".into()),
+        )));
+    });
+    text
+}
+
+/// Adds the accessibility nodes of all elements in the `flow` to the
+/// `update`. Returns the ids of the top level nodes.
+pub fn flow_accessibility(
+    flow: &LayoutFlow<MarkdownContent>,
+    update: &mut TreeUpdate,
+    next_node_id: &mut dyn FnMut() -> NodeId,
+) -> Vec<NodeId> {
+    flow.iter()
+        .filter_map(|element| element.data.accessibility(update, next_node_id))
+        .collect()
+}
+
 // TODO: Shoul this be a part of some markdown object??
 pub fn draw_flow(
     scene: &mut Scene,
@@ -1172,7 +2599,7 @@ pub fn draw_flow(
     };
     let visible_parts = flow.get_visible_parts(offset, height);
 
-    for visible_part in visible_parts.iter() {
+    for visible_part in visible_parts {
         let element_box: Rect = Rect::new(
             position.x,
             position.y + visible_part.offset,
@@ -1228,3 +2655,65 @@ pub fn flow_on_mouse_drag(
         data.on_mouse_drag(text_ctx, width, position);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use masonry::core::BrushIndex;
+    use parley::FontContext;
+    use usvg::fontdb;
+
+    use super::{MarkdownContent, MarkdownList};
+    use crate::{
+        markdown::{
+            context::{LayoutContext, MarkdownContext, SvgContext},
+            image_cache::ImageCache,
+            parser::parse_markdown,
+        },
+        theme::Theme,
+    };
+
+    /// Returns the list nested in the first item of the `list`.
+    fn nested_list(list: &MarkdownList) -> Option<&MarkdownList> {
+        list.list[0].iter().find_map(|element| match &element.data {
+            MarkdownContent::List(list) => Some(list),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn nested_list_indentation_is_constant() {
+        let svg_ctx = SvgContext::new(
+            Arc::new(fontdb::Database::new()),
+            100.0,
+            ImageCache::new(),
+        );
+        let mut font_ctx = FontContext::new();
+        let mut parley_layout_ctx = parley::LayoutContext::<BrushIndex>::new();
+        let mut layout_ctx =
+            LayoutContext::new(&mut font_ctx, &mut parley_layout_ctx);
+        let mut theme = Theme::dark_default();
+        theme.markdown.list_nesting_indentation = 8.0;
+        let mut ctx = MarkdownContext::new(&svg_ctx, &mut layout_ctx, &theme);
+
+        let mut flow = parse_markdown("- one\n  - two\n    - three\n");
+        flow.apply_to_all(|(_, data)| {
+            data.layout(&mut ctx, 400.0, false);
+        });
+        let Some(MarkdownContent::List(first)) = flow.get(0) else {
+            panic!("The document should start with a list");
+        };
+        let second = nested_list(first).expect("second level list");
+        let third = nested_list(second).expect("third level list");
+
+        assert_eq!(first.level_indentation, 0.0);
+        assert_eq!(second.level_indentation, 8.0);
+        assert_eq!(third.level_indentation, 8.0);
+        // The markers of the same kind take the same width on every level.
+        assert_eq!(
+            third.indentation - third.level_indentation,
+            second.indentation - second.level_indentation
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! Replacement of the emoji shortcodes (e.g. `:smile:`) in the markdown text.
+//! Only the commonly used GitHub shortcodes are known, the unknown ones are
+//! kept as they are.
+
+use std::borrow::Cow;
+
+/// Returns the emoji for the `shortcode` (without the colons).
+fn emoji(shortcode: &str) -> Option<&'static str> {
+    let emoji = match shortcode {
+        "+1" | "thumbsup" | "thumbs_up" => "👍",
+        "-1" | "thumbsdown" | "thumbs_down" => "👎",
+        "100" => "💯",
+        "angry" => "😠",
+        "arrow_down" => "⬇️",
+        "arrow_left" => "⬅️",
+        "arrow_right" => "➡️",
+        "arrow_up" => "⬆️",
+        "beer" => "🍺",
+        "blush" => "😊",
+        "bomb" => "💣",
+        "book" => "📖",
+        "boom" => "💥",
+        "broken_heart" => "💔",
+        "bug" => "🐛",
+        "bulb" => "💡",
+        "calendar" => "📆",
+        "cat" => "🐱",
+        "check" | "heavy_check_mark" => "✔️",
+        "clap" => "👏",
+        "coffee" => "☕",
+        "confused" => "😕",
+        "construction" => "🚧",
+        "cool" => "🆒",
+        "cry" => "😢",
+        "dog" => "🐶",
+        "eyes" => "👀",
+        "fire" => "🔥",
+        "gear" => "⚙️",
+        "grin" => "😁",
+        "grinning" => "😀",
+        "hammer" => "🔨",
+        "heart" => "❤️",
+        "heart_eyes" => "😍",
+        "heavy_multiplication_x" => "✖️",
+        "hourglass" => "⌛",
+        "information_source" => "ℹ️",
+        "joy" => "😂",
+        "key" => "🔑",
+        "laughing" | "satisfied" => "😆",
+        "link" => "🔗",
+        "lock" => "🔒",
+        "mag" => "🔍",
+        "memo" | "pencil" => "📝",
+        "neutral_face" => "😐",
+        "no_entry" => "⛔",
+        "ok" => "🆗",
+        "ok_hand" => "👌",
+        "package" => "📦",
+        "paperclip" => "📎",
+        "party_popper" | "tada" => "🎉",
+        "point_right" => "👉",
+        "pray" => "🙏",
+        "question" => "❓",
+        "rage" => "😡",
+        "recycle" => "♻️",
+        "rocket" => "🚀",
+        "rofl" => "🤣",
+        "scream" => "😱",
+        "see_no_evil" => "🙈",
+        "shrug" => "🤷",
+        "sleeping" => "😴",
+        "slightly_smiling_face" => "🙂",
+        "smile" => "😄",
+        "smiley" => "😃",
+        "smirk" => "😏",
+        "sob" => "😭",
+        "sparkles" => "✨",
+        "star" => "⭐",
+        "stuck_out_tongue" => "😛",
+        "sunglasses" => "😎",
+        "sweat_smile" => "😅",
+        "thinking" => "🤔",
+        "trophy" => "🏆",
+        "unlock" => "🔓",
+        "warning" => "⚠️",
+        "wave" => "👋",
+        "white_check_mark" => "✅",
+        "wink" => "😉",
+        "wrench" => "🔧",
+        "x" => "❌",
+        "zap" => "⚡",
+        _ => return None,
+    };
+    Some(emoji)
+}
+
+fn is_shortcode_char(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '+' | '-')
+}
+
+/// Returns the `text` with the known shortcodes replaced by the emojis.
+pub fn replace_shortcodes(text: &str) -> Cow<'_, str> {
+    if !text.contains(':') {
+        return Cow::Borrowed(text);
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(':') {
+        let after_colon = &rest[start + 1..];
+        let Some(end) = after_colon.find(|c: char| !is_shortcode_char(c)) else {
+            break;
+        };
+        let emoji = (after_colon[end..].starts_with(':'))
+            .then(|| emoji(&after_colon[..end]))
+            .flatten();
+        match emoji {
+            Some(emoji) => {
+                result.push_str(&rest[..start]);
+                result.push_str(emoji);
+                rest = &after_colon[end + 1..];
+            }
+            None => {
+                // The character after the name may start the next shortcode.
+                result.push_str(&rest[..start + 1 + end]);
+                rest = &after_colon[end..];
+            }
+        }
+    }
+    result.push_str(rest);
+    Cow::Owned(result)
+}
+
+/// Returns the length of the shortcode which is not finished at the end of
+/// the `text` (e.g. `:thumbs` of `see :thumbs`), if there is one.
+pub fn unfinished_shortcode_len(text: &str) -> Option<usize> {
+    let start = text.rfind(':')?;
+    text[start + 1..]
+        .chars()
+        .all(is_shortcode_char)
+        .then_some(text.len() - start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{replace_shortcodes, unfinished_shortcode_len};
+
+    #[test]
+    fn replaces_known_shortcodes() {
+        assert_eq!(replace_shortcodes("hi :smile:!"), "hi 😄!");
+        assert_eq!(replace_shortcodes(":+1::tada:"), "👍🎉");
+        assert_eq!(
+            replace_shortcodes("at 12:30 :unknown: :x"),
+            "at 12:30 :unknown: :x"
+        );
+        assert_eq!(replace_shortcodes("a :b:smile: c"), "a :b😄 c");
+        assert_eq!(replace_shortcodes("ÿ:wave:ÿ"), "ÿ👋ÿ");
+    }
+
+    #[test]
+    fn unfinished_shortcode() {
+        assert_eq!(unfinished_shortcode_len("see :thumbs"), Some(7));
+        assert_eq!(unfinished_shortcode_len("see :"), Some(1));
+        assert_eq!(unfinished_shortcode_len("see :a b"), None);
+        assert_eq!(unfinished_shortcode_len("no colon"), None);
+    }
+}
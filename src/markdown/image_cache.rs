@@ -0,0 +1,92 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use eyre::Result;
+use tracing::warn;
+
+#[derive(Clone, Debug)]
+pub enum ImageState {
+    Loading,
+    Loaded(Arc<image::RgbaImage>),
+    Failed(String),
+}
+
+/// Images shared by all the markdown texts of a widget. The images are loaded
+/// on background threads so the layout doesn't wait for the disk or network.
+#[derive(Clone, Debug, Default)]
+pub struct ImageCache {
+    images: Arc<Mutex<HashMap<String, ImageState>>>,
+    // Set when some image finished loading (successfully or not) and the
+    // layout should be updated.
+    completed: Arc<AtomicBool>,
+}
+
+impl ImageCache {
+    pub fn new() -> ImageCache {
+        ImageCache::default()
+    }
+
+    /// Returns the state of the image with the `url`. When the image is not
+    /// in the cache yet, the `load` function is spawned on a new thread and
+    /// `ImageState::Loading` is returned.
+    pub fn get_or_load<F>(&self, url: &str, load: F) -> ImageState
+    where
+        F: FnOnce() -> Result<image::RgbaImage> + Send + 'static,
+    {
+        let mut images = self.images.lock().unwrap();
+        if let Some(state) = images.get(url) {
+            return state.clone();
+        }
+        images.insert(url.to_string(), ImageState::Loading);
+
+        let url = url.to_string();
+        let images = self.images.clone();
+        let completed = self.completed.clone();
+        thread::spawn(move || {
+            let state = match load() {
+                Ok(image_data) => ImageState::Loaded(Arc::new(image_data)),
+                Err(err) => {
+                    warn!("Loading image with path {url} failed with error: {err}");
+                    ImageState::Failed(err.to_string())
+                }
+            };
+            images.lock().unwrap().insert(url, state);
+            completed.store(true, Ordering::Release);
+        });
+        ImageState::Loading
+    }
+
+    /// Returns `true` when some image finished loading since the last call.
+    pub fn take_completed(&self) -> bool {
+        self.completed.swap(false, Ordering::AcqRel)
+    }
+
+    /// Returns the URLs of the images which failed to load together with the
+    /// errors.
+    pub fn errors(&self) -> Vec<(String, String)> {
+        self.images
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(url, state)| match state {
+                ImageState::Failed(err) => Some((url.clone(), err.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns `true` when there is at least one image being loaded.
+    pub fn is_loading(&self) -> bool {
+        self.images
+            .lock()
+            .unwrap()
+            .values()
+            .any(|state| matches!(state, ImageState::Loading))
+    }
+}
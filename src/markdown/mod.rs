@@ -1,71 +1,249 @@
 pub mod context;
 pub mod elements;
+pub mod emoji;
+pub mod image_cache;
 pub mod parser;
 pub mod text;
+pub mod toc;
 
 use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    ops::Range,
     path::{Path, PathBuf},
-    sync::Arc,
-    time::Instant,
+    str::FromStr,
+    sync::{
+        mpsc::{self, Receiver},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
-use accesskit::{Node, Role};
-use context::{LayoutContext, MarkdownContext, SvgContext};
-use elements::{draw_flow, MarkdownContent};
-use kurbo::{Affine, Rect, Vec2};
+use accesskit::{Node, NodeId, Role};
+use context::{LayoutContext, MarkdownContext, SvgContext, TextContext};
+use elements::{draw_flow, flow_accessibility, MarkdownContent};
+use image_cache::ImageCache;
+use kurbo::{Affine, Line, Point, Rect, Size, Stroke, Vec2};
 use masonry::core::{
     AccessCtx, EventCtx, PaintCtx, PointerEvent, PropertiesMut, PropertiesRef,
-    RegisterCtx, Widget,
+    QueryCtx, RegisterCtx, TextEvent, Update, UpdateCtx, Widget, WidgetId,
+    WidgetMut,
 };
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use parser::parse_markdown;
-use peniko::BlendMode;
+use peniko::{BlendMode, Fill};
 use smallvec::SmallVec;
-use text::styles::BrushPalete;
-use tracing::{debug, info};
+use text::{
+    layouted_text::Highlight, simple::SimpleText, styles::BrushPalete, MarkdownText,
+};
+use toc::TocEntry;
+use tracing::{debug, info, warn};
 use usvg::fontdb;
 use vello::Scene;
+use winit::{
+    keyboard::{Key, NamedKey},
+    window::CursorIcon,
+};
 use xilem::{
-    core::{Message, MessageResult, View, ViewMarker},
+    core::{
+        Message, MessageProxy, MessageResult, View, ViewMarker, ViewPathTracker,
+    },
     view::PointerButton,
     Pod, ViewCtx,
 };
 
-use crate::{layout_flow::LayoutFlow, mouse_event::Click, theme::get_theme};
+use crate::{
+    buffer::{Buffer, Encoding},
+    config::get_config,
+    generation::Generation,
+    layout_flow::LayoutFlow,
+    mouse_event::Click,
+    scene_utils::SizedScene,
+    svg_fonts::get_svg_fonts_arc,
+    theme::{get_theme, toggle_dark_light, zoom_by_key},
+};
+
+// Editors often write a file in several steps (e.g. truncate and write), so
+// the file is reloaded only after it stays unchanged for this long.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(100);
+
+// Watches the markdown file for changes done outside of the widget.
+struct FileWatcher {
+    path: PathBuf,
+    // The watching stops when the watcher is dropped.
+    _watcher: RecommendedWatcher,
+    // Only the changes of the file (and the errors) are received.
+    events: Receiver<notify::Result<notify::Event>>,
+    // Time of the last change which wasn't reloaded yet.
+    pending_change: Option<Instant>,
+}
+
+impl FileWatcher {
+    /// The `on_change` is called from the watcher thread after every change
+    /// of the file.
+    fn new(
+        path: &Path,
+        on_change: impl Fn() + Send + 'static,
+    ) -> Option<FileWatcher> {
+        let path = match path.canonicalize() {
+            Ok(path) => path,
+            Err(err) => {
+                warn!("Markdown file {path:?} can't be watched: {err}");
+                return None;
+            }
+        };
+        let (sender, events) = mpsc::channel();
+        let watched_path = path.clone();
+        let handler = move |event: notify::Result<notify::Event>| {
+            let relevant = match &event {
+                Ok(event) => {
+                    (event.kind.is_modify() || event.kind.is_create())
+                        && event.paths.iter().any(|path| *path == watched_path)
+                }
+                Err(_) => true,
+            };
+            // The sending fails only when the widget is gone.
+            if relevant && sender.send(event).is_ok() {
+                on_change();
+            }
+        };
+        let mut watcher = match notify::recommended_watcher(handler) {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                warn!("Markdown file watcher can't be created: {err}");
+                return None;
+            }
+        };
+        // The directory is watched instead of the file so the changes are
+        // registered even when an editor saves the file by replacing it.
+        let directory = path.parent()?;
+        if let Err(err) = watcher.watch(directory, RecursiveMode::NonRecursive) {
+            warn!("Markdown file {path:?} can't be watched: {err}");
+            return None;
+        }
+        Some(FileWatcher {
+            path,
+            _watcher: watcher,
+            events,
+            pending_change: None,
+        })
+    }
+
+    /// Takes the received changes. Returns `true` while some change waits
+    /// for the reload.
+    fn poll(&mut self) -> bool {
+        for event in self.events.try_iter() {
+            match event {
+                Ok(_) => self.pending_change = Some(Instant::now()),
+                Err(err) => warn!("Markdown file watcher error: {err}"),
+            }
+        }
+        self.pending_change.is_some()
+    }
+
+    /// Returns `true` when the file was changed and there was no other change
+    /// for `RELOAD_DEBOUNCE`.
+    fn should_reload(&mut self) -> bool {
+        self.poll();
+        match self.pending_change {
+            Some(time) if time.elapsed() >= RELOAD_DEBOUNCE => {
+                self.pending_change = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
 
 pub struct MarkdowWidget {
     markdown_layout: LayoutFlow<MarkdownContent>,
     max_advance: f64,
     dirty: bool,
     scroll: Vec2,
+    // The `scroll` is animated towards the target.
+    scroll_target: Vec2,
+    last_frame_time: Option<Instant>,
+    // Height of the visible part of the widget.
+    view_height: f64,
+    // Width of the widest element. It is larger than `max_advance` when some
+    // code line or table doesn't fit and the document can be scrolled
+    // horizontally.
+    content_width: f64,
+    // Distance between the pointer and the top of the scrollbar thumb while
+    // the thumb is dragged.
+    scrollbar_drag: Option<f64>,
     fontdb: Arc<fontdb::Database>,
+    // Images are loaded in the background; the layout is redone whenever some
+    // of them finishes loading.
+    image_cache: ImageCache,
     brush_palete: BrushPalete,
+    // Generation of the theme the `brush_palete` and the layout are made for.
+    theme_generation: Generation,
     primary_mouse_button_down: bool,
     last_click_time: Option<Instant>,
     click_count: u32,
+    // The link under the pointer as (element index, byte range within the
+    // element text).
+    hovered_link: Option<(usize, Range<usize>)>,
+    // Text selection as (element index, byte index within the element text).
+    // The anchor is where the selection started, the end follows the mouse.
+    selection_anchor: Option<(usize, usize)>,
+    selection_end: Option<(usize, usize)>,
+    // Only the widgets created from a file have the watcher.
+    file_watcher: Option<FileWatcher>,
+    // The search is active when there is a query. Matches are pairs of
+    // (element index, byte range within the element text).
+    search_query: Option<String>,
+    search_matches: Vec<(usize, Range<usize>)>,
+    current_match: usize,
+    // Elements whose texts got the highlights, they are cleared on the next
+    // update.
+    highlighted_elements: Vec<usize>,
+    // The anchor to scroll to once the document is laid out.
+    pending_anchor: Option<String>,
+    // The content size last reported by the `ContentSizeChanged` action.
+    reported_content_size: Size,
 }
 
 impl MarkdowWidget {
+    /// Creates the widget showing the `markdown_file`.
+    ///
+    /// The file is watched, but the widget can't wake itself from the watcher
+    /// thread, so a widget created by `new` doesn't reload the file on its
+    /// own. The owner has to call [`MarkdowWidget::poll_file_changes`] (e.g.
+    /// periodically or on its rebuilds), the file is then reloaded once it
+    /// stays unchanged for a while. To get notified about the changes use
+    /// [`MarkdowWidget::with_file_change_callback`]; [`markdown_view`] does
+    /// that and reloads the file by itself.
     pub fn new<P: AsRef<Path>>(markdown_file: P) -> Self {
-        // TODO: Ehm... unwraps...
-        let content: String =
-            String::from_utf8(std::fs::read(&markdown_file).unwrap()).unwrap();
-        let markdown_layout = parse_markdown(&content);
-        // TODO: This one should be "global".
-        let mut fontdb = fontdb::Database::default();
-        fontdb.load_system_fonts();
-
-        // TODO: Add default fonts into the package so they are always present.
-        fontdb.set_serif_family("Times New Roman");
-        fontdb.set_sans_serif_family("Arial");
-        fontdb.set_cursive_family("Comic Sans MS");
-        fontdb.set_fantasy_family("Impact");
-        fontdb.set_monospace_family("Courier New");
-
-        // FIXME: FIXME FIXME: I'm not sure about the legality of the fonts
-        // being committed in the repo. Needs to be resolved ASAP.
-        fontdb.load_fonts_dir("./fonts/");
-
-        let fontdb = Arc::new(fontdb);
+        Self::with_file_change_callback(markdown_file, || {})
+    }
+
+    /// Same as [`MarkdowWidget::new`], but the `on_file_change` is called
+    /// (from the watcher thread) whenever the file changes, so the owner can
+    /// wake the widget by [`MarkdowWidget::poll_file_changes`].
+    pub fn with_file_change_callback<P: AsRef<Path>>(
+        markdown_file: P,
+        on_file_change: impl Fn() + Send + 'static,
+    ) -> Self {
+        // A missing or unreadable file shows an empty document.
+        let content = match std::fs::read(&markdown_file) {
+            Ok(bytes) => Encoding::decode(bytes).0,
+            Err(err) => {
+                let path = markdown_file.as_ref();
+                warn!("Markdown file {path:?} can't be read: {err}");
+                String::new()
+            }
+        };
+        let mut widget = Self::from_string(&content);
+        widget.file_watcher =
+            FileWatcher::new(markdown_file.as_ref(), on_file_change);
+        widget
+    }
+
+    pub fn from_string(content: &str) -> Self {
+        let markdown_layout = parse_markdown(content);
+        let fontdb = get_svg_fonts_arc();
 
         let theme = get_theme();
         let brush_palete: BrushPalete = BrushPalete::new(&theme);
@@ -75,14 +253,659 @@ impl MarkdowWidget {
             dirty: true,
             max_advance: 0.0,
             scroll: Vec2::new(0.0, 0.0),
+            scroll_target: Vec2::new(0.0, 0.0),
+            last_frame_time: None,
+            view_height: 0.0,
+            content_width: 0.0,
+            scrollbar_drag: None,
             fontdb,
+            image_cache: ImageCache::new(),
             brush_palete,
+            theme_generation: theme.generation,
             primary_mouse_button_down: false,
             last_click_time: None,
             click_count: 0,
+            hovered_link: None,
+            selection_anchor: None,
+            selection_end: None,
+            file_watcher: None,
+            search_query: None,
+            search_matches: Vec::new(),
+            current_match: 0,
+            highlighted_elements: Vec::new(),
+            pending_anchor: None,
+            reported_content_size: Size::ZERO,
+        }
+    }
+
+    /// Replaces the whole document with the markdown `content`.
+    pub fn set_content(this: &mut WidgetMut<'_, Self>, content: &str) {
+        this.widget.replace_content(content);
+        this.ctx.request_layout();
+        this.ctx.request_accessibility_update();
+        // The new content may contain images which are not loaded yet.
+        this.ctx.request_anim_frame();
+    }
+
+    /// Same as [`MarkdowWidget::set_content`], but the scroll position is kept
+    /// (as far as the new document allows), so an edited document doesn't
+    /// jump to the top.
+    pub fn update_content(this: &mut WidgetMut<'_, Self>, content: &str) {
+        let scroll = this.widget.scroll;
+        Self::set_content(this, content);
+        // The layout clamps the scroll to the new document.
+        this.widget.scroll = scroll;
+        this.widget.scroll_target = scroll;
+    }
+
+    /// Reads the markdown file again. Does nothing for the widgets created from
+    /// a string.
+    pub fn reload(this: &mut WidgetMut<'_, Self>) {
+        if this.widget.reload_file() {
+            this.ctx.request_layout();
+            this.ctx.request_accessibility_update();
+            this.ctx.request_anim_frame();
+        }
+    }
+
+    /// Checks the changes of the watched file. The file is reloaded on the
+    /// animation frames once it stays unchanged for a while.
+    pub fn poll_file_changes(this: &mut WidgetMut<'_, Self>) {
+        if let Some(file_watcher) = &mut this.widget.file_watcher {
+            if file_watcher.poll() {
+                this.ctx.request_anim_frame();
+            }
+        }
+    }
+
+    /// Scrolls the view to the `anchor` (see [`MarkdowWidget::scroll_to_anchor`])
+    /// after the next layout, so it works before the first layout too.
+    pub fn set_anchor(this: &mut WidgetMut<'_, Self>, anchor: &str) {
+        this.widget.pending_anchor = Some(anchor.to_string());
+        this.ctx.request_layout();
+    }
+
+    /// Rebuilds the values derived from the theme when the theme was changed
+    /// (see [`crate::theme::set_theme`]).
+    pub fn sync_theme(this: &mut WidgetMut<'_, Self>) {
+        if this.widget.update_theme() {
+            this.ctx.request_layout();
+        }
+    }
+
+    /// Returns `true` when the theme was changed since the last update.
+    fn update_theme(&mut self) -> bool {
+        let theme = get_theme();
+        if theme.generation == self.theme_generation {
+            return false;
+        }
+        self.theme_generation = theme.generation;
+        self.brush_palete = BrushPalete::new(&theme);
+        self.dirty = true;
+        true
+    }
+
+    fn replace_content(&mut self, content: &str) {
+        self.markdown_layout = parse_markdown(content);
+        self.dirty = true;
+        self.scroll = Vec2::new(0.0, 0.0);
+        self.scroll_target = self.scroll;
+        self.selection_anchor = None;
+        self.selection_end = None;
+        self.hovered_link = None;
+        self.highlighted_elements.clear();
+        self.update_search();
+    }
+
+    /// Reloads the watched file. Returns `false` when there is nothing to
+    /// reload.
+    fn reload_file(&mut self) -> bool {
+        let Some(path) = self.file_watcher.as_ref().map(|w| w.path.clone()) else {
+            return false;
+        };
+        match std::fs::read(&path) {
+            Ok(bytes) => {
+                self.replace_content(&Encoding::decode(bytes).0);
+                true
+            }
+            Err(err) => {
+                warn!("Markdown file {path:?} can't be reloaded: {err}");
+                false
+            }
+        }
+    }
+
+    /// Scrolls the view to the header or footnote with the given `anchor`
+    /// (see [`MarkdownContent::has_anchor`]). Returns `false` when there is no
+    /// such element.
+    pub fn scroll_to_anchor(&mut self, anchor: &str) -> bool {
+        let offset = self
+            .markdown_layout
+            .iter()
+            .find(|element| element.data.has_anchor(anchor))
+            .map(|element| element.offset);
+        if let Some(offset) = offset {
+            self.scroll_to(offset);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Height of the whole laid out document.
+    pub fn content_height(&self) -> f64 {
+        self.markdown_layout.height()
+    }
+
+    /// Width of the widest element of the laid out document, at least the
+    /// layout width.
+    pub fn content_width(&self) -> f64 {
+        self.content_width
+    }
+
+    /// Returns the headers of the document (see [`toc::table_of_contents`]).
+    pub fn table_of_contents(&self) -> Vec<TocEntry> {
+        toc::table_of_contents(&self.markdown_layout)
+    }
+
+    /// Scrolls the view to the header of the table of contents `entry`.
+    pub fn scroll_to_toc_entry(this: &mut WidgetMut<'_, Self>, entry: &TocEntry) {
+        Self::scroll_to_offset(this, entry.offset);
+    }
+
+    /// Scrolls the view so the `offset` in the document is at the top. The
+    /// scroll is limited so the document doesn't leave the view.
+    pub fn scroll_to_offset(this: &mut WidgetMut<'_, Self>, offset: f64) {
+        this.widget.scroll_to(offset);
+        this.ctx.request_paint_only();
+    }
+
+    /// Scrolls the view to the first header with the `heading` text or anchor
+    /// (see [`elements::Header::anchor`]). Returns `false` when there is no
+    /// such header.
+    pub fn scroll_to_heading(this: &mut WidgetMut<'_, Self>, heading: &str) -> bool {
+        let entry = this
+            .widget
+            .table_of_contents()
+            .into_iter()
+            .find(|entry| entry.text == heading || entry.anchor == heading);
+        match entry {
+            Some(entry) => {
+                Self::scroll_to_toc_entry(this, &entry);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Scrolls the view so the `offset` in the document is at the top.
+    fn scroll_to(&mut self, offset: f64) {
+        self.scroll = self.clamp_scroll(Vec2::new(self.scroll.x, -offset));
+        self.scroll_target = self.scroll;
+    }
+
+    /// Returns the `scroll` limited so the document doesn't leave the view.
+    fn clamp_scroll(&self, mut scroll: Vec2) -> Vec2 {
+        scroll.x = scroll
+            .x
+            .max(-(self.content_width - self.max_advance).max(0.0))
+            .min(0.0);
+        scroll.y = scroll
+            .y
+            .max(-self.markdown_layout.height() + self.view_height)
+            .min(0.0);
+        scroll
+    }
+
+    /// Returns the scrollbar thumb in the widget coordinates or `None` when
+    /// the whole document fits into the view.
+    fn scrollbar_thumb(&self, width: f64) -> Option<Rect> {
+        let content_height = self.markdown_layout.height();
+        let max_scroll = content_height - self.view_height;
+        if max_scroll <= 0.0 {
+            return None;
+        }
+        let scrollbar_width = get_theme().markdown.scrollbar_width;
+        let thumb_height =
+            (self.view_height / content_height).min(1.0) * self.view_height;
+        // The scroll is negative when the document is moved up.
+        let thumb_y =
+            (-self.scroll.y / max_scroll) * (self.view_height - thumb_height);
+        Some(Rect::new(
+            width - scrollbar_width,
+            thumb_y,
+            width,
+            thumb_y + thumb_height,
+        ))
+    }
+
+    /// Scrolls the document so the top of the scrollbar thumb is at
+    /// `thumb_y`.
+    fn drag_scrollbar_thumb(&mut self, thumb_y: f64, thumb_height: f64) {
+        let max_scroll = self.markdown_layout.height() - self.view_height;
+        let track_height = self.view_height - thumb_height;
+        if max_scroll <= 0.0 || track_height <= 0.0 {
+            return;
+        }
+        let scroll_y = -(thumb_y / track_height) * max_scroll;
+        self.scroll = self.clamp_scroll(Vec2::new(self.scroll.x, scroll_y));
+        self.scroll_target = self.scroll;
+    }
+
+    fn paint_scrollbar(&self, scene: &mut Scene, width: f64) {
+        let Some(thumb) = self.scrollbar_thumb(width) else {
+            return;
+        };
+        let theme = get_theme();
+        SizedScene::new(scene, Size::new(width, self.view_height))
+            .draw_rounded_rect(
+                Affine::IDENTITY,
+                thumb,
+                theme.markdown.scrollbar_width / 2.0,
+                theme.markdown.scrollbar_color,
+            );
+    }
+
+    /// Moves the scroll one animation frame towards the scroll target.
+    fn animate_scroll(&mut self) {
+        const SCROLL_DECAY_FACTOR: f64 = 15.0;
+        const SCROLL_SNAP_DISTANCE: f64 = 0.5;
+        let now = Instant::now();
+        let dt = self
+            .last_frame_time
+            .map(|last| now.duration_since(last).as_secs_f64())
+            .unwrap_or(0.0);
+        self.scroll_target = self.clamp_scroll(self.scroll_target);
+        let distance = self.scroll_target - self.scroll;
+        if distance.hypot() <= SCROLL_SNAP_DISTANCE {
+            self.scroll = self.scroll_target;
+            self.last_frame_time = None;
+        } else {
+            // The step is limited so the scroll never overshoots the target.
+            self.scroll += distance * (dt * SCROLL_DECAY_FACTOR).min(1.0);
+            self.scroll = self.clamp_scroll(self.scroll);
+            self.last_frame_time = Some(now);
+        }
+    }
+
+    /// Returns all occurrences of the `query` (ASCII case insensitive) as
+    /// pairs of the element index and the byte range within the element text.
+    pub fn search(&self, query: &str) -> Vec<(usize, Range<usize>)> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+        // ASCII lowercase keeps the byte indices intact.
+        let query = query.to_ascii_lowercase();
+        let mut matches = Vec::new();
+        for (index, element) in self.markdown_layout.iter().enumerate() {
+            let Some(text) = element.data.plain_text() else {
+                continue;
+            };
+            let text = text.to_ascii_lowercase();
+            matches.extend(
+                text.match_indices(&query)
+                    .map(|(start, _)| (index, start..start + query.len())),
+            );
+        }
+        matches
+    }
+
+    fn update_search(&mut self) {
+        self.search_matches = match &self.search_query {
+            Some(query) => self.search(query),
+            None => Vec::new(),
+        };
+        self.current_match = 0;
+        self.update_highlights();
+        self.scroll_to_current_match();
+    }
+
+    fn move_to_next_match(&mut self, forward: bool) {
+        let count = self.search_matches.len();
+        if count == 0 {
+            return;
+        }
+        self.current_match = if forward {
+            (self.current_match + 1) % count
+        } else {
+            (self.current_match + count - 1) % count
+        };
+        self.update_highlights();
+        self.scroll_to_current_match();
+    }
+
+    /// Passes the selection and the search matches to the texts of the
+    /// elements. The elements paint them over their own backgrounds.
+    fn update_highlights(&mut self) {
+        for index in std::mem::take(&mut self.highlighted_elements) {
+            self.update_element_text(index, |text| {
+                text.set_selection(None);
+                text.set_highlights(Vec::new());
+            });
+        }
+        for (index, range) in self.selected_ranges() {
+            self.update_element_text(index, |text| text.set_selection(Some(range)));
+        }
+        let mut highlights: BTreeMap<usize, Vec<Highlight>> = BTreeMap::new();
+        for (i, (index, range)) in self.search_matches.iter().enumerate() {
+            highlights
+                .entry(*index)
+                .or_default()
+                .push(Highlight::new(range.clone(), i == self.current_match));
+        }
+        for (index, highlights) in highlights {
+            self.update_element_text(index, |text| text.set_highlights(highlights));
+        }
+    }
+
+    /// Runs `f` on the text of the element at `index`. The element is
+    /// remembered, so its highlights are cleared by the next update.
+    fn update_element_text<F>(&mut self, index: usize, f: F)
+    where
+        F: FnOnce(&mut MarkdownText),
+    {
+        if index >= self.markdown_layout.len() {
+            return;
+        }
+        if let Some(text) = self.markdown_layout.get_mutable(index).text_mut() {
+            f(text);
+        }
+        self.highlighted_elements.push(index);
+    }
+
+    fn scroll_to_current_match(&mut self) {
+        let Some((index, range)) =
+            self.search_matches.get(self.current_match).cloned()
+        else {
+            return;
+        };
+        let Some(element) = self.markdown_layout.get(index) else {
+            return;
+        };
+        let match_offset = element
+            .range_rects(range)
+            .first()
+            .map(|rect| rect.y0)
+            .unwrap_or(0.0);
+        let offset = self.markdown_layout.offset_of(index) + match_offset;
+        self.scroll_to(offset);
+    }
+
+    /// Handles the shortcuts with the control key. Returns `false` when the
+    /// key wasn't handled.
+    fn on_control_key(&mut self, key: &Key, shift: bool) -> bool {
+        let Key::Character(str) = key else {
+            return false;
+        };
+        match str.as_str() {
+            "c" => self.copy_selection(),
+            "f" => {
+                self.search_query = Some(String::new());
+                self.update_search();
+            }
+            "g" | "G" => self.move_to_next_match(!shift),
+            "l" | "L" if shift => toggle_dark_light(),
+            key => return zoom_by_key(key),
+        }
+        true
+    }
+
+    /// Scrolls the document by the navigation keys. Returns `false` when the
+    /// key wasn't handled.
+    fn on_navigation_key(&mut self, key: &Key, control: bool) -> bool {
+        let Key::Named(named_key) = key else {
+            return false;
+        };
+        let theme = get_theme();
+        // Approximation of the line height, the real one differs per element.
+        let line_height = theme.text.text_size as f64 * theme.scale as f64 * 1.2;
+        let page_height = self.view_height * 0.9;
+        // The scroll is negative when the document is moved up.
+        let scroll_target = match named_key {
+            NamedKey::PageDown => self.scroll_target.y - page_height,
+            NamedKey::PageUp => self.scroll_target.y + page_height,
+            NamedKey::ArrowDown => self.scroll_target.y - line_height,
+            NamedKey::ArrowUp => self.scroll_target.y + line_height,
+            NamedKey::End if control => f64::NEG_INFINITY,
+            NamedKey::Home if control => 0.0,
+            _ => return false,
+        };
+        self.scroll_target =
+            self.clamp_scroll(Vec2::new(self.scroll_target.x, scroll_target));
+        true
+    }
+
+    /// Handles the keys typed while the search is active. Returns `false`
+    /// when the key wasn't handled.
+    fn on_search_key(&mut self, key: &Key) -> bool {
+        let Some(query) = &mut self.search_query else {
+            return false;
+        };
+        match key {
+            Key::Named(NamedKey::Escape) => {
+                self.search_query = None;
+                self.update_search();
+            }
+            Key::Named(NamedKey::Enter) => self.move_to_next_match(true),
+            Key::Named(NamedKey::Backspace) => {
+                query.pop();
+                self.update_search();
+            }
+            Key::Named(NamedKey::Space) => {
+                query.push(' ');
+                self.update_search();
+            }
+            Key::Character(str) => {
+                query.push_str(str);
+                self.update_search();
+            }
+            _ => return false,
+        }
+        true
+    }
+    /// Returns URL of the link under the `point`. The `point` is in the
+    /// document coordinates.
+    fn link_at_point(&self, point: Point) -> Option<String> {
+        let (element, y) = self.markdown_layout.get_element_at_offset(point.y)?;
+        element
+            .link_at_point(Point::new(point.x, y))
+            .map(|link| link.url)
+    }
+
+    /// Toggles the task list checkbox under the `point`. The `point` is in the
+    /// document coordinates.
+    fn toggle_task_at_point(&mut self, point: Point) -> bool {
+        match self.markdown_layout.get_mut_element_at_offset(point.y) {
+            Some((mut element, y)) => {
+                element.toggle_task_at_point(Point::new(point.x, y))
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the element index and the byte index of the cursor nearest to
+    /// the `point`. The `point` is in the document coordinates.
+    fn text_position_at_point(&self, point: Point) -> Option<(usize, usize)> {
+        let (index, y) = self.markdown_layout.get_index_at_offset(point.y)?;
+        self.markdown_layout
+            .get(index)?
+            .cursor_index_at_point(Point::new(point.x, y))
+            .map(|char_index| (index, char_index))
+    }
+
+    /// Returns the indices of the selected elements together with the selected
+    /// byte range within each of them.
+    fn selected_ranges(&self) -> Vec<(usize, Range<usize>)> {
+        let (Some(anchor), Some(end)) = (self.selection_anchor, self.selection_end)
+        else {
+            return Vec::new();
+        };
+        let (start, end) = if anchor <= end {
+            (anchor, end)
+        } else {
+            (end, anchor)
+        };
+        self.markdown_layout
+            .iter()
+            .enumerate()
+            .skip(start.0)
+            .take(end.0 - start.0 + 1)
+            .filter_map(|(index, element)| {
+                let text = element.data.plain_text()?;
+                let range_start = if index == start.0 { start.1 } else { 0 };
+                let range_end = if index == end.0 { end.1 } else { text.len() };
+                (range_start < range_end).then_some((index, range_start..range_end))
+            })
+            .collect()
+    }
+
+    /// Returns the selected text. The text of each element is on its own line.
+    fn selected_text(&self) -> String {
+        self.selected_ranges()
+            .into_iter()
+            .filter_map(|(index, range)| {
+                self.markdown_layout.get(index)?.plain_text()?.get(range)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn copy_selection(&self) {
+        let text = self.selected_text();
+        if text.is_empty() {
+            return;
+        }
+        match arboard::Clipboard::new() {
+            Ok(mut clipboard) => {
+                if let Err(err) = clipboard.set_text(text) {
+                    warn!("Copying of the selection failed: {err}");
+                }
+            }
+            Err(err) => warn!("Clipboard is not available: {err}"),
+        }
+    }
+
+    /// Paints the search query and the number of matches at the bottom of the
+    /// widget while the search is active.
+    fn paint_search_bar(
+        &self,
+        scene: &mut Scene,
+        ctx: &mut MarkdownContext,
+        size: Size,
+    ) {
+        let Some(query) = &self.search_query else {
+            return;
+        };
+        let count = self.search_matches.len();
+        let position = if count == 0 {
+            0
+        } else {
+            self.current_match + 1
+        };
+        let mut text = SimpleText::new(format!("Find: {query}  {position}/{count}"));
+        let mut text_ctx = TextContext::new(ctx.svg_ctx, ctx.layout_ctx, ctx.theme);
+        text.build_layout(&mut text_ctx, None);
+        let padding = ctx.theme.markdown.code_block_margin / 2.0;
+        let y = size.height - text.height() - 2.0 * padding;
+        let bar = Rect::new(0.0, y, size.width, size.height);
+        scene.fill(
+            Fill::NonZero,
+            Affine::IDENTITY,
+            ctx.theme.background_color,
+            None,
+            &bar,
+        );
+        scene.stroke(
+            &Stroke::new(1.0),
+            Affine::IDENTITY,
+            ctx.theme.markdown.horizontal_line_color,
+            None,
+            &Line::new((0.0, y), (size.width, y)),
+        );
+        let position = Vec2::new(padding, y + padding);
+        text.draw_text(scene, &size, &position, &self.brush_palete);
+    }
+
+    /// Returns the element index and the byte range of the link under the
+    /// `point`. The `point` is in the document coordinates.
+    fn link_range_at_point(&self, point: Point) -> Option<(usize, Range<usize>)> {
+        let (index, y) = self.markdown_layout.get_index_at_offset(point.y)?;
+        self.markdown_layout
+            .get(index)?
+            .link_at_point(Point::new(point.x, y))
+            .map(|link| (index, link.index_range))
+    }
+
+    /// Underlines the hovered link.
+    fn paint_hovered_link(&self, scene: &mut Scene) {
+        let Some((index, range)) = &self.hovered_link else {
+            return;
+        };
+        let Some(element) = self.markdown_layout.get(*index) else {
+            return;
+        };
+        let theme = get_theme();
+        let offset = self.markdown_layout.offset_of(*index);
+        let transform = Affine::translate(self.scroll + Vec2::new(0.0, offset));
+        for rect in element.range_rects(range.clone()) {
+            let underline = Line::new((rect.x0, rect.y1), (rect.x1, rect.y1));
+            scene.stroke(
+                &Stroke::new(1.0),
+                transform,
+                theme.markdown.link_hover_color,
+                None,
+                &underline,
+            );
+        }
+    }
+
+    fn on_link_click(&mut self, ctx: &mut EventCtx, url: String) {
+        if let Some(anchor) = url.strip_prefix('#') {
+            if !self.scroll_to_anchor(anchor) {
+                warn!("Markdown anchor {url} was not found");
+            }
+            ctx.request_paint_only();
+        } else {
+            let local = is_local_link(&url);
+            ctx.submit_action(masonry::core::Action::Other(Box::new(LinkClicked {
+                url,
+                local,
+            })));
         }
     }
 }
+
+/// Action submitted by the [`MarkdowWidget`] when a link pointing outside of
+/// the document is clicked.
+#[derive(Clone, Debug)]
+pub struct LinkClicked {
+    pub url: String,
+    /// The link is a relative path or a `file:` URL, it has to be resolved
+    /// by the application instead of being opened in the browser.
+    pub local: bool,
+}
+
+/// Message sent to the [`MarkdownView`] by the watcher of its file.
+#[derive(Debug)]
+struct FileChanged;
+
+/// Action submitted by the [`MarkdowWidget`] on the first animation frame
+/// after the size of the laid out document changed (see
+/// [`MarkdowWidget::content_width`] and [`MarkdowWidget::content_height`]).
+#[derive(Clone, Debug)]
+pub struct ContentSizeChanged {
+    pub size: Size,
+}
+
+/// Same as [`MarkdowWidget::from_string`], parsing the markdown never fails.
+impl FromStr for MarkdowWidget {
+    type Err = Infallible;
+
+    fn from_str(content: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from_string(content))
+    }
+}
+
 impl Widget for MarkdowWidget {
     fn on_pointer_event(
         &mut self,
@@ -91,31 +914,47 @@ impl Widget for MarkdowWidget {
         event: &PointerEvent,
     ) {
         info!("event: {event:?} >>> ctx: {}", ctx.size());
-        let local_position =
-            event.local_position(ctx) + self.scroll.to_point().to_vec2();
+        // The scroll is negative when the document is moved up.
+        let local_position = event.local_position(ctx) - self.scroll;
         match event {
             PointerEvent::MouseWheel(delta, _) => {
-                const SCROLLING_SPEED: f64 = 3.0;
-                let delta =
-                    Vec2::new(delta.x * SCROLLING_SPEED, delta.y * SCROLLING_SPEED);
-                self.scroll += delta;
-                // TODO: horizontal scrolling
-                self.scroll.x = 0.0;
-                let bounding_box = ctx.bounding_rect();
-                info!("widget height: {}", bounding_box);
-                self.scroll.y = self.scroll.y.min(0.0);
-                self.scroll.y = self.scroll.y.max(
-                    -self.markdown_layout.height() + bounding_box.height()
-                        - ctx.window_origin().y,
-                );
-                info!("scrolling new scroll: {} , self.markdown_layout.height() {}, ctx.size() {}", self.scroll, self.markdown_layout.height(), ctx.size());
+                let delta = {
+                    let config = get_config();
+                    config.scroll_delta(
+                        Vec2::new(delta.x, delta.y),
+                        config.markdown_scrolling_speed,
+                    )
+                };
+                self.scroll_target = self.clamp_scroll(self.scroll_target + delta);
+                info!("scrolling new scroll target: {} , self.markdown_layout.height() {}, ctx.size() {}", self.scroll_target, self.markdown_layout.height(), ctx.size());
                 if let Some(bla) = self.markdown_layout.flow.last() {
                     info!("bla.offset: {}", bla.offset);
                 }
-                ctx.request_paint_only();
+                ctx.request_anim_frame();
                 ctx.set_handled();
             }
             PointerEvent::PointerDown(button, pointer_state) => {
+                let position = event.local_position(ctx);
+                if let Some(thumb) = self.scrollbar_thumb(ctx.size().width) {
+                    if *button == PointerButton::Primary && position.x >= thumb.x0 {
+                        // A click outside of the thumb moves the thumb center
+                        // under the pointer.
+                        let grab_offset = if thumb.contains(position) {
+                            position.y - thumb.y0
+                        } else {
+                            thumb.height() / 2.0
+                        };
+                        self.scrollbar_drag = Some(grab_offset);
+                        self.drag_scrollbar_thumb(
+                            position.y - grab_offset,
+                            thumb.height(),
+                        );
+                        ctx.capture_pointer();
+                        ctx.request_paint_only();
+                        ctx.set_handled();
+                        return;
+                    }
+                }
                 let theme = get_theme();
                 let now = Instant::now();
                 if let Some(last) = self.last_click_time.take() {
@@ -137,18 +976,63 @@ impl Widget for MarkdowWidget {
                 self.primary_mouse_button_down = true;
 
                 let click = Click::from_count(self.click_count);
+                if *button == PointerButton::Primary && click == Click::Single {
+                    if self.toggle_task_at_point(local_position) {
+                        ctx.request_paint_only();
+                        ctx.request_accessibility_update();
+                    }
+                    if let Some(url) = self.link_at_point(local_position) {
+                        self.on_link_click(ctx, url);
+                    }
+                    self.selection_anchor =
+                        self.text_position_at_point(local_position);
+                    self.selection_end = self.selection_anchor;
+                    self.update_highlights();
+                    ctx.request_paint_only();
+                }
                 ctx.request_focus();
                 ctx.capture_pointer();
                 // TODO: Check if the handled is set correctly
                 ctx.set_handled();
             }
             PointerEvent::PointerMove(pointer_state) => {
+                if let Some(grab_offset) = self.scrollbar_drag {
+                    if let Some(thumb) = self.scrollbar_thumb(ctx.size().width) {
+                        let position = event.local_position(ctx);
+                        self.drag_scrollbar_thumb(
+                            position.y - grab_offset,
+                            thumb.height(),
+                        );
+                        ctx.request_paint_only();
+                    }
+                } else if self.primary_mouse_button_down
+                    && self.selection_anchor.is_some()
+                {
+                    if let Some(end) = self.text_position_at_point(local_position) {
+                        if self.selection_end != Some(end) {
+                            self.selection_end = Some(end);
+                            self.update_highlights();
+                            ctx.request_paint_only();
+                        }
+                    }
+                }
+                let hovered_link = self.link_range_at_point(local_position);
+                if hovered_link != self.hovered_link {
+                    self.hovered_link = hovered_link;
+                    ctx.request_paint_only();
+                }
                 // TODO: Check if the handled is set correctly
                 ctx.set_handled();
             }
+            PointerEvent::PointerLeave(_) => {
+                if self.hovered_link.take().is_some() {
+                    ctx.request_paint_only();
+                }
+            }
             PointerEvent::PointerUp(button, pointer_state) => {
                 if *button == PointerButton::Primary {
-                    self.primary_mouse_button_down = true;
+                    self.primary_mouse_button_down = false;
+                    self.scrollbar_drag = None;
                 }
                 // TODO: Check if the handled is set correctly
                 ctx.set_handled();
@@ -157,10 +1041,97 @@ impl Widget for MarkdowWidget {
         }
     }
 
+    fn on_text_event(
+        &mut self,
+        ctx: &mut EventCtx,
+        _props: &mut PropertiesMut<'_>,
+        event: &TextEvent,
+    ) {
+        if let TextEvent::KeyboardKey(key_event, modifiers) = event {
+            if !key_event.state.is_pressed() {
+                return;
+            }
+            let key = &key_event.logical_key;
+            let handled = if modifiers.control_key() {
+                self.on_control_key(key, modifiers.shift_key())
+            } else {
+                self.on_search_key(key)
+            };
+            if handled || self.on_navigation_key(key, modifiers.control_key()) {
+                if self.update_theme() {
+                    // Zoomed or switched between the dark and light theme.
+                    ctx.request_layout();
+                }
+                ctx.request_paint_only();
+                // The navigation keys only move the scroll target.
+                ctx.request_anim_frame();
+                ctx.set_handled();
+            }
+        }
+    }
+
+    fn accepts_focus(&self) -> bool {
+        true
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        _props: &mut PropertiesMut<'_>,
+        event: &Update,
+    ) {
+        if let Update::WidgetAdded = event {
+            // Starts polling the images loaded by the first layout.
+            ctx.request_anim_frame();
+        }
+    }
+
+    fn on_anim_frame(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        _props: &mut PropertiesMut<'_>,
+        _interval: u64,
+    ) {
+        if let Some(file_watcher) = &mut self.file_watcher {
+            if file_watcher.should_reload() && self.reload_file() {
+                ctx.request_layout();
+                ctx.request_accessibility_update();
+            }
+        }
+        if self.image_cache.take_completed() {
+            self.dirty = true;
+            ctx.request_layout();
+        }
+        if self.scroll != self.scroll_target {
+            self.animate_scroll();
+            ctx.request_paint_only();
+        }
+        let content_size = Size::new(self.content_width(), self.content_height());
+        if !self.dirty && content_size != self.reported_content_size {
+            self.reported_content_size = content_size;
+            ctx.submit_action(masonry::core::Action::Other(Box::new(
+                ContentSizeChanged { size: content_size },
+            )));
+        }
+        // Wait until the changed file stays unchanged, keep polling the loaded
+        // images or continue the scroll animation. The layout requests the
+        // frame reporting the content size itself.
+        if self
+            .file_watcher
+            .as_ref()
+            .is_some_and(|w| w.pending_change.is_some())
+            || self.image_cache.is_loading()
+            || self.scroll != self.scroll_target
+        {
+            ctx.request_anim_frame();
+        }
+    }
+
     fn register_children(&mut self, _ctx: &mut RegisterCtx) {}
 
     fn compose(&mut self, ctx: &mut masonry::core::ComposeCtx) {
         info!("compose called: size: {}, baseline_offset: {}, window_origin: {}, layout_rect: {}", ctx.size(), ctx.baseline_offset(), ctx.window_origin(), ctx.bounding_rect());
+        self.view_height = ctx.bounding_rect().height() - ctx.window_origin().y;
     }
 
     fn layout(
@@ -170,10 +1141,15 @@ impl Widget for MarkdowWidget {
         bc: &masonry::core::BoxConstraints,
     ) -> kurbo::Size {
         let size = bc.max();
+        self.update_theme();
         let theme = &get_theme();
 
         let (font_ctx, layout_ctx) = ctx.text_contexts();
-        let svg_ctx = SvgContext::new(self.fontdb.clone());
+        let svg_ctx = SvgContext::new(
+            self.fontdb.clone(),
+            size.height,
+            self.image_cache.clone(),
+        );
         let mut layout_ctx: LayoutContext<'_> =
             LayoutContext::new(font_ctx, layout_ctx);
         let mut markdown_ctx: MarkdownContext = MarkdownContext {
@@ -186,10 +1162,28 @@ impl Widget for MarkdowWidget {
             self.markdown_layout.apply_to_all(|(i, data)| {
                 data.layout(&mut markdown_ctx, size.width, i == 0);
             });
+            self.content_width = self
+                .markdown_layout
+                .iter()
+                .map(|element| element.data.content_width())
+                .fold(size.width, f64::max);
         }
 
         self.max_advance = size.width;
         self.dirty = false;
+        // The document may have got narrower.
+        self.scroll = self.clamp_scroll(self.scroll);
+        self.scroll_target = self.clamp_scroll(self.scroll_target);
+        if let Some(anchor) = self.pending_anchor.take() {
+            if !self.scroll_to_anchor(&anchor) {
+                warn!("Markdown anchor #{anchor} was not found");
+            }
+        }
+        // The changed content size is reported on the next animation frame.
+        let content_size = Size::new(self.content_width(), self.content_height());
+        if content_size != self.reported_content_size {
+            ctx.request_anim_frame();
+        }
         info!("size: {}", size);
         size
     }
@@ -209,7 +1203,11 @@ impl Widget for MarkdowWidget {
         let size = ctx.size();
         let theme = &get_theme();
         let (font_ctx, layout_ctx) = ctx.text_contexts();
-        let svg_ctx = SvgContext::new(self.fontdb.clone());
+        let svg_ctx = SvgContext::new(
+            self.fontdb.clone(),
+            size.height,
+            self.image_cache.clone(),
+        );
         let mut layout_ctx: LayoutContext<'_> =
             LayoutContext::new(font_ctx, layout_ctx);
         let mut markdown_ctx: MarkdownContext = MarkdownContext {
@@ -217,6 +1215,13 @@ impl Widget for MarkdowWidget {
             theme,
             layout_ctx: &mut layout_ctx,
         };
+        scene.fill(
+            Fill::NonZero,
+            Affine::IDENTITY,
+            theme.background_color,
+            None,
+            &size.to_rect(),
+        );
         let element_box: Rect = Rect::from_origin_size(self.scroll.to_point(), size);
         info!("MarkdowWidget::paint::element_box: {}", element_box);
         draw_flow(
@@ -227,19 +1232,49 @@ impl Widget for MarkdowWidget {
             &self.brush_palete,
             &self.markdown_layout,
         );
+        self.paint_hovered_link(scene);
+        self.paint_scrollbar(scene, size.width);
+        self.paint_search_bar(scene, &mut markdown_ctx, size);
         scene.pop_layer();
     }
 
+    fn get_cursor(&self, _ctx: &QueryCtx, pos: Point) -> CursorIcon {
+        let pos = pos - self.scroll;
+        if self.link_range_at_point(pos).is_some() {
+            return CursorIcon::Pointer;
+        }
+        let over_text = self
+            .markdown_layout
+            .get_element_at_offset(pos.y)
+            .and_then(|(element, y)| {
+                element.char_index_at_point(Point::new(pos.x, y))
+            })
+            .is_some();
+        if over_text {
+            CursorIcon::Text
+        } else {
+            CursorIcon::Default
+        }
+    }
+
     fn accessibility_role(&self) -> accesskit::Role {
         Role::Document
     }
 
     fn accessibility(
         &mut self,
-        _ctx: &mut AccessCtx,
+        ctx: &mut AccessCtx,
         _props: &PropertiesRef<'_>,
-        _node: &mut Node,
+        node: &mut Node,
     ) {
+        let children = flow_accessibility(
+            &self.markdown_layout,
+            ctx.tree_update(),
+            &mut || NodeId::from(WidgetId::next()),
+        );
+        for child in children {
+            node.push_child(child);
+        }
     }
 
     fn children_ids(&self) -> SmallVec<[masonry::core::WidgetId; 16]> {
@@ -281,16 +1316,150 @@ impl Widget for MarkdowWidget {
 //    }
 //}
 
-pub struct MarkdownView {
+type LinkClickCallback<State, Action> =
+    Box<dyn Fn(&mut State, LinkClicked) -> Action + Send + Sync + 'static>;
+
+type LayoutChangedCallback<State, Action> =
+    Box<dyn Fn(&mut State, Size) -> Action + Send + Sync + 'static>;
+
+fn link_clicked<State, Action>(
+    on_link_click: &Option<LinkClickCallback<State, Action>>,
+    app_state: &mut State,
+    link: LinkClicked,
+) -> MessageResult<Action> {
+    if let Some(on_link_click) = on_link_click {
+        MessageResult::Action(on_link_click(app_state, link))
+    } else {
+        let url = link.url;
+        if link.local {
+            debug!("The local link {url} has no handler, it is ignored");
+        } else if !is_web_link(&url) {
+            warn!("The link {url} is not a web link, it is not opened");
+        } else if let Err(err) = open::that(&url) {
+            warn!("Opening of the link {url} failed: {err}");
+        }
+        MessageResult::Nop
+    }
+}
+
+/// Returns `true` for the links that are safe to pass to the system opener.
+fn is_web_link(url: &str) -> bool {
+    ["http://", "https://", "mailto:"].iter().any(|scheme| {
+        url.get(..scheme.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+    })
+}
+
+/// Returns `true` for the relative paths and the `file:` links.
+fn is_local_link(url: &str) -> bool {
+    match url.split_once(':') {
+        Some((scheme, _)) => {
+            scheme.eq_ignore_ascii_case("file")
+                // A Windows drive letter or a path containing a colon.
+                || scheme.len() < 2
+                || !scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        }
+        None => true,
+    }
+}
+
+fn markdown_message<State, Action>(
+    on_link_click: &Option<LinkClickCallback<State, Action>>,
+    on_layout_changed: &Option<LayoutChangedCallback<State, Action>>,
+    message: Box<dyn Message>,
+    app_state: &mut State,
+) -> MessageResult<Action, Box<dyn Message>> {
+    match message.downcast::<masonry::core::Action>() {
+        Ok(action) => match *action {
+            masonry::core::Action::Other(other) => {
+                let other = match other.downcast::<ContentSizeChanged>() {
+                    Ok(changed) => {
+                        return match on_layout_changed {
+                            Some(on_layout_changed) => MessageResult::Action(
+                                on_layout_changed(app_state, changed.size),
+                            ),
+                            None => MessageResult::Nop,
+                        };
+                    }
+                    Err(other) => other,
+                };
+                match other.downcast::<LinkClicked>() {
+                    Ok(link) => link_clicked(on_link_click, app_state, *link),
+                    Err(other) => {
+                        let action = masonry::core::Action::Other(other);
+                        tracing::error!(
+                            "Wrong action type in MarkdownView::message: {action:?}"
+                        );
+                        MessageResult::Stale(Box::new(action))
+                    }
+                }
+            }
+            action => {
+                tracing::error!(
+                    "Wrong action type in MarkdownView::message: {action:?}"
+                );
+                MessageResult::Stale(Box::new(action))
+            }
+        },
+        Err(message) => {
+            tracing::error!("Wrong message type in Button::message: {message:?}");
+            MessageResult::Stale(message)
+        }
+    }
+}
+
+pub struct MarkdownView<State, Action> {
     path: PathBuf,
+    anchor: Option<String>,
+    on_link_click: Option<LinkClickCallback<State, Action>>,
+    on_layout_changed: Option<LayoutChangedCallback<State, Action>>,
+}
+
+pub fn markdown_view<State, Action>(path: PathBuf) -> MarkdownView<State, Action> {
+    MarkdownView {
+        path,
+        anchor: None,
+        on_link_click: None,
+        on_layout_changed: None,
+    }
 }
 
-pub fn markdown_view(path: PathBuf) -> MarkdownView {
-    MarkdownView { path }
+impl<State, Action> MarkdownView<State, Action> {
+    /// Sets the callback called when a link pointing outside of the document
+    /// is clicked. Without the callback the web links are opened in the
+    /// default application (most likely browser) and the local links (see
+    /// [`LinkClicked::local`]) are ignored.
+    pub fn on_link_click(
+        mut self,
+        on_link_click: impl Fn(&mut State, LinkClicked) -> Action + Send + Sync + 'static,
+    ) -> Self {
+        self.on_link_click = Some(Box::new(on_link_click));
+        self
+    }
+
+    /// Sets the callback called with the size of the laid out document
+    /// whenever it changes, e.g. to size a surrounding container or to draw
+    /// an outer scrollbar.
+    pub fn with_on_layout_changed(
+        mut self,
+        on_layout_changed: impl Fn(&mut State, Size) -> Action + Send + Sync + 'static,
+    ) -> Self {
+        self.on_layout_changed = Some(Box::new(on_layout_changed));
+        self
+    }
+
+    /// Scrolls the document to the header or footnote with the `anchor` when
+    /// it's shown and whenever the anchor changes.
+    pub fn anchor(mut self, anchor: impl Into<String>) -> Self {
+        self.anchor = Some(anchor.into());
+        self
+    }
 }
 
-impl ViewMarker for MarkdownView {}
-impl<State, Action> View<State, Action, ViewCtx> for MarkdownView
+impl<State, Action> ViewMarker for MarkdownView<State, Action> {}
+impl<State, Action> View<State, Action, ViewCtx> for MarkdownView<State, Action>
 where
     State: 'static,
     Action: 'static,
@@ -301,19 +1470,36 @@ where
 
     fn build(&self, ctx: &mut ViewCtx) -> (Self::Element, Self::ViewState) {
         debug!("CodeView::build");
+        let proxy = MessageProxy::new(ctx.proxy(), ctx.view_path().into());
         ctx.with_leaf_action_widget(|ctx| {
-            ctx.new_pod(MarkdowWidget::new(&self.path))
+            let on_file_change = move || {
+                // The rebuild polls the change, see `MarkdownView::message`.
+                if let Err(err) = proxy.message(FileChanged) {
+                    debug!("Markdown file change not delivered: {err:?}");
+                }
+            };
+            let mut widget =
+                MarkdowWidget::with_file_change_callback(&self.path, on_file_change);
+            widget.pending_anchor = self.anchor.clone();
+            ctx.new_pod(widget)
         })
     }
 
     fn rebuild(
         &self,
-        _prev: &Self,
+        prev: &Self,
         _view_state: &mut Self::ViewState,
         _ctx: &mut ViewCtx,
-        _element: xilem::core::Mut<Self::Element>,
+        mut element: xilem::core::Mut<Self::Element>,
     ) {
         debug!("CodeView::rebuild");
+        if self.anchor != prev.anchor {
+            if let Some(anchor) = &self.anchor {
+                MarkdowWidget::set_anchor(&mut element, anchor);
+            }
+        }
+        MarkdowWidget::sync_theme(&mut element);
+        MarkdowWidget::poll_file_changes(&mut element);
     }
 
     fn teardown(
@@ -331,22 +1517,218 @@ where
         _view_state: &mut Self::ViewState,
         _id_path: &[xilem::core::ViewId],
         message: Box<dyn Message>,
-        _app_state: &mut State,
+        app_state: &mut State,
     ) -> xilem::core::MessageResult<Action, Box<dyn Message>> {
         debug!("CodeView::message");
-        match message.downcast::<masonry::core::Action>() {
-            Ok(action) => {
-                tracing::error!(
-                    "Wrong action type in CodeView::message: {action:?}"
-                );
-                MessageResult::Stale(action)
-            }
-            Err(message) => {
-                tracing::error!(
-                    "Wrong message type in Button::message: {message:?}"
-                );
-                MessageResult::Stale(message)
-            }
+        let message = match message.downcast::<FileChanged>() {
+            Ok(_) => return MessageResult::RequestRebuild,
+            Err(message) => message,
+        };
+        markdown_message(
+            &self.on_link_click,
+            &self.on_layout_changed,
+            message,
+            app_state,
+        )
+    }
+}
+
+/// The same as [`MarkdownView`] but the markdown is given as a string instead
+/// of a file.
+pub struct MarkdownStringView<State, Action> {
+    content: String,
+    on_link_click: Option<LinkClickCallback<State, Action>>,
+    on_layout_changed: Option<LayoutChangedCallback<State, Action>>,
+}
+
+pub fn markdown_view_from_string<State, Action>(
+    content: impl Into<String>,
+) -> MarkdownStringView<State, Action> {
+    MarkdownStringView {
+        content: content.into(),
+        on_link_click: None,
+        on_layout_changed: None,
+    }
+}
+
+impl<State, Action> MarkdownStringView<State, Action> {
+    /// See [`MarkdownView::on_link_click`].
+    pub fn on_link_click(
+        mut self,
+        on_link_click: impl Fn(&mut State, LinkClicked) -> Action + Send + Sync + 'static,
+    ) -> Self {
+        self.on_link_click = Some(Box::new(on_link_click));
+        self
+    }
+
+    /// See [`MarkdownView::with_on_layout_changed`].
+    pub fn with_on_layout_changed(
+        mut self,
+        on_layout_changed: impl Fn(&mut State, Size) -> Action + Send + Sync + 'static,
+    ) -> Self {
+        self.on_layout_changed = Some(Box::new(on_layout_changed));
+        self
+    }
+}
+
+impl<State, Action> ViewMarker for MarkdownStringView<State, Action> {}
+impl<State, Action> View<State, Action, ViewCtx>
+    for MarkdownStringView<State, Action>
+where
+    State: 'static,
+    Action: 'static,
+{
+    type Element = Pod<MarkdowWidget>;
+
+    type ViewState = ();
+
+    fn build(&self, ctx: &mut ViewCtx) -> (Self::Element, Self::ViewState) {
+        debug!("MarkdownStringView::build");
+        ctx.with_leaf_action_widget(|ctx| {
+            ctx.new_pod(MarkdowWidget::from_string(&self.content))
+        })
+    }
+
+    fn rebuild(
+        &self,
+        prev: &Self,
+        _view_state: &mut Self::ViewState,
+        _ctx: &mut ViewCtx,
+        mut element: xilem::core::Mut<Self::Element>,
+    ) {
+        debug!("MarkdownStringView::rebuild");
+        if prev.content != self.content {
+            MarkdowWidget::set_content(&mut element, &self.content);
+        }
+        MarkdowWidget::sync_theme(&mut element);
+    }
+
+    fn teardown(
+        &self,
+        _view_state: &mut Self::ViewState,
+        ctx: &mut ViewCtx,
+        element: xilem::core::Mut<Self::Element>,
+    ) {
+        debug!("MarkdownStringView::teardown");
+        ctx.teardown_leaf(element);
+    }
+
+    fn message(
+        &self,
+        _view_state: &mut Self::ViewState,
+        _id_path: &[xilem::core::ViewId],
+        message: Box<dyn Message>,
+        app_state: &mut State,
+    ) -> xilem::core::MessageResult<Action, Box<dyn Message>> {
+        debug!("MarkdownStringView::message");
+        markdown_message(
+            &self.on_link_click,
+            &self.on_layout_changed,
+            message,
+            app_state,
+        )
+    }
+}
+
+/// Shows the markdown of the `buffer`, so it can be used as a live preview
+/// next to a [`crate::code_widget::CodeView`] of the same buffer. The document
+/// is parsed again when the buffer changes.
+pub struct MarkdownBufferView<State, Action> {
+    buffer: Arc<Mutex<Buffer>>,
+    on_link_click: Option<LinkClickCallback<State, Action>>,
+    on_layout_changed: Option<LayoutChangedCallback<State, Action>>,
+}
+
+pub fn markdown_buffer_view<State, Action>(
+    buffer: Arc<Mutex<Buffer>>,
+) -> MarkdownBufferView<State, Action> {
+    MarkdownBufferView {
+        buffer,
+        on_link_click: None,
+        on_layout_changed: None,
+    }
+}
+
+impl<State, Action> MarkdownBufferView<State, Action> {
+    /// See [`MarkdownView::on_link_click`].
+    pub fn on_link_click(
+        mut self,
+        on_link_click: impl Fn(&mut State, LinkClicked) -> Action + Send + Sync + 'static,
+    ) -> Self {
+        self.on_link_click = Some(Box::new(on_link_click));
+        self
+    }
+
+    /// See [`MarkdownView::with_on_layout_changed`].
+    pub fn with_on_layout_changed(
+        mut self,
+        on_layout_changed: impl Fn(&mut State, Size) -> Action + Send + Sync + 'static,
+    ) -> Self {
+        self.on_layout_changed = Some(Box::new(on_layout_changed));
+        self
+    }
+}
+
+impl<State, Action> ViewMarker for MarkdownBufferView<State, Action> {}
+impl<State, Action> View<State, Action, ViewCtx>
+    for MarkdownBufferView<State, Action>
+where
+    State: 'static,
+    Action: 'static,
+{
+    type Element = Pod<MarkdowWidget>;
+
+    // Generation of the buffer the shown document was parsed from.
+    type ViewState = Generation;
+
+    fn build(&self, ctx: &mut ViewCtx) -> (Self::Element, Self::ViewState) {
+        debug!("MarkdownBufferView::build");
+        let buffer = self.buffer.lock().unwrap();
+        let (element, ()) = ctx.with_leaf_action_widget(|ctx| {
+            ctx.new_pod(MarkdowWidget::from_string(&buffer.content()))
+        });
+        (element, buffer.generation())
+    }
+
+    fn rebuild(
+        &self,
+        _prev: &Self,
+        view_state: &mut Self::ViewState,
+        _ctx: &mut ViewCtx,
+        mut element: xilem::core::Mut<Self::Element>,
+    ) {
+        debug!("MarkdownBufferView::rebuild");
+        let buffer = self.buffer.lock().unwrap();
+        if buffer.generation() != *view_state {
+            MarkdowWidget::update_content(&mut element, &buffer.content());
+            *view_state = buffer.generation();
         }
+        MarkdowWidget::sync_theme(&mut element);
+    }
+
+    fn teardown(
+        &self,
+        _view_state: &mut Self::ViewState,
+        ctx: &mut ViewCtx,
+        element: xilem::core::Mut<Self::Element>,
+    ) {
+        debug!("MarkdownBufferView::teardown");
+        ctx.teardown_leaf(element);
+    }
+
+    fn message(
+        &self,
+        _view_state: &mut Self::ViewState,
+        _id_path: &[xilem::core::ViewId],
+        message: Box<dyn Message>,
+        app_state: &mut State,
+    ) -> xilem::core::MessageResult<Action, Box<dyn Message>> {
+        debug!("MarkdownBufferView::message");
+        markdown_message(
+            &self.on_link_click,
+            &self.on_layout_changed,
+            message,
+            app_state,
+        )
     }
 }
@@ -1,35 +1,95 @@
+use std::{collections::HashMap, mem::take, ops::Range};
+
 use pulldown_cmark::{
-    BlockQuoteKind, BrokenLinkCallback, Event, HeadingLevel, Options, Parser, Tag,
-    TagEnd,
+    Alignment, BlockQuoteKind, BrokenLinkCallback, Event, HeadingLevel, LinkType,
+    Options, Parser, Tag, TagEnd,
 };
 use tracing::{error, warn};
 
 use super::{
     elements::MarkdownContent,
-    text::{
-        styles::{MarkerKind, TextMarker},
-        Link,
-    },
+    emoji::{replace_shortcodes, unfinished_shortcode_len},
+    text::styles::{BrushPalete, MarkerKind, TextMarker},
 };
 use crate::{
     layout_flow::LayoutFlow,
     markdown::{
         elements::{
-            CodeBlock, Header, HorizontalLine, IndentationDecoration, Indented,
-            ListMarker, MarkdownList, Paragraph,
+            CodeBlock, DefinitionList, Footnote, Header, HorizontalLine, ImageBlock,
+            IndentationDecoration, Indented, ListMarker, MarkdownList, MathBlock,
+            Paragraph, Table,
         },
-        text::{InlinedImage, MarkdownText},
+        text::{simple::SimpleText, InlinedImage, MarkdownText},
     },
 };
 
+/// Footnotes collected while parsing. They are appended at the end of the
+/// document.
+#[derive(Default)]
+struct Footnotes {
+    // Labels in the order the footnotes are numbered (from 1).
+    labels: Vec<String>,
+    definitions: HashMap<String, LayoutFlow<MarkdownContent>>,
+}
+
+impl Footnotes {
+    /// Returns the number of the footnote with the `label`. The footnotes are
+    /// numbered in the order of their first reference.
+    fn number(&mut self, label: &str) -> usize {
+        match self.labels.iter().position(|l| l == label) {
+            Some(index) => index + 1,
+            None => {
+                self.labels.push(label.to_string());
+                self.labels.len()
+            }
+        }
+    }
+
+    fn append_to(mut self, res: &mut LayoutFlow<MarkdownContent>) {
+        if self.definitions.is_empty() {
+            return;
+        }
+        // Definitions without any reference are numbered after the others.
+        let mut unreferenced: Vec<_> = self
+            .definitions
+            .keys()
+            .filter(|label| !self.labels.contains(*label))
+            .cloned()
+            .collect();
+        unreferenced.sort();
+        self.labels.extend(unreferenced);
+
+        res.push(MarkdownContent::HorizontalLine(HorizontalLine::new()));
+        for (index, label) in self.labels.into_iter().enumerate() {
+            if let Some(flow) = self.definitions.remove(&label) {
+                res.push(MarkdownContent::Footnote(Footnote::new(
+                    label,
+                    index + 1,
+                    flow,
+                )));
+            } else {
+                warn!("Markdown footnote {label} has no definition");
+            }
+        }
+    }
+}
+
 pub struct MarkerState {
     bold_start: usize,
     italic_start: usize,
     strikethrough_start: usize,
     markers: Vec<TextMarker>,
-    links: Vec<Link>,
     link_url: String,
     link_start: usize,
+    // Bare URLs are not turned into links inside of explicit links.
+    in_link: bool,
+    // Byte range of the plain text pushed by the consecutive text events.
+    // The text events can split URLs (e.g. at `&`) so the whole run is
+    // searched for the bare URLs.
+    plain_text_run: Range<usize>,
+    // Position of the last marker start or end or of the last inline image.
+    // The text before it is not changed by the shortcode replacement.
+    last_marker_pos: usize,
 }
 
 impl MarkerState {
@@ -39,18 +99,29 @@ impl MarkerState {
             italic_start: 0,
             strikethrough_start: 0,
             markers: Vec::new(),
-            links: Vec::new(),
             link_url: "".into(),
             link_start: 0,
+            in_link: false,
+            plain_text_run: 0..0,
+            last_marker_pos: 0,
         }
     }
 
     fn clear(&mut self) {
         self.markers.clear();
-        self.links.clear();
+        self.plain_text_run = 0..0;
+        self.last_marker_pos = 0;
     }
 
     fn process_marker(&mut self, event: &Event, text_end: usize) -> bool {
+        let processed = self.process_marker_event(event, text_end);
+        if processed {
+            self.last_marker_pos = text_end;
+        }
+        processed
+    }
+
+    fn process_marker_event(&mut self, event: &Event, text_end: usize) -> bool {
         match event {
             Event::Start(Tag::Strong) => {
                 self.bold_start = text_end;
@@ -65,13 +136,18 @@ impl MarkerState {
                 true
             }
             Event::Start(Tag::Link {
-                link_type: _,
+                link_type,
                 dest_url,
                 title: _,
                 id: _,
             }) => {
-                self.link_url = dest_url.to_string();
+                self.link_url = match link_type {
+                    LinkType::Email => format!("mailto:{dest_url}"),
+                    _ => dest_url.to_string(),
+                };
                 self.link_start = text_end;
+                self.in_link = true;
+                self.plain_text_run.end = usize::MAX;
                 true
             }
             Event::End(TagEnd::Strong) => {
@@ -84,7 +160,7 @@ impl MarkerState {
             }
             Event::End(TagEnd::Emphasis) => {
                 self.markers.push(TextMarker {
-                    start_pos: self.strikethrough_start,
+                    start_pos: self.italic_start,
                     end_pos: text_end,
                     kind: MarkerKind::Italic,
                 });
@@ -99,15 +175,122 @@ impl MarkerState {
                 true
             }
             Event::End(TagEnd::Link) => {
-                self.links.push(Link {
-                    url: self.link_url.clone(),
-                    index_range: self.link_start..text_end,
+                self.markers.push(TextMarker {
+                    start_pos: self.link_start,
+                    end_pos: text_end,
+                    kind: MarkerKind::Link {
+                        url: take(&mut self.link_url),
+                    },
                 });
+                self.in_link = false;
                 true
             }
             _ => false,
         }
     }
+
+    /// Appends the `text_bit` to the `text` with the emoji shortcodes replaced
+    /// and turns the bare URLs in it into links (GFM autolinks).
+    fn push_text(&mut self, text: &mut String, text_bit: &str) {
+        if self.in_link {
+            text.push_str(&replace_shortcodes(text_bit));
+            return;
+        }
+        if self.plain_text_run.end != text.len()
+            || !text.is_char_boundary(self.plain_text_run.start)
+        {
+            self.plain_text_run = text.len()..text.len();
+        }
+        let run_start = self.plain_text_run.start;
+
+        // The shortcode unfinished at the end of the run may continue in the
+        // `text_bit` (e.g. `:thumbs_up:` is split at `_`). It's left as it is
+        // when a marker or an image starts inside of it.
+        let unfinished_len = unfinished_shortcode_len(&text[run_start..])
+            .filter(|len| text.len() - len >= self.last_marker_pos);
+        let mut new_text = match unfinished_len {
+            Some(len) => text.split_off(text.len() - len),
+            None => String::new(),
+        };
+        new_text.push_str(text_bit);
+        text.push_str(&replace_shortcodes(&new_text));
+        self.plain_text_run.end = text.len();
+
+        // The links found in the run before are found again.
+        self.markers.retain(|marker| {
+            !matches!(marker.kind, MarkerKind::Link { .. })
+                || marker.end_pos <= run_start
+        });
+        for range in find_bare_urls(&text[run_start..]) {
+            let start_pos = run_start + range.start;
+            let end_pos = run_start + range.end;
+            let url = &text[start_pos..end_pos];
+            let url = if url.starts_with("www.") {
+                format!("http://{url}")
+            } else {
+                url.to_string()
+            };
+            self.markers.push(TextMarker {
+                start_pos,
+                end_pos,
+                kind: MarkerKind::Link { url },
+            });
+        }
+    }
+}
+
+/// Returns the byte ranges of the bare `http://`, `https://` and `www.` URLs
+/// in the `text`. The trailing punctuation and unbalanced closing parentheses
+/// are not part of the URLs.
+fn find_bare_urls(text: &str) -> Vec<Range<usize>> {
+    let mut urls = Vec::new();
+    let mut search_start = 0;
+    while search_start < text.len() {
+        let rest = &text[search_start..];
+        let Some(start) = ["https://", "http://", "www."]
+            .iter()
+            .filter_map(|prefix| rest.find(prefix).map(|i| (i, prefix.len())))
+            .min()
+            .map(|(i, prefix_len)| (search_start + i, prefix_len))
+        else {
+            break;
+        };
+        let (start, prefix_len) = start;
+        let end = text[start..]
+            .find(|c: char| c.is_whitespace() || c == '<')
+            .map_or(text.len(), |i| start + i);
+        search_start = end.max(start + prefix_len);
+
+        // URLs start at a word boundary.
+        if text[..start]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '/' || c == '.')
+        {
+            continue;
+        }
+        let mut url = &text[start..end];
+        loop {
+            let trimmed = url.trim_end_matches([
+                '.', ',', ':', ';', '!', '?', '"', '\'', '*', '_', '~',
+            ]);
+            let trimmed = if trimmed.ends_with(')')
+                && trimmed.matches(')').count() > trimmed.matches('(').count()
+            {
+                &trimmed[..trimmed.len() - 1]
+            } else {
+                trimmed
+            };
+            if trimmed.len() == url.len() {
+                break;
+            }
+            url = trimmed;
+        }
+        if url.len() > prefix_len {
+            urls.push(start..start + url.len());
+        }
+    }
+    urls
 }
 
 impl Default for MarkerState {
@@ -127,14 +310,9 @@ fn process_header_events<'a, T: BrokenLinkCallback<'a>>(
             continue;
         }
         match event {
-            Event::Text(cow_str) => text.push_str(&cow_str),
+            Event::Text(cow_str) => marker_state.push_text(&mut text, &cow_str),
             Event::End(TagEnd::Heading(_)) => {
-                let text = MarkdownText::new(
-                    text,
-                    marker_state.markers,
-                    Vec::new(),
-                    Vec::new(),
-                );
+                let text = MarkdownText::new(text, marker_state.markers, Vec::new());
                 return MarkdownContent::Header(Header::new(text, *header_level));
             }
             e => {
@@ -164,6 +342,51 @@ fn process_code_block_events<'a, T: BrokenLinkCallback<'a>>(
     panic!("Header tag parsing expects Heading end tag and none was received");
 }
 
+fn process_table_events<'a, T: BrokenLinkCallback<'a>>(
+    events: &mut Parser<'a, T>,
+    alignments: Vec<Alignment>,
+) -> MarkdownContent {
+    let mut head = Vec::new();
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut text = String::new();
+    let mut marker_state = MarkerState::new();
+    for event in events {
+        if marker_state.process_marker(&event, text.len()) {
+            continue;
+        }
+        match event {
+            Event::Text(cow_str) => marker_state.push_text(&mut text, &cow_str),
+            Event::Code(cow_str) => {
+                marker_state.markers.push(TextMarker {
+                    start_pos: text.len(),
+                    end_pos: text.len() + cow_str.len(),
+                    kind: MarkerKind::InlineCode,
+                });
+                text.push_str(&cow_str);
+            }
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            Event::Start(Tag::TableHead | Tag::TableRow | Tag::TableCell) => {}
+            Event::End(TagEnd::TableCell) => {
+                row.push(MarkdownText::new(
+                    take(&mut text),
+                    take(&mut marker_state.markers),
+                    Vec::new(),
+                ));
+            }
+            Event::End(TagEnd::TableHead) => head = take(&mut row),
+            Event::End(TagEnd::TableRow) => rows.push(take(&mut row)),
+            Event::End(TagEnd::Table) => {
+                return MarkdownContent::Table(Table::new(head, rows, alignments));
+            }
+            e => {
+                error!("Table tag parsing expects only some event but {e:?} was received")
+            }
+        }
+    }
+    panic!("Table tag parsing expects Table end tag and none was received");
+}
+
 fn discar_html_block_events<'a, T: BrokenLinkCallback<'a>>(
     events: &mut Parser<'a, T>,
 ) {
@@ -175,28 +398,137 @@ fn discar_html_block_events<'a, T: BrokenLinkCallback<'a>>(
     }
 }
 
+/// Returns the title of a definition list item. The whole title is bold.
+fn process_definition_title_events<'a, T: BrokenLinkCallback<'a>>(
+    events: &mut Parser<'a, T>,
+) -> MarkdownText {
+    let mut text = String::new();
+    let mut marker_state = MarkerState::new();
+    for event in events {
+        if marker_state.process_marker(&event, text.len()) {
+            continue;
+        }
+        match event {
+            Event::Text(cow_str) => marker_state.push_text(&mut text, &cow_str),
+            Event::Code(cow_str) => {
+                marker_state.markers.push(TextMarker {
+                    start_pos: text.len(),
+                    end_pos: text.len() + cow_str.len(),
+                    kind: MarkerKind::InlineCode,
+                });
+                text.push_str(&cow_str);
+            }
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            Event::End(TagEnd::DefinitionListTitle) => {
+                let mut markers = marker_state.markers;
+                markers.insert(
+                    0,
+                    TextMarker {
+                        start_pos: 0,
+                        end_pos: text.len(),
+                        kind: MarkerKind::Bold,
+                    },
+                );
+                return MarkdownText::new(text, markers, Vec::new());
+            }
+            e => {
+                error!("DefinitionListTitle tag parsing expects only some event but {e:?} was received")
+            }
+        }
+    }
+    panic!("DefinitionListTitle tag parsing expects DefinitionListTitle end tag and none was received");
+}
+
+fn process_definition_list_events<'a, T: BrokenLinkCallback<'a>>(
+    events: &mut Parser<'a, T>,
+    footnotes: &mut Footnotes,
+) -> MarkdownContent {
+    let mut items: Vec<(MarkdownText, LayoutFlow<MarkdownContent>)> = Vec::new();
+
+    while let Some(event) = events.next() {
+        match event {
+            Event::Start(Tag::DefinitionListTitle) => {
+                let title = process_definition_title_events(events);
+                items.push((title, LayoutFlow::new()));
+            }
+            Event::Start(Tag::DefinitionListDefinition) => {
+                let mut definition = process_events(
+                    events,
+                    Some(Event::End(TagEnd::DefinitionListDefinition)),
+                    footnotes,
+                );
+                // The title can have several definitions, they are shown
+                // one after another.
+                match items.last_mut() {
+                    Some((_, flow)) => {
+                        while !definition.is_empty() {
+                            flow.push(definition.remove(0));
+                        }
+                    }
+                    None => items.push((
+                        MarkdownText::new(String::new(), Vec::new(), Vec::new()),
+                        definition,
+                    )),
+                }
+            }
+            Event::End(TagEnd::DefinitionList) => break,
+            e => {
+                error!("DefinitionList tag parsing expects only some event but {e:?} was received")
+            }
+        }
+    }
+    MarkdownContent::DefinitionList(DefinitionList::new(items))
+}
+
+/// Returns the list items together with the checked state of the task list
+/// items (`None` for the ordinary items).
 fn process_list_events<'a, T: BrokenLinkCallback<'a>>(
     events: &mut Parser<'a, T>,
-) -> Vec<LayoutFlow<MarkdownContent>> {
+    footnotes: &mut Footnotes,
+) -> (Vec<LayoutFlow<MarkdownContent>>, Vec<Option<bool>>) {
     let mut list_elements = Vec::new();
+    let mut tasks = Vec::new();
 
     while let Some(event) = events.next() {
         println!("Event: {event:?}");
         if let Event::Start(Tag::Item) = event {
-            list_elements
-                .push(process_events(events, Some(Event::End(TagEnd::Item))));
+            let mut task = None;
+            list_elements.push(process_flow_events(
+                events,
+                Some(Event::End(TagEnd::Item)),
+                &mut task,
+                footnotes,
+            ));
+            tasks.push(task);
         } else if let Event::End(TagEnd::List(_)) = event {
             break;
         } else {
             panic!("List tag parsing expects List end tag; received {event:?}");
         }
     }
-    list_elements
+    (list_elements, tasks)
 }
 
 fn process_events<'a, T: BrokenLinkCallback<'a>>(
     events: &mut Parser<'a, T>,
     untill: Option<Event>,
+    footnotes: &mut Footnotes,
+) -> LayoutFlow<MarkdownContent> {
+    let mut task = None;
+    let res = process_flow_events(events, untill, &mut task, footnotes);
+    if task.is_some() {
+        warn!("TaskListMarker outside of a list item is ignored");
+    }
+    res
+}
+
+/// Same as `process_events`, but the checked state of the task list marker
+/// (if there is any) is stored into the `task`.
+fn process_flow_events<'a, T: BrokenLinkCallback<'a>>(
+    events: &mut Parser<'a, T>,
+    untill: Option<Event>,
+    task: &mut Option<bool>,
+    footnotes: &mut Footnotes,
 ) -> LayoutFlow<MarkdownContent> {
     let mut res = LayoutFlow::new();
 
@@ -219,13 +551,18 @@ fn process_events<'a, T: BrokenLinkCallback<'a>>(
                 Tag::Image {
                     link_type: _,
                     dest_url,
-                    title: _,
+                    title,
                     id: _,
                 } => {
-                    // TODO: Use title and alt text.
-                    let _some_text = process_image_events(events);
-                    inline_images
-                        .push(InlinedImage::new(dest_url.to_string(), text.len()));
+                    let alt = process_image_events(events);
+                    let title = (!title.is_empty()).then(|| title.to_string());
+                    inline_images.push(InlinedImage::new(
+                        dest_url.to_string(),
+                        alt,
+                        title,
+                        text.len(),
+                    ));
+                    marker_state.last_marker_pos = text.len();
                 }
                 Tag::CodeBlock(kind) => {
                     let lanauge = match kind {
@@ -240,8 +577,8 @@ fn process_events<'a, T: BrokenLinkCallback<'a>>(
                     };
                     res.push(process_code_block_events(events, lanauge));
                 }
-                Tag::Table(_alignments) => {
-                    warn!("Markdown tables not supported")
+                Tag::Table(alignments) => {
+                    res.push(process_table_events(events, alignments.clone()));
                 }
                 Tag::Paragraph => {}
                 Tag::Heading {
@@ -254,6 +591,7 @@ fn process_events<'a, T: BrokenLinkCallback<'a>>(
                     let flow = process_events(
                         events,
                         Some(Event::End(TagEnd::BlockQuote(*block_quote_kind))),
+                        footnotes,
                     );
                     let decoration = match block_quote_kind {
                         Some(BlockQuoteKind::Note) => IndentationDecoration::Note,
@@ -286,15 +624,13 @@ fn process_events<'a, T: BrokenLinkCallback<'a>>(
                                 text.clone(),
                                 marker_state.markers.clone(),
                                 inline_images.clone(),
-                                marker_state.links.clone(),
                             ),
                         )));
                         text.clear();
-                        marker_state.markers.clear();
+                        marker_state.clear();
                         inline_images.clear();
-                        marker_state.links.clear();
                     }
-                    let list = process_list_events(events);
+                    let (list, tasks) = process_list_events(events, footnotes);
                     // TODO: Think about the markers. There should be a better way to set them up
                     let marker = if let Some(list_marker) = list_marker {
                         ListMarker::Numbers {
@@ -303,24 +639,30 @@ fn process_events<'a, T: BrokenLinkCallback<'a>>(
                         }
                     } else {
                         ListMarker::Symbol {
-                            symbol: Box::new("•".to_string().into()),
+                            symbol: Box::new(
+                                SimpleText::new("•".to_string())
+                                    .with_brush(BrushPalete::INDENTATION_BRUSH),
+                            ),
                         }
                     };
-                    res.push(MarkdownContent::List(MarkdownList::new(list, marker)));
+                    res.push(MarkdownContent::List(MarkdownList::new(
+                        list, tasks, marker,
+                    )));
                 }
-                Tag::FootnoteDefinition(_cow_str) => todo!(),
-                Tag::DefinitionList => {
-                    warn!("DefinitionList in markdown is not supported!")
+                Tag::FootnoteDefinition(label) => {
+                    let flow = process_events(
+                        events,
+                        Some(Event::End(TagEnd::FootnoteDefinition)),
+                        footnotes,
+                    );
+                    footnotes.definitions.insert(label.to_string(), flow);
                 }
-                Tag::DefinitionListTitle => {
-                    warn!("DefinitionList in markdown is not supported!")
+                Tag::DefinitionList => {
+                    res.push(process_definition_list_events(events, footnotes));
                 }
-                Tag::DefinitionListDefinition => {
-                    warn!("DefinitionList in markdown is not supported!")
+                Tag::DefinitionListTitle | Tag::DefinitionListDefinition => {
+                    error!("{tag:?} outside of a DefinitionList is ignored")
                 }
-                Tag::TableHead => todo!(),
-                Tag::TableRow => todo!(),
-                Tag::TableCell => todo!(),
                 Tag::MetadataBlock(_metadata_block_kind) => {
                     warn!("MetadataBlock in markdown are not supported")
                 }
@@ -329,14 +671,33 @@ fn process_events<'a, T: BrokenLinkCallback<'a>>(
             Event::End(end_tag) => {
                 match end_tag {
                     TagEnd::Paragraph => {
-                        // TODO: Work on the links and inlined_images
-                        if !text.trim().is_empty() || !inline_images.is_empty() {
+                        // A paragraph with just an image is shown as a block
+                        // image.
+                        if text.trim().is_empty() && inline_images.len() == 1 {
+                            // The image wrapped in a link keeps the link.
+                            let link =
+                                marker_state.markers.iter().find_map(|marker| {
+                                    match &marker.kind {
+                                        MarkerKind::Link { url } => {
+                                            Some(url.clone())
+                                        }
+                                        _ => None,
+                                    }
+                                });
+                            res.push(MarkdownContent::Image(
+                                ImageBlock::new(inline_images.remove(0))
+                                    .with_link(link),
+                            ));
+                            text.clear();
+                            marker_state.clear();
+                        } else if !text.trim().is_empty()
+                            || !inline_images.is_empty()
+                        {
                             res.push(MarkdownContent::Paragraph(Paragraph::new(
                                 MarkdownText::new(
                                     text.clone(),
                                     marker_state.markers.clone(),
                                     inline_images.clone(),
-                                    marker_state.links.clone(),
                                 ),
                             )));
                             text.clear();
@@ -344,18 +705,13 @@ fn process_events<'a, T: BrokenLinkCallback<'a>>(
                             inline_images.clear();
                         }
                     }
-                    TagEnd::FootnoteDefinition => todo!(),
-                    TagEnd::Table => todo!(),
-                    TagEnd::TableHead => todo!(),
-                    TagEnd::TableRow => todo!(),
-                    TagEnd::TableCell => todo!(),
                     e => {
                         warn!("Markdown parsing unprocessed end tag: {e:?}");
                     }
                 }
             }
             Event::Text(text_bit) => {
-                text.push_str(&text_bit);
+                marker_state.push_text(&mut text, &text_bit);
             }
             Event::Code(text_bit) => {
                 // TODO: Maybe it should be a text_manager with both text and markers.
@@ -384,20 +740,55 @@ fn process_events<'a, T: BrokenLinkCallback<'a>>(
             Event::Rule => {
                 res.push(MarkdownContent::HorizontalLine(HorizontalLine::new()));
             }
-            Event::FootnoteReference(_text) => {
-                warn!("FootnoteReference in markdown is not supported!")
+            Event::FootnoteReference(label) => {
+                let number = footnotes.number(&label).to_string();
+                let range = text.len()..text.len() + number.len();
+                marker_state.markers.push(TextMarker {
+                    start_pos: range.start,
+                    end_pos: range.end,
+                    kind: MarkerKind::Superscript,
+                });
+                marker_state.markers.push(TextMarker {
+                    start_pos: range.start,
+                    end_pos: range.end,
+                    kind: MarkerKind::Link {
+                        url: format!("#fn-{label}"),
+                    },
+                });
+                text.push_str(&number);
             }
-            Event::TaskListMarker(_marker) => {
-                warn!("TaskListMarker in markdown is not supported!")
+            Event::TaskListMarker(checked) => {
+                *task = Some(checked);
             }
             Event::InlineHtml(_) => {
                 warn!("InlineHtml in markdown is not supported!")
             }
-            Event::InlineMath(_) => {
-                warn!("InlineMath in markdown is not supported!")
+            Event::InlineMath(text_bit) => {
+                marker_state.markers.push(TextMarker {
+                    start_pos: text.len(),
+                    end_pos: text.len() + text_bit.len(),
+                    kind: MarkerKind::Math,
+                });
+                text.push_str(&text_bit);
             }
-            Event::DisplayMath(_) => {
-                warn!("DisplayMath in markdown is not supported!")
+            Event::DisplayMath(text_bit) => {
+                // The display math is a block, so the text before it ends up in
+                // its own paragraph.
+                if !text.trim().is_empty() || !inline_images.is_empty() {
+                    res.push(MarkdownContent::Paragraph(Paragraph::new(
+                        MarkdownText::new(
+                            text.clone(),
+                            marker_state.markers.clone(),
+                            inline_images.clone(),
+                        ),
+                    )));
+                }
+                text.clear();
+                marker_state.clear();
+                inline_images.clear();
+                res.push(MarkdownContent::Math(MathBlock::new(
+                    text_bit.trim().to_string(),
+                )));
             }
         }
     }
@@ -410,7 +801,6 @@ fn process_events<'a, T: BrokenLinkCallback<'a>>(
                 text.clone(),
                 marker_state.markers.clone(),
                 inline_images.clone(),
-                marker_state.links.clone(),
             ),
         )));
     }
@@ -421,14 +811,20 @@ fn process_events<'a, T: BrokenLinkCallback<'a>>(
 pub fn parse_markdown(text: &str) -> LayoutFlow<MarkdownContent> {
     let mut parser = Parser::new_ext(
         text,
-        //Options::ENABLE_TABLES
-        //| Options::ENABLE_FOOTNOTES
+        Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
         //| Options::ENABLE_STRIKETHROUGH
-        Options::ENABLE_STRIKETHROUGH //| Options::ENABLE_TASKLISTS
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_MATH
+        | Options::ENABLE_DEFINITION_LIST
         | Options::ENABLE_GFM, //| Options::ENABLE_HEADING_ATTRIBUTES,
     );
 
-    process_events(&mut parser, None)
+    let mut footnotes = Footnotes::default();
+    let mut res = process_events(&mut parser, None, &mut footnotes);
+    footnotes.append_to(&mut res);
+    res
 }
 
 fn process_image_events<'a, T: BrokenLinkCallback<'a>>(
@@ -437,7 +833,7 @@ fn process_image_events<'a, T: BrokenLinkCallback<'a>>(
     let mut text = String::new();
     for event in events {
         match event {
-            Event::Text(cow_str) => text = cow_str.to_string(),
+            Event::Text(cow_str) => text.push_str(&cow_str),
             Event::End(TagEnd::Image) => return text,
             e => {
                 error!("Image tag parsing expects only Text event but {e:?} was received")
@@ -447,3 +843,156 @@ fn process_image_events<'a, T: BrokenLinkCallback<'a>>(
     error!("Image tag parsing expects Image End tag and none was received");
     String::new()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{find_bare_urls, parse_markdown};
+    use crate::markdown::{
+        elements::MarkdownContent,
+        text::{styles::MarkerKind, MarkdownText},
+    };
+
+    fn first_paragraph(markdown: &str) -> MarkdownText {
+        let flow = parse_markdown(markdown);
+        let Some(MarkdownContent::Paragraph(paragraph)) =
+            flow.iter().next().map(|element| &element.data)
+        else {
+            panic!("The markdown should start with a paragraph");
+        };
+        paragraph.text().clone()
+    }
+
+    fn links(markdown: &str) -> Vec<(String, String)> {
+        let text = first_paragraph(markdown);
+        text.links()
+            .map(|link| {
+                let linked = &text.plain_text()[link.index_range.clone()];
+                (link.url.clone(), linked.to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bare_url_is_link() {
+        let url = "https://example.com";
+        assert_eq!(
+            links("Visit https://example.com today"),
+            vec![(url.to_string(), url.to_string())]
+        );
+    }
+
+    #[test]
+    fn autolinks() {
+        assert_eq!(
+            links("<https://example.com> and <me@example.com>"),
+            vec![
+                ("https://example.com".into(), "https://example.com".into()),
+                ("mailto:me@example.com".into(), "me@example.com".into()),
+            ]
+        );
+        // The explicit links are not autolinked again.
+        assert_eq!(
+            links("[https://a.org](https://b.org)"),
+            vec![("https://b.org".into(), "https://a.org".into())]
+        );
+    }
+
+    #[test]
+    fn bare_url_boundaries() {
+        let urls = |text: &str| {
+            find_bare_urls(text)
+                .into_iter()
+                .map(|range| text[range].to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(urls("see www.example.com."), vec!["www.example.com"]);
+        assert_eq!(
+            urls("(https://en.wikipedia.org/wiki/Rust_(language))"),
+            vec!["https://en.wikipedia.org/wiki/Rust_(language)"]
+        );
+        assert_eq!(urls("xhttps://a.b and https://"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn italic_and_strikethrough_ranges() {
+        let marked = |markdown: &str| {
+            let text = first_paragraph(markdown);
+            text.markers()
+                .iter()
+                .map(|marker| {
+                    text.plain_text()[marker.start_pos..marker.end_pos].to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(marked("*italic* ~~strike~~"), vec!["italic", "strike"]);
+        assert_eq!(marked("~~strike~~ *italic*"), vec!["strike", "italic"]);
+    }
+
+    #[test]
+    fn emoji_shortcodes() {
+        let text = first_paragraph("Done :thumbs_up: **:tada:** :nope: 12:30");
+        assert_eq!(text.plain_text(), "Done 👍 🎉 :nope: 12:30");
+        // The bold marker covers the emoji.
+        let bold = &text.markers()[0];
+        assert_eq!(&text.plain_text()[bold.start_pos..bold.end_pos], "🎉");
+    }
+
+    #[test]
+    fn emoji_shortcode_split_by_marker() {
+        let text = first_paragraph("Go :thumbs_**up:** now");
+        assert_eq!(text.plain_text(), "Go :thumbs_up: now");
+        let bold = &text.markers()[0];
+        assert_eq!(&text.plain_text()[bold.start_pos..bold.end_pos], "up:");
+
+        let text = first_paragraph(":thumbs_![icon](icon.png)up:");
+        assert_eq!(text.plain_text(), ":thumbs_up:");
+    }
+
+    #[test]
+    fn standalone_image_is_block() {
+        let flow =
+            parse_markdown("![Logo](logo.png)\n\nInline ![icon](icon.png) image");
+        let elements: Vec<_> = flow.iter().map(|element| &element.data).collect();
+        let [MarkdownContent::Image(image_block), MarkdownContent::Paragraph(_)] =
+            elements[..]
+        else {
+            panic!("Expected a block image followed by a paragraph");
+        };
+        assert_eq!(image_block.image().alt(), "Logo");
+        assert_eq!(image_block.link(), None);
+    }
+
+    #[test]
+    fn linked_standalone_image_keeps_link() {
+        let flow = parse_markdown("[![Logo](logo.png)](https://example.com)");
+        let Some(MarkdownContent::Image(image_block)) =
+            flow.iter().next().map(|element| &element.data)
+        else {
+            panic!("Expected a block image");
+        };
+        assert_eq!(image_block.image().alt(), "Logo");
+        assert_eq!(image_block.link(), Some("https://example.com"));
+    }
+
+    #[test]
+    fn definition_list() {
+        let flow = parse_markdown(
+            "apple\n:   red fruit\n:   or green\n\n`orange`\n\n:   orange fruit\n",
+        );
+        let Some(MarkdownContent::DefinitionList(definition_list)) =
+            flow.iter().next().map(|element| &element.data)
+        else {
+            panic!("The markdown should start with a definition list");
+        };
+        let items = definition_list.items();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].0.plain_text(), "apple");
+        assert_eq!(items[0].1.len(), 2);
+        assert_eq!(items[1].0.plain_text(), "orange");
+        assert_eq!(items[1].1.len(), 1);
+        // The titles are bold.
+        let bold = &items[1].0.markers()[0];
+        assert!(matches!(bold.kind, MarkerKind::Bold));
+        assert_eq!(bold.start_pos..bold.end_pos, 0..6);
+    }
+}
@@ -8,7 +8,7 @@ use parley::{
 use peniko::{BlendMode, Fill, Image};
 use vello::{peniko::Color, Scene};
 
-use crate::markdown::context::LayoutContext;
+use crate::{markdown::context::LayoutContext, theme::get_theme};
 
 #[derive(Clone, Debug)]
 pub struct Brush {
@@ -58,11 +58,26 @@ impl Selection {
     }
 }
 
+/// Highlighted search match. The current match has its own color.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Highlight {
+    // Range of start and end bytes in text. (NOT the char index).
+    indices: Range<usize>,
+    current: bool,
+}
+
+impl Highlight {
+    pub fn new(indices: Range<usize>, current: bool) -> Highlight {
+        Highlight { indices, current }
+    }
+}
+
 #[derive(Clone)]
 pub struct LayoutedText {
     text: String,
     layout: Layout<BrushIndex>,
     selection: Option<Selection>,
+    highlights: Vec<Highlight>,
     cursor: Option<Cursor>,
 }
 
@@ -78,6 +93,7 @@ impl LayoutedText {
             text: str,
             layout: Layout::new(),
             selection: None,
+            highlights: Vec::new(),
             cursor: None,
         }
     }
@@ -87,6 +103,7 @@ impl LayoutedText {
             text: String::new(),
             layout: Layout::new(),
             selection: None,
+            highlights: Vec::new(),
             cursor: None,
         }
     }
@@ -95,6 +112,63 @@ impl LayoutedText {
         Cursor::from_point(&self.layout, point.x as f32, point.y as f32)
     }
 
+    /// Returns the byte index of the start of the cluster under the `point`
+    /// or `None` when the `point` is outside of the text.
+    pub fn cluster_index_at_point(&self, point: &Point) -> Option<usize> {
+        if point.x < 0.0
+            || point.y < 0.0
+            || point.x > self.full_width()
+            || point.y > self.height()
+        {
+            return None;
+        }
+        Cluster::from_point(&self.layout, point.x as f32, point.y as f32)
+            .map(|(cluster, _)| cluster.text_range().start)
+    }
+
+    /// Returns the bounding boxes of the text in the byte `range`. There is
+    /// one box per glyph run intersecting the `range`.
+    pub fn range_rects(&self, range: Range<usize>) -> Vec<Rect> {
+        let mut rects = Vec::new();
+        for line in self.layout.lines() {
+            let line_metrics = line.metrics();
+            for item in line.items() {
+                let PositionedLayoutItem::GlyphRun(glyph_run) = item else {
+                    continue;
+                };
+                let mut x = glyph_run.offset() as f64;
+                let mut start = None;
+                let mut end = x;
+                for cluster in glyph_run.run().visual_clusters() {
+                    let advance = cluster.advance() as f64;
+                    let cluster_range = cluster.text_range();
+                    if cluster_range.start >= range.start
+                        && cluster_range.end <= range.end
+                    {
+                        start.get_or_insert(x);
+                        end = x + advance;
+                    }
+                    x += advance;
+                }
+                if let Some(start) = start {
+                    rects.push(Rect::new(
+                        start,
+                        line_metrics.min_coord as f64,
+                        end,
+                        line_metrics.max_coord as f64,
+                    ));
+                }
+            }
+        }
+        rects
+    }
+
+    /// Returns the rectangles covering the selected byte range. There is one
+    /// rectangle per line.
+    pub fn selection_rects(&self, byte_range: Range<usize>) -> Vec<Rect> {
+        selection_rects(&self.layout, byte_range)
+    }
+
     pub fn set_selection(&mut self, selection: Selection) {
         self.selection = Some(selection);
     }
@@ -103,6 +177,10 @@ impl LayoutedText {
         self.selection = None;
     }
 
+    pub fn set_highlights(&mut self, highlights: Vec<Highlight>) {
+        self.highlights = highlights;
+    }
+
     pub fn is_empty(&self) -> bool {
         self.text.is_empty()
     }
@@ -123,6 +201,24 @@ impl LayoutedText {
         self.layout.full_width() as f64
     }
 
+    pub fn line_count(&self) -> usize {
+        self.layout.len()
+    }
+
+    /// Height of the first line. Zero when the text is not laid out.
+    pub fn line_height(&self) -> f64 {
+        self.layout
+            .get(0)
+            .map_or(0.0, |line| line.metrics().line_height as f64)
+    }
+
+    /// Distance of the first line baseline from the top of the text.
+    pub fn baseline_offset(&self) -> f64 {
+        self.layout
+            .get(0)
+            .map_or(0.0, |line| line.metrics().baseline as f64)
+    }
+
     pub fn build_layout<F>(
         &mut self,
         text_ctx: &mut LayoutContext,
@@ -166,6 +262,7 @@ impl LayoutedText {
             scene_size,
             position,
             &self.selection,
+            &self.highlights,
             self.cursor,
             get_image,
             brushes,
@@ -173,12 +270,15 @@ impl LayoutedText {
     }
 }
 
+/// Draws the `layout` at the `position`. The backgrounds of the `highlights`
+/// and the `selection` are filled before the glyphs so the text stays on top.
 pub fn draw_text<'a, F>(
     layout: &Layout<BrushIndex>,
     scene: &mut Scene,
     scene_size: &Size,
     position: &Vec2,
     selection: &Option<Selection>,
+    highlights: &[Highlight],
     cursor: Option<Cursor>,
     get_image: F,
     brushes: &[Brush],
@@ -186,8 +286,29 @@ pub fn draw_text<'a, F>(
     F: Fn(u64) -> Option<&'a Image>,
 {
     let transform: Affine = Affine::translate(*position);
+    let theme = get_theme();
+
+    for highlight in highlights {
+        let color = if highlight.current {
+            theme.markdown.current_search_match_color
+        } else {
+            theme.markdown.search_match_color
+        };
+        for rect in selection_rects(layout, highlight.indices.clone()) {
+            scene.fill(Fill::NonZero, transform, color, None, &rect);
+        }
+    }
 
     if let Some(selection) = selection {
+        for rect in selection_rects(layout, selection.indices.clone()) {
+            scene.fill(
+                Fill::NonZero,
+                transform,
+                theme.text.selection_color,
+                None,
+                &rect,
+            );
+        }
     }
 
     if let Some(cursor) = cursor {
@@ -294,6 +415,47 @@ pub fn draw_text<'a, F>(
     }
 }
 
+/// Returns the rectangles covering the selected byte range of the `layout`.
+/// Every line of the selection has one rectangle. The inner lines are
+/// covered up to their end.
+pub fn selection_rects<B: parley::Brush>(
+    layout: &Layout<B>,
+    byte_range: Range<usize>,
+) -> Vec<Rect> {
+    if byte_range.is_empty() {
+        return Vec::new();
+    }
+    let start =
+        Cursor::from_byte_index(layout, byte_range.start, Affinity::Downstream)
+            .geometry(layout, 0.0);
+    let end = Cursor::from_byte_index(layout, byte_range.end, Affinity::Upstream)
+        .geometry(layout, 0.0);
+
+    let mut rects = Vec::new();
+    for line in layout.lines() {
+        let metrics = line.metrics();
+        let (top, bottom) = (metrics.min_coord as f64, metrics.max_coord as f64);
+        if bottom <= start.y0 {
+            continue;
+        }
+        if top >= end.y1 {
+            break;
+        }
+        let x0 = if start.y0 >= top {
+            start.x0
+        } else {
+            metrics.offset as f64
+        };
+        let x1 = if end.y1 <= bottom {
+            end.x0
+        } else {
+            (metrics.offset + metrics.advance) as f64
+        };
+        rects.push(Rect::new(x0, top, x1, bottom));
+    }
+    rects
+}
+
 fn draw_underline(
     scene: &mut Scene,
     underline: &Decoration<BrushIndex>,
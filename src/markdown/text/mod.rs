@@ -2,27 +2,40 @@ pub mod layouted_text;
 pub mod simple;
 pub mod styles;
 
-use std::{cmp::Ordering, f64, fmt, fs, ops::Range, path::Path};
+use std::{f64, fmt, fs, ops::Range, path::Path};
 
-use kurbo::{Point, Size, Vec2};
-use layouted_text::LayoutedText;
+use base64::Engine;
+use eyre::{eyre, Result};
+use kurbo::{Point, Rect, Size, Vec2};
+use layouted_text::{Highlight, LayoutedText, Selection};
 use masonry::core::BrushIndex;
-use parley::{InlineBox, StyleProperty};
+use parley::{Alignment, FontStyle, InlineBox, StyleProperty};
 use peniko::{Image, ImageFormat};
-use styles::{BrushPalete, TextMarker};
+use styles::{BrushPalete, MarkerKind, TextMarker};
 use tracing::info;
 use vello::Scene;
 
-use super::context::{SvgContext, TextContext};
-use crate::{basic_types::Height, mouse_event::Click};
+use super::{
+    context::{SvgContext, TextContext},
+    image_cache::ImageState,
+};
+use crate::{
+    basic_types::{Height, Width},
+    generation::Generation,
+    mouse_event::Click,
+};
 
 #[derive(Clone)]
 pub struct MarkdownText {
     text: LayoutedText,
     markers: Vec<TextMarker>,
     inlined_images: Vec<InlinedImage>,
-    links: Vec<Link>,
+    // Index of the hovered link marker.
     hovered_link: Option<usize>,
+    // Width and theme generation of the last layout. The layout is rebuilt
+    // only when they change. The width is NaN when the layout isn't valid.
+    layout_width: f64,
+    layout_theme_generation: Generation,
 }
 
 #[derive(Clone)]
@@ -37,21 +50,111 @@ impl Link {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct InlinedImage {
     url: String,
+    alt: String,
+    title: Option<String>,
     data: Option<Image>,
     text_index: usize,
+    // Bigger images are scaled down (keeping the aspect ratio). The default
+    // is the width of the text and twice the height of the viewport.
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+    // The `data` holds only a placeholder until the image is loaded.
+    loading: bool,
+    // The width limit the loaded image was scaled down to. The image is scaled
+    // again from the cached original when the limit grows.
+    scaled_down_to: Option<u32>,
+    // Range of the alt text inserted into the text after the box of the image
+    // which failed to load.
+    alt_range: Option<Range<usize>>,
 }
 
 impl InlinedImage {
-    pub fn new(url: String, text_index: usize) -> Self {
+    pub fn new(
+        url: String,
+        alt: String,
+        title: Option<String>,
+        text_index: usize,
+    ) -> Self {
         Self {
             url,
+            alt,
+            title,
             text_index,
             data: None,
+            max_width: None,
+            max_height: None,
+            loading: false,
+            scaled_down_to: None,
+            alt_range: None,
         }
     }
+
+    pub fn alt(&self) -> &str {
+        &self.alt
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Returns the loaded image, the placeholder of the image being loaded or
+    /// the placeholder of the broken image.
+    pub fn data(&self) -> Option<&Image> {
+        self.data.as_ref()
+    }
+
+    /// Starts loading the image or picks up the loaded one. Until the image is
+    /// loaded the `data` holds a placeholder. Returns `true` when loading the
+    /// image failed (only once, the broken placeholder is kept afterwards).
+    pub fn load(&mut self, svg_context: &SvgContext, width: f64) -> bool {
+        let max_width = self.max_width.unwrap_or(width as u32);
+        let wider = self.scaled_down_to.is_some_and(|scaled| max_width > scaled);
+        if self.data.is_some() && !self.loading && !wider {
+            return false;
+        }
+        let url = self.url.clone();
+        let thread_svg_context = svg_context.clone();
+        let state = svg_context
+            .image_cache
+            .get_or_load(&self.url, move || load_image(&url, &thread_svg_context));
+        self.loading = matches!(state, ImageState::Loading);
+        let mut failed = false;
+        let image_data = match state {
+            ImageState::Loaded(image_data) => {
+                let max_height = self
+                    .max_height
+                    .unwrap_or((svg_context.viewport_height * 2.0) as u32);
+                let scaled =
+                    scale_down((*image_data).clone(), max_width, max_height);
+                self.scaled_down_to = (scaled.dimensions()
+                    != image_data.dimensions())
+                .then_some(max_width);
+                scaled
+            }
+            ImageState::Loading => {
+                if self.data.is_some() {
+                    return false;
+                }
+                loading_placeholder()
+            }
+            ImageState::Failed(_) => {
+                failed = true;
+                error_placeholder()
+            }
+        };
+
+        let (width, height) = image_data.dimensions();
+        self.data = Some(Image::new(
+            image_data.to_vec().into(),
+            ImageFormat::Rgba8,
+            width,
+            height,
+        ));
+        failed
+    }
 }
 
 impl fmt::Debug for MarkdownText {
@@ -65,22 +168,202 @@ enum ImageType {
     Rasterized(image::ImageFormat),
 }
 
+fn image_type_from_mime(mime_type: &str) -> Result<ImageType> {
+    if mime_type == "image/svg+xml" {
+        Ok(ImageType::Svg)
+    } else {
+        Ok(ImageType::Rasterized(
+            image::ImageFormat::from_mime_type(mime_type)
+                .ok_or_else(|| eyre!("Unknown image mime type {mime_type}"))?,
+        ))
+    }
+}
+
+/// Decodes the `%XX` escapes. Invalid escapes are kept as they are.
+fn percent_decode(str: &str) -> Vec<u8> {
+    let bytes = str.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| str.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// Decodes the image embedded in the `data:<mime>[;base64],<payload>` URI.
+/// Payloads without the `base64` parameter are URL encoded.
+fn decode_data_uri(url: &str) -> Result<(Vec<u8>, ImageType)> {
+    let (header, payload) = url
+        .strip_prefix("data:")
+        .and_then(|uri| uri.split_once(','))
+        .ok_or_else(|| eyre!("The data URI has no payload"))?;
+    let mut parameters = header.split(';');
+    let mime_type = parameters.next().unwrap_or_default();
+    let raw_data = if parameters.any(|parameter| parameter == "base64") {
+        base64::engine::general_purpose::STANDARD.decode(payload)?
+    } else {
+        percent_decode(payload)
+    };
+    Ok((raw_data, image_type_from_mime(mime_type)?))
+}
+
+/// Loads the image from a data URI, a local file or from the network.
+fn load_image(url: &str, svg_context: &SvgContext) -> Result<image::RgbaImage> {
+    let (raw_data, image_type) = if url.starts_with("data:") {
+        decode_data_uri(url)?
+    } else if !url.contains("://") {
+        // This conditions most likely means it is a local file link.
+        let path: &Path = url.as_ref();
+        let buf = fs::read(url)?;
+        let extension = path
+            .extension()
+            .ok_or_else(|| eyre!("The image has no file extension"))?;
+        let image_type = if extension.eq_ignore_ascii_case("svg") {
+            ImageType::Svg
+        } else {
+            ImageType::Rasterized(
+                image::ImageFormat::from_extension(extension)
+                    .ok_or_else(|| eyre!("Unknown image extension {extension:?}"))?,
+            )
+        };
+        (buf, image_type)
+    } else {
+        let mut response = ureq::get(url).call()?;
+        let mime_type = response
+            .body()
+            .mime_type()
+            .ok_or_else(|| eyre!("The response has no mime type"))?;
+        let image_type = image_type_from_mime(mime_type)?;
+        let buf = response.body_mut().read_to_vec()?;
+        (buf, image_type)
+    };
+
+    let image_data = match image_type {
+        ImageType::Svg => {
+            let svg_str = String::from_utf8(raw_data)?;
+            svg_context.render_svg_str(&svg_str)?
+        }
+        ImageType::Rasterized(format) => {
+            match image::load_from_memory_with_format(&raw_data, format) {
+                Ok(image) => image.to_rgba8(),
+                // Try to fallback to automatic format recognition.
+                Err(_) => image::load_from_memory(&raw_data)?.to_rgba8(),
+            }
+        }
+    };
+    Ok(image_data)
+}
+
+/// Returns a grey square shown in place of the images which are still being
+/// loaded.
+fn loading_placeholder() -> image::RgbaImage {
+    image::RgbaImage::from_pixel(64, 64, image::Rgba([0x80, 0x80, 0x80, 0xFF]))
+}
+
+/// Returns a grey square with a red cross shown in place of the images which
+/// failed to load.
+fn error_placeholder() -> image::RgbaImage {
+    const SIZE: u32 = 64;
+    image::RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+        // Both diagonals, three pixels thick.
+        if x.abs_diff(y) <= 1 || (x + y).abs_diff(SIZE - 1) <= 1 {
+            image::Rgba([0xD0, 0x20, 0x20, 0xFF])
+        } else {
+            image::Rgba([0x80, 0x80, 0x80, 0xFF])
+        }
+    })
+}
+
+/// Scales the image down to fit into `max_width` x `max_height` while keeping
+/// the aspect ratio. Smaller images are returned untouched.
+fn scale_down(
+    image_data: image::RgbaImage,
+    max_width: u32,
+    max_height: u32,
+) -> image::RgbaImage {
+    let (width, height) = image_data.dimensions();
+    if width <= max_width && height <= max_height {
+        return image_data;
+    }
+    let scale = f64::min(
+        max_width as f64 / width as f64,
+        max_height as f64 / height as f64,
+    );
+    let new_width = ((width as f64 * scale) as u32).max(1);
+    let new_height = ((height as f64 * scale) as u32).max(1);
+    image::imageops::resize(
+        &image_data,
+        new_width,
+        new_height,
+        image::imageops::FilterType::Lanczos3,
+    )
+}
+
 impl MarkdownText {
     pub fn new(
         str: String,
         markers: Vec<TextMarker>,
         inlined_images: Vec<InlinedImage>,
-        links: Vec<Link>,
     ) -> Self {
         Self {
             text: LayoutedText::new(str),
             markers,
             inlined_images,
-            links,
             hovered_link: None,
+            layout_width: f64::NAN,
+            layout_theme_generation: Generation::default(),
+        }
+    }
+
+    /// Replaces the text. The markers and the images are dropped as their
+    /// positions don't apply to the new text.
+    pub fn set_text(&mut self, text: &str) {
+        self.text.set_text(text);
+        self.markers.clear();
+        self.inlined_images.clear();
+        self.hovered_link = None;
+        self.invalidate_layout();
+    }
+
+    /// Selects the byte `range`, `None` removes the selection.
+    pub fn set_selection(&mut self, range: Option<Range<usize>>) {
+        match range {
+            Some(range) => self.text.set_selection(Selection::new(range)),
+            None => self.text.remove_selection(),
         }
     }
 
+    /// Highlights the search matches. They are painted by `draw_text`, so
+    /// no new layout is needed.
+    pub fn set_highlights(&mut self, highlights: Vec<Highlight>) {
+        self.text.set_highlights(highlights);
+    }
+
+    fn invalidate_layout(&mut self) {
+        self.layout_width = f64::NAN;
+    }
+
+    /// Returns `true` when the layout was built for the `width` and the theme
+    /// generation and no image is still loading.
+    fn is_layout_valid(&self, width: f64, theme_generation: Generation) -> bool {
+        width == self.layout_width
+            && theme_generation == self.layout_theme_generation
+            && !self.inlined_images.iter().any(|image| image.loading)
+    }
+
     pub fn on_mouse_click(
         &mut self,
         text_ctx: &mut TextContext,
@@ -101,21 +384,14 @@ impl MarkdownText {
         info!("on mouse move text");
         let cursor = self.text.cursor_position(&point);
         let index = cursor.index();
-        let hovered_link = self
-            .links
-            .binary_search_by(|v| {
-                // TODO: This comparison should probably use epsilon
-                if v.index_range.start <= index && v.index_range.end >= index {
-                    Ordering::Equal
-                } else if v.index_range.start < index {
-                    Ordering::Less
-                } else {
-                    Ordering::Greater
-                }
-            })
-            .ok();
+        let hovered_link = self.markers.iter().position(|marker| {
+            matches!(marker.kind, MarkerKind::Link { .. })
+                && marker.start_pos <= index
+                && marker.end_pos >= index
+        });
 
         if self.hovered_link != hovered_link {
+            self.hovered_link = hovered_link;
             self.build_layout(text_ctx, extra_default_styles, extra_styles, width);
         }
     }
@@ -134,93 +410,121 @@ impl MarkdownText {
         }
     }
 
-    fn load_images(&mut self, svg_context: &SvgContext) {
-        for inlined_image in self.inlined_images.iter_mut() {
-            if inlined_image.data.is_none() {
-                // TODO: Do something about unwraps
-                // Maybe show broken link image or something and add something
-                // to some error feed???
-                // TODO: Add some cache and make image loading asynchronous.
-
-                // This conditions most likely means it is a local file link.
-                let (raw_data, image_type) = if !inlined_image.url.contains("://") {
-                    let path: &Path = inlined_image.url.as_ref();
-                    let buf = fs::read(&inlined_image.url).unwrap();
-                    let extension = path.extension().unwrap();
-                    let image_type = if extension.eq_ignore_ascii_case("svg") {
-                        ImageType::Svg
-                    } else {
-                        ImageType::Rasterized(
-                            image::ImageFormat::from_extension(extension).unwrap(),
-                        )
-                    };
-                    (buf, image_type)
-                } else {
-                    let mut response = ureq::get(&inlined_image.url).call().unwrap();
-                    let mime_type = response.body().mime_type().unwrap();
-                    let image_type = if mime_type == "image/svg+xml" {
-                        ImageType::Svg
-                    } else {
-                        ImageType::Rasterized(
-                            image::ImageFormat::from_mime_type(mime_type).unwrap(),
-                        )
-                    };
-                    let buf = response.body_mut().read_to_vec().unwrap();
-                    (buf, image_type)
-                };
-
-                let image_data: image::ImageBuffer<image::Rgba<u8>, Vec<u8>> =
-                    match image_type {
-                        ImageType::Svg => {
-                            let svg_str = String::from_utf8(raw_data).unwrap();
-                            let options = usvg::Options {
-                                fontdb: svg_context.fontdb.clone(),
-                                ..usvg::Options::default()
-                            };
-
-                            let svg_tree =
-                                usvg::Tree::from_str(&svg_str, &options).unwrap();
-                            let width = svg_tree.size().width().ceil() as u32;
-                            let height = svg_tree.size().height().ceil() as u32;
-                            let mut pixmap =
-                                tiny_skia::Pixmap::new(width, height).unwrap();
-                            resvg::render(
-                                &svg_tree,
-                                tiny_skia::Transform::identity(),
-                                &mut pixmap.as_mut(),
-                            );
-                            image::ImageBuffer::from_raw(
-                                width,
-                                height,
-                                pixmap.take(),
-                            )
-                            .unwrap()
-                        }
-                        ImageType::Rasterized(format) => {
-                            match image::load_from_memory_with_format(
-                                &raw_data, format,
-                            ) {
-                                Ok(image) => image.to_rgba8(),
-                                Err(_) => {
-                                    // Try to fallback to automatic format recognition.
-                                    image::load_from_memory(&raw_data)
-                                        .unwrap_or_else(
-                                        |err| {
-                                            panic!("ERROR: Loading image with path {} failed with error: {}", inlined_image.url, err)
-                                        }).to_rgba8()
-                                }
-                            }
-                        }
-                    };
-
-                let (width, height) = image_data.dimensions();
-                inlined_image.data = Some(Image::new(
-                    image_data.to_vec().into(),
-                    ImageFormat::Rgba8,
-                    width,
-                    height,
-                ));
+    /// Returns the byte index into the text under the `point` or `None` when
+    /// the `point` is outside of the text.
+    pub fn char_index_at_point(&self, point: Point) -> Option<usize> {
+        self.text.cluster_index_at_point(&point)
+    }
+
+    /// Returns the byte index of the cursor nearest to the `point`. Unlike
+    /// `char_index_at_point` this works also for points outside of the text.
+    pub fn cursor_index_at_point(&self, point: Point) -> usize {
+        self.text.cursor_position(&point).index()
+    }
+
+    pub fn link_at_point(&self, point: Point) -> Option<Link> {
+        let index = self.char_index_at_point(point)?;
+        self.links().find(|link| link.index_range.contains(&index))
+    }
+
+    /// Returns the bounding boxes of the text in the byte `range`.
+    pub fn range_rects(&self, range: Range<usize>) -> Vec<Rect> {
+        self.text.range_rects(range)
+    }
+
+    pub fn plain_text(&self) -> &str {
+        self.text.text()
+    }
+
+    pub fn markers(&self) -> &[TextMarker] {
+        &self.markers
+    }
+
+    /// Returns the links of the text (the link markers).
+    pub fn links(&self) -> impl Iterator<Item = Link> + '_ {
+        self.markers.iter().filter_map(|marker| match &marker.kind {
+            MarkerKind::Link { url } => {
+                Some(Link::new(url.clone(), marker.start_pos..marker.end_pos))
             }
+            _ => None,
+        })
+    }
+
+    pub fn has_link(&self, url: &str) -> bool {
+        self.links().any(|link| link.url == url)
+    }
+
+    /// Appends the `text` linking to the `url` separated by a space.
+    pub fn push_link(&mut self, text: &str, url: String) {
+        let mut new_text = self.text.text().to_string();
+        if !new_text.is_empty() {
+            new_text.push(' ');
+        }
+        let start = new_text.len();
+        new_text.push_str(text);
+        self.markers.push(TextMarker {
+            start_pos: start,
+            end_pos: new_text.len(),
+            kind: MarkerKind::Link { url },
+        });
+        self.text.set_text(&new_text);
+        self.invalidate_layout();
+    }
+
+    /// Inserts the alt text of the image at `image_index` into the text at the
+    /// position of the image. The ranges of the markers and following images
+    /// are shifted accordingly.
+    fn insert_alt_text(&mut self, image_index: usize) {
+        let index = self.inlined_images[image_index].text_index;
+        let alt = self.inlined_images[image_index].alt.clone();
+        let shift = alt.len();
+
+        let mut new_text = self.text.text().to_string();
+        new_text.insert_str(index, &alt);
+        self.text.set_text(&new_text);
+
+        for marker in self.markers.iter_mut() {
+            if marker.start_pos >= index {
+                marker.start_pos += shift;
+            }
+            if marker.end_pos > index {
+                marker.end_pos += shift;
+            }
+        }
+        for (i, inlined_image) in self.inlined_images.iter_mut().enumerate() {
+            if inlined_image.text_index > index
+                || (inlined_image.text_index == index && i > image_index)
+            {
+                inlined_image.text_index += shift;
+            }
+            if let Some(alt_range) = &mut inlined_image.alt_range {
+                if alt_range.start >= index {
+                    *alt_range = alt_range.start + shift..alt_range.end + shift;
+                }
+            }
+        }
+        self.inlined_images[image_index].alt_range = Some(index..index + shift);
+    }
+
+    fn load_images(&mut self, svg_context: &SvgContext, width: f64) {
+        let mut failed_with_alt = Vec::new();
+        for (image_index, inlined_image) in
+            self.inlined_images.iter_mut().enumerate()
+        {
+            if inlined_image.alt_range.is_some() {
+                continue;
+            }
+            // The broken image box has the size of the loading placeholder so
+            // the surrounding text doesn't jump. The alt text is shown next to
+            // it.
+            if inlined_image.load(svg_context, width)
+                && !inlined_image.alt.is_empty()
+            {
+                failed_with_alt.push(image_index);
+            }
+        }
+        for image_index in failed_with_alt {
+            self.insert_alt_text(image_index);
         }
     }
 
@@ -243,18 +547,37 @@ impl MarkdownText {
                 for marker in self.markers.iter() {
                     marker.feed_to_builder(builder, text_ctx.theme);
                 }
-                for (extra_style, range) in extra_styles {
-                    builder.push(extra_style.clone(), range.clone());
-                }
-                for link in self.links.iter() {
+                if let Some(link) =
+                    self.hovered_link.and_then(|index| self.markers.get(index))
+                {
+                    let range = link.start_pos..link.end_pos;
+                    builder.push(
+                        StyleProperty::Brush(BrushPalete::LINK_HOVER_BRUSH),
+                        range.clone(),
+                    );
                     builder.push(
-                        StyleProperty::Brush(BrushPalete::LINK_COLOR),
-                        link.index_range.clone(),
+                        StyleProperty::UnderlineBrush(Some(
+                            BrushPalete::LINK_HOVER_BRUSH,
+                        )),
+                        range,
                     );
                 }
+                for (extra_style, range) in extra_styles {
+                    builder.push(extra_style.clone(), range.clone());
+                }
                 for (image_index, inlined_image) in
                     self.inlined_images.iter().enumerate()
                 {
+                    if let Some(alt_range) = &inlined_image.alt_range {
+                        builder.push(
+                            StyleProperty::Brush(BrushPalete::INDENTATION_BRUSH),
+                            alt_range.clone(),
+                        );
+                        builder.push(
+                            StyleProperty::FontStyle(FontStyle::Italic),
+                            alt_range.clone(),
+                        );
+                    }
                     if let Some(data) = &inlined_image.data {
                         builder.push_inline_box(InlineBox {
                             id: image_index as u64,
@@ -269,7 +592,8 @@ impl MarkdownText {
     }
 
     // Loads inlined images and layouts the text with prepared box reserved for
-    // them.
+    // them. Nothing is done when the layout for the same width and theme is
+    // already built, the extra styles are expected to stay the same.
     pub fn load_and_layout_text(
         &mut self,
         text_ctx: &mut TextContext,
@@ -277,8 +601,14 @@ impl MarkdownText {
         extra_styles: &[(StyleProperty<BrushIndex>, Range<usize>)],
         width: f64,
     ) {
-        self.load_images(text_ctx.svg_ctx);
+        let theme_generation = text_ctx.theme.generation;
+        if self.is_layout_valid(width, theme_generation) {
+            return;
+        }
+        self.load_images(text_ctx.svg_ctx, width);
         self.build_layout(text_ctx, extra_default_styles, extra_styles, width);
+        self.layout_width = width;
+        self.layout_theme_generation = theme_generation;
     }
 
     pub fn draw_text(
@@ -303,4 +633,133 @@ impl MarkdownText {
     pub fn height(&self) -> Height {
         self.text.height()
     }
+
+    /// Width of the laid out text ignoring the wrapping width.
+    pub fn full_width(&self) -> Width {
+        self.text.full_width()
+    }
+
+    /// Distance of the first line baseline from the top of the text.
+    pub fn baseline_offset(&self) -> f64 {
+        self.text.baseline_offset()
+    }
+
+    pub fn align(&mut self, container_width: Width, alignment: Alignment) {
+        self.text
+            .align(Some(container_width as f32), alignment, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use masonry::core::BrushIndex;
+    use parley::FontContext;
+    use usvg::fontdb;
+
+    use super::{
+        load_image,
+        styles::{MarkerKind, TextMarker},
+        InlinedImage, MarkdownText,
+    };
+    use crate::{
+        markdown::{
+            context::{LayoutContext, SvgContext, TextContext},
+            image_cache::ImageCache,
+        },
+        theme::Theme,
+    };
+
+    fn svg_context() -> SvgContext {
+        let fontdb = Arc::new(fontdb::Database::new());
+        SvgContext::new(fontdb, 100.0, ImageCache::new())
+    }
+
+    #[test]
+    fn load_base64_png_data_uri() {
+        let url = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+        let image = load_image(url, &svg_context()).unwrap();
+        assert_eq!(image.dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn load_url_encoded_svg_data_uri() {
+        let url = "data:image/svg+xml;charset=utf-8,%3Csvg xmlns=%22http://www.w3.org/2000/svg%22 width=%222%22 height=%223%22%3E%3C/svg%3E";
+        let image = load_image(url, &svg_context()).unwrap();
+        assert_eq!(image.dimensions(), (2, 3));
+    }
+
+    #[test]
+    fn insert_alt_text_shifts_ranges() {
+        let image = |index| {
+            InlinedImage::new(
+                "missing.png".to_string(),
+                "alt".to_string(),
+                None,
+                index,
+            )
+        };
+        let mut text = MarkdownText::new(
+            "ab cd".to_string(),
+            vec![
+                TextMarker {
+                    start_pos: 0,
+                    end_pos: 2,
+                    kind: MarkerKind::Bold,
+                },
+                TextMarker {
+                    start_pos: 3,
+                    end_pos: 5,
+                    kind: MarkerKind::Link {
+                        url: "url".to_string(),
+                    },
+                },
+            ],
+            vec![image(2), image(2), image(5)],
+        );
+        text.insert_alt_text(1);
+        assert_eq!(text.plain_text(), "abalt cd");
+        assert_eq!(text.markers[0].start_pos..text.markers[0].end_pos, 0..2);
+        assert_eq!(text.markers[1].start_pos..text.markers[1].end_pos, 6..8);
+        assert_eq!(text.inlined_images[0].text_index, 2);
+        assert_eq!(text.inlined_images[1].alt_range, Some(2..5));
+        assert_eq!(text.inlined_images[2].text_index, 8);
+    }
+
+    #[test]
+    fn layout_is_cached() {
+        let svg_ctx = svg_context();
+        let mut font_ctx = FontContext::new();
+        let mut parley_layout_ctx = parley::LayoutContext::<BrushIndex>::new();
+        let mut layout_ctx =
+            LayoutContext::new(&mut font_ctx, &mut parley_layout_ctx);
+        let mut theme = Theme::dark_default();
+        let mut text = MarkdownText::new("some text".to_string(), vec![], vec![]);
+        let mut layout = |text: &mut MarkdownText, theme: &Theme, width| {
+            let mut text_ctx = TextContext::new(&svg_ctx, &mut layout_ctx, theme);
+            text.load_and_layout_text(&mut text_ctx, &[], &[], width);
+        };
+
+        assert!(!text.is_layout_valid(100.0, theme.generation));
+        layout(&mut text, &theme, 100.0);
+        assert!(text.is_layout_valid(100.0, theme.generation));
+        assert!(!text.is_layout_valid(200.0, theme.generation));
+
+        layout(&mut text, &theme, 200.0);
+        assert_eq!(text.layout_width, 200.0);
+
+        let old_generation = theme.generation;
+        theme.generation.nudge();
+        assert!(!text.is_layout_valid(200.0, theme.generation));
+        layout(&mut text, &theme, 200.0);
+        assert!(text.is_layout_valid(200.0, theme.generation));
+        assert!(!text.is_layout_valid(200.0, old_generation));
+
+        text.set_text("other text");
+        assert!(!text.is_layout_valid(200.0, theme.generation));
+        layout(&mut text, &theme, 200.0);
+        assert!(text.is_layout_valid(200.0, theme.generation));
+        assert_eq!(text.plain_text(), "other text");
+    }
 }
@@ -1,5 +1,6 @@
 use kurbo::{Size, Vec2};
-use parley::Alignment;
+use masonry::core::BrushIndex;
+use parley::{Alignment, StyleProperty};
 use vello::Scene;
 
 use super::{layouted_text::LayoutedText, styles::BrushPalete};
@@ -8,21 +9,40 @@ use crate::markdown::context::TextContext;
 #[derive(Clone, Debug)]
 pub struct SimpleText {
     text: LayoutedText,
+    // Overrides of the default text styles.
+    brush: Option<BrushIndex>,
+    font_size: Option<f32>,
 }
 
 impl SimpleText {
     pub fn new(text: String) -> SimpleText {
         SimpleText {
             text: LayoutedText::new(text),
+            brush: None,
+            font_size: None,
         }
     }
 
     pub fn empty() -> SimpleText {
         SimpleText {
             text: LayoutedText::new("".into()),
+            brush: None,
+            font_size: None,
         }
     }
 
+    /// Draws the text with the `brush` instead of the default text brush.
+    pub fn with_brush(mut self, brush: BrushIndex) -> Self {
+        self.brush = Some(brush);
+        self
+    }
+
+    /// Uses the font `size` instead of the default text size.
+    pub fn with_font_size(mut self, size: f32) -> Self {
+        self.font_size = Some(size);
+        self
+    }
+
     pub fn build_layout(
         &mut self,
         text_ctx: &mut TextContext,
@@ -34,6 +54,12 @@ impl SimpleText {
             max_advance,
             |builder| {
                 BrushPalete::fill_default_styles(text_ctx.theme, builder);
+                if let Some(brush) = self.brush {
+                    builder.push_default(StyleProperty::Brush(brush));
+                }
+                if let Some(font_size) = self.font_size {
+                    builder.push_default(StyleProperty::FontSize(font_size));
+                }
             },
         );
     }
@@ -62,6 +88,10 @@ impl SimpleText {
         self.text.full_width()
     }
 
+    pub fn baseline_offset(&self) -> f64 {
+        self.text.baseline_offset()
+    }
+
     pub fn align(
         &mut self,
         container_width: Option<f32>,
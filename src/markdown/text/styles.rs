@@ -22,7 +22,9 @@ impl BrushPalete {
                 Brush::just_text(theme.markdown.box_quotation.tip_color),
                 Brush::just_text(theme.markdown.box_quotation.warning_color),
                 Brush::just_text(theme.markdown.box_quotation.caution_color),
-                Brush::just_text(theme.markdown.box_quotation.caution_color),
+                Brush::just_text(theme.markdown.link_color),
+                Brush::just_text(theme.markdown.link_hover_color),
+                Brush::just_text(theme.markdown.code_block_language_label_color),
             ],
         }
     }
@@ -31,8 +33,62 @@ impl BrushPalete {
         &self.palete
     }
 
+    /// Appends the `brush` to the palete and returns its index. The palete is
+    /// recreated when the theme changes, so the brushes have to be registered
+    /// again after that.
+    pub fn register_brush(&mut self, brush: Brush) -> BrushIndex {
+        self.palete.push(brush);
+        BrushIndex(self.palete.len() - 1)
+    }
+
+    pub fn text_brush(&self) -> BrushIndex {
+        BrushPalete::TEXT_BRUSH
+    }
+
+    pub fn code_brush(&self) -> BrushIndex {
+        BrushPalete::CODE_BRUSH
+    }
+
+    pub fn indentation_brush(&self) -> BrushIndex {
+        BrushPalete::INDENTATION_BRUSH
+    }
+
+    pub fn note_brush(&self) -> BrushIndex {
+        BrushPalete::NOTE_BRUSH
+    }
+
+    pub fn important_brush(&self) -> BrushIndex {
+        BrushPalete::IMPORTANT_BRUSH
+    }
+
+    pub fn tip_brush(&self) -> BrushIndex {
+        BrushPalete::TIP_BRUSH
+    }
+
+    pub fn warning_brush(&self) -> BrushIndex {
+        BrushPalete::WARNING_BRUSH
+    }
+
+    pub fn caution_brush(&self) -> BrushIndex {
+        BrushPalete::CAUTION_BRUSH
+    }
+
+    pub fn link_brush(&self) -> BrushIndex {
+        BrushPalete::LINK_BRUSH
+    }
+
+    pub fn link_hover_brush(&self) -> BrushIndex {
+        BrushPalete::LINK_HOVER_BRUSH
+    }
+
+    pub fn code_language_brush(&self) -> BrushIndex {
+        BrushPalete::CODE_LANGUAGE_BRUSH
+    }
+
     // TODO: Maybe enum would be better but it is hard to say how worth it is
     // to dig into this direction.
+    // The brushes created by `new`. Other brushes can be added by
+    // `register_brush`.
     pub const TEXT_BRUSH: BrushIndex = BrushIndex(0);
     pub const CODE_BRUSH: BrushIndex = BrushIndex(1);
     pub const INDENTATION_BRUSH: BrushIndex = BrushIndex(2);
@@ -41,7 +97,9 @@ impl BrushPalete {
     pub const TIP_BRUSH: BrushIndex = BrushIndex(5);
     pub const WARNING_BRUSH: BrushIndex = BrushIndex(6);
     pub const CAUTION_BRUSH: BrushIndex = BrushIndex(7);
-    pub const LINK_COLOR: BrushIndex = BrushIndex(8);
+    pub const LINK_BRUSH: BrushIndex = BrushIndex(8);
+    pub const LINK_HOVER_BRUSH: BrushIndex = BrushIndex(9);
+    pub const CODE_LANGUAGE_BRUSH: BrushIndex = BrushIndex(10);
 
     pub fn fill_default_styles(
         theme: &Theme,
@@ -71,7 +129,7 @@ impl TextMarker {
         theme: &'a Theme,
     ) {
         let rang = self.start_pos..self.end_pos;
-        match self.kind {
+        match &self.kind {
             MarkerKind::Bold => {
                 builder.push(StyleProperty::FontWeight(FontWeight::BOLD), rang)
             }
@@ -91,14 +149,64 @@ impl TextMarker {
                 );
                 builder.push(StyleProperty::Brush(BrushPalete::CODE_BRUSH), rang);
             }
+            MarkerKind::Math => {
+                // TODO: Layout the TeX once there is some math layout engine.
+                builder.push(
+                    StyleProperty::FontStack(
+                        theme.text.monospace_font_stack.clone(),
+                    ),
+                    rang.clone(),
+                );
+                builder
+                    .push(StyleProperty::FontStyle(FontStyle::Italic), rang.clone());
+                builder.push(StyleProperty::Brush(BrushPalete::CODE_BRUSH), rang);
+            }
+            MarkerKind::Superscript => {
+                // TODO: Raise the text above the baseline once parley supports
+                // it. For now the superscript is only smaller.
+                builder.push(
+                    StyleProperty::FontSize(theme.text.text_size as f32 * 0.7),
+                    rang,
+                )
+            }
+            MarkerKind::Link { .. } => {
+                builder.push(StyleProperty::Underline(true), rang.clone());
+                builder.push(
+                    StyleProperty::UnderlineBrush(Some(BrushPalete::LINK_BRUSH)),
+                    rang.clone(),
+                );
+                builder.push(StyleProperty::Brush(BrushPalete::LINK_BRUSH), rang);
+            }
         }
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub enum MarkerKind {
     Bold,
     Italic,
     Strikethrough,
     InlineCode,
+    Math,
+    Superscript,
+    Link { url: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use masonry::core::BrushIndex;
+    use vello::peniko::Color;
+
+    use super::BrushPalete;
+    use crate::{markdown::text::layouted_text::Brush, theme::Theme};
+
+    #[test]
+    fn register_brush_appends() {
+        let mut brush_palete = BrushPalete::new(&Theme::new());
+        let len = brush_palete.palete().len();
+        let index = brush_palete.register_brush(Brush::just_text(Color::WHITE));
+        assert_eq!(index, BrushIndex(len));
+        assert_eq!(brush_palete.palete().len(), len + 1);
+        assert_eq!(brush_palete.code_language_brush(), BrushIndex(len - 1));
+    }
 }
@@ -0,0 +1,81 @@
+use pulldown_cmark::HeadingLevel;
+
+use super::elements::MarkdownContent;
+use crate::layout_flow::LayoutFlow;
+
+/// Entry of the table of contents of a markdown document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TocEntry {
+    pub level: HeadingLevel,
+    pub text: String,
+    /// See [`super::elements::Header::anchor`].
+    pub anchor: String,
+    /// Offset of the header from the top of the document. Valid only after
+    /// the document was laid out.
+    pub offset: f64,
+}
+
+/// Collects all headers of the document in the order they appear. The headers
+/// nested in the quotations, lists and footnotes are included.
+pub fn table_of_contents(flow: &LayoutFlow<MarkdownContent>) -> Vec<TocEntry> {
+    let mut entries = Vec::new();
+    collect_headers(flow, 0.0, &mut entries);
+    entries
+}
+
+fn collect_headers(
+    flow: &LayoutFlow<MarkdownContent>,
+    flow_offset: f64,
+    entries: &mut Vec<TocEntry>,
+) {
+    for (offset, _height, element) in flow.iter_positioned() {
+        let offset = flow_offset + offset;
+        if let MarkdownContent::Header(header) = element {
+            entries.push(TocEntry {
+                level: header.level(),
+                text: header.text().plain_text().to_string(),
+                anchor: header.anchor(),
+                offset,
+            });
+        }
+        for (nested_offset, nested_flow) in element.nested_flows() {
+            collect_headers(nested_flow, offset + nested_offset, entries);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::HeadingLevel;
+
+    use super::table_of_contents;
+    use crate::markdown::parser::parse_markdown;
+
+    #[test]
+    fn nested_headers() {
+        let flow = parse_markdown(
+            "# Title\n\ntext\n\n> ## Quoted\n\n- ### In list\n\n## Last one\n",
+        );
+        let entries = table_of_contents(&flow)
+            .into_iter()
+            .map(|entry| (entry.level, entry.text, entry.anchor))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                (HeadingLevel::H1, "Title".to_string(), "title".to_string()),
+                (HeadingLevel::H2, "Quoted".to_string(), "quoted".to_string()),
+                (
+                    HeadingLevel::H3,
+                    "In list".to_string(),
+                    "in-list".to_string()
+                ),
+                (
+                    HeadingLevel::H2,
+                    "Last one".to_string(),
+                    "last-one".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use kurbo::{Affine, Rect, Shape, Size, Stroke};
+use kurbo::{Affine, Rect, RoundedRect, Shape, Size, Stroke};
 use peniko::{BlendMode, BrushRef, Color, Fill, Font, Image};
 use vello::{DrawGlyphs, Scene};
 
@@ -77,6 +77,30 @@ impl<'a> SizedScene<'a> {
             .stroke(style, transform, brush, brush_transform, shape);
     }
 
+    pub fn draw_rounded_rect<'b>(
+        &mut self,
+        transform: Affine,
+        rect: Rect,
+        radius: f64,
+        brush: impl Into<BrushRef<'b>>,
+    ) {
+        let shape = RoundedRect::from_rect(rect, radius);
+        self.scene
+            .fill(Fill::NonZero, transform, brush, None, &shape);
+    }
+
+    pub fn stroke_rounded_rect<'b>(
+        &mut self,
+        transform: Affine,
+        rect: Rect,
+        radius: f64,
+        stroke: &Stroke,
+        brush: impl Into<BrushRef<'b>>,
+    ) {
+        let shape = RoundedRect::from_rect(rect, radius);
+        self.scene.stroke(stroke, transform, brush, None, &shape);
+    }
+
     pub fn draw_image(&mut self, image: &Image, transform: Affine) {
         self.scene.draw_image(image, transform);
     }
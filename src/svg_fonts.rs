@@ -1,11 +1,18 @@
-use std::sync::{LazyLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::{Arc, LazyLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use usvg::fontdb;
 
+use crate::{
+    config::get_config,
+    utils::{load_font_blobs_dirs, LoadFontOptions},
+};
+
 // I'm not happy with this solution but I guess it is better then nothing...
 // Would be better if is was passed into widgets by masonry but I don't know
 // if is it possible.
-static THEME: LazyLock<RwLock<fontdb::Database>> = LazyLock::new(|| {
+// The fonts are loaded from the directories of the editor settings, so the
+// database has to be used only after the settings are loaded.
+static SVG_FONTS: LazyLock<RwLock<Arc<fontdb::Database>>> = LazyLock::new(|| {
     let mut fontdb = fontdb::Database::default();
     fontdb.load_system_fonts();
 
@@ -18,16 +25,25 @@ static THEME: LazyLock<RwLock<fontdb::Database>> = LazyLock::new(|| {
 
     // FIXME: FIXME FIXME: I'm not sure about the legality of the fonts
     // being committed in the repo. Needs to be resolved ASAP.
-
-    // TDDO: This should point to some asset dir.
-    fontdb.load_fonts_dir("./fonts/");
-    RwLock::new(fontdb)
+    let font_dirs = get_config().font_dirs();
+    for blob in load_font_blobs_dirs(&font_dirs, LoadFontOptions::default()) {
+        fontdb.load_font_data(blob);
+    }
+    RwLock::new(Arc::new(fontdb))
 });
 
-pub fn get_svg_fonts<'a>() -> RwLockReadGuard<'a, fontdb::Database> {
-    (*THEME).read().unwrap()
+pub fn get_svg_fonts<'a>() -> RwLockReadGuard<'a, Arc<fontdb::Database>> {
+    (*SVG_FONTS).read().unwrap()
+}
+
+/// Returns the shared font database. The widgets keep the returned `Arc`, so
+/// the database is loaded only once for all of them.
+pub fn get_svg_fonts_arc() -> Arc<fontdb::Database> {
+    get_svg_fonts().clone()
 }
 
-pub fn get_svg_fonts_but<'a>() -> RwLockWriteGuard<'a, fontdb::Database> {
-    (*THEME).write().unwrap()
+/// The database can be modified with `Arc::make_mut`. The widgets which
+/// already hold the database keep the old version.
+pub fn get_svg_fonts_but<'a>() -> RwLockWriteGuard<'a, Arc<fontdb::Database>> {
+    (*SVG_FONTS).write().unwrap()
 }
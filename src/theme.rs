@@ -1,5 +1,11 @@
-use std::sync::{LazyLock, RwLock, RwLockReadGuard};
+mod config;
 
+use std::{
+    borrow::Cow,
+    sync::{LazyLock, RwLock, RwLockReadGuard},
+};
+
+pub use config::{PartialMarkdowTheme, PartialTextTheme, PartialTheme};
 use parley::{FontFamily, FontStack, GenericFamily};
 use vello::peniko::Color;
 
@@ -7,9 +13,26 @@ use crate::generation::Generation;
 
 static THEME: LazyLock<RwLock<Theme>> = LazyLock::new(|| RwLock::new(Theme::new()));
 
+// The user's overrides applied by `PartialTheme::apply`, they are kept when
+// the preset is switched.
+static THEME_OVERRIDES: LazyLock<RwLock<PartialTheme>> =
+    LazyLock::new(|| RwLock::new(PartialTheme::default()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemePreset {
+    Dark,
+    Light,
+    HighContrast,
+}
+
 #[derive(Debug, Clone)]
 pub struct Theme {
+    // The preset the theme is based on (the user's overrides may be applied
+    // on top of it). `None` for the custom themes (e.g. loaded from a TOML
+    // file).
+    pub preset: Option<ThemePreset>,
     pub scale: f32,
+    pub background_color: Color,
     pub text: TextTheme,
     pub markdown: MarkdowTheme,
     pub generation: Generation,
@@ -24,13 +47,103 @@ impl Theme {
         let mut generation = Generation::default();
         generation.nudge();
         Theme {
+            preset: Some(ThemePreset::Dark),
             scale: 1.0,
+            background_color: Color::from_rgb8(0x1E, 0x1E, 0x1E),
             text: TextTheme::new(),
             markdown: MarkdowTheme::new(),
             generation,
             multi_click_register_time: 0.25,
         }
     }
+
+    /// Light text on a dark background.
+    pub fn dark_default() -> Theme {
+        Theme::new()
+    }
+
+    /// Dark text on a light background.
+    pub fn light_default() -> Theme {
+        let mut theme = Theme::new();
+        theme.preset = Some(ThemePreset::Light);
+        theme.background_color = Color::from_rgb8(0xFA, 0xFA, 0xF8);
+
+        let text = &mut theme.text;
+        text.text_color = Color::from_rgb8(0x1A, 0x1A, 0x1A);
+        text.monospace_text_color = Color::from_rgb8(0xB3, 0x4D, 0x00);
+        text.cursor_color = Color::from_rgb8(0x30, 0x30, 0x30);
+        text.selection_color = Color::from_rgb8(0xAD, 0xD6, 0xFF);
+        text.ghost_text_color = Color::from_rgb8(0xA0, 0xA0, 0xA0);
+
+        let markdown = &mut theme.markdown;
+        markdown.standard_quotation.color = Color::from_rgb8(0xC0, 0xC0, 0xC0);
+        markdown.box_quotation.note_color = Color::from_rgb8(0x80, 0x80, 0x80);
+        markdown.horizontal_line_color = Color::from_rgb8(0xC0, 0xC0, 0xC0);
+        markdown.link_color = Color::from_rgb8(0x00, 0x5C, 0xC5);
+        markdown.link_hover_color = Color::from_rgb8(0x00, 0x3A, 0x80);
+        markdown.search_match_color = Color::from_rgb8(0xFF, 0xE5, 0x8F);
+        markdown.current_search_match_color = Color::from_rgb8(0xFF, 0xB0, 0x00);
+        markdown.scrollbar_color = Color::from_rgb8(0xB0, 0xB0, 0xB0);
+        markdown.table_border_color = Color::from_rgb8(0xC0, 0xC0, 0xC0);
+        theme
+    }
+
+    /// White text on a black background. All text colors have contrast ratio
+    /// at least 4.5:1 (WCAG 2.1 AA).
+    pub fn high_contrast() -> Theme {
+        let mut theme = Theme::new();
+        theme.preset = Some(ThemePreset::HighContrast);
+        theme.background_color = Color::BLACK;
+
+        let text = &mut theme.text;
+        text.text_color = Color::WHITE;
+        text.monospace_text_color = Color::from_rgb8(0x00, 0xFF, 0xFF);
+        text.cursor_color = Color::WHITE;
+        text.selection_color = Color::from_rgb8(0x00, 0x3A, 0x8C);
+        text.ghost_text_color = Color::from_rgb8(0xB0, 0xB0, 0xB0);
+
+        let markdown = &mut theme.markdown;
+        markdown.standard_quotation.color = Color::from_rgb8(0xC0, 0xC0, 0xC0);
+        let box_quotation = &mut markdown.box_quotation;
+        box_quotation.note_color = Color::WHITE;
+        box_quotation.important_color = Color::from_rgb8(0xFF, 0xA0, 0x00);
+        box_quotation.tip_color = Color::from_rgb8(0x00, 0xE0, 0xFF);
+        box_quotation.warning_color = Color::from_rgb8(0xFF, 0xFF, 0x00);
+        box_quotation.caution_color = Color::from_rgb8(0xFF, 0x44, 0x44);
+        markdown.horizontal_line_color = Color::from_rgb8(0xC0, 0xC0, 0xC0);
+        markdown.link_color = Color::from_rgb8(0xFF, 0xFF, 0x00);
+        markdown.link_hover_color = Color::from_rgb8(0xFF, 0xFF, 0xA0);
+        markdown.search_match_color = Color::from_rgb8(0x5C, 0x4B, 0x12);
+        markdown.current_search_match_color = Color::from_rgb8(0x8A, 0x4B, 0x00);
+        markdown.scrollbar_color = Color::from_rgb8(0xC0, 0xC0, 0xC0);
+        markdown.table_border_color = Color::from_rgb8(0xC0, 0xC0, 0xC0);
+        markdown.code_block_language_label_color =
+            Color::from_rgb8(0xC0, 0xC0, 0xC0);
+        theme
+    }
+
+    /// Returns the contrast ratio of the colors as defined by WCAG 2.1. The
+    /// result is between 1.0 (same colors) and 21.0 (black and white). The
+    /// alpha channel is ignored.
+    pub fn contrast_ratio(foreground: Color, background: Color) -> f64 {
+        let foreground = relative_luminance(foreground);
+        let background = relative_luminance(background);
+        (foreground.max(background) + 0.05) / (foreground.min(background) + 0.05)
+    }
+}
+
+// Relative luminance of the sRGB color by the W3C formula.
+fn relative_luminance(color: Color) -> f64 {
+    let linear = |component: f32| {
+        let component = component as f64;
+        if component <= 0.04045 {
+            component / 12.92
+        } else {
+            ((component + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let [r, g, b, _] = color.components;
+    0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
 }
 
 #[derive(Debug, Clone)]
@@ -43,6 +156,7 @@ pub struct TextTheme {
     pub monospace_text_size: u32,
     pub cursor_color: Color,
     pub selection_color: Color,
+    pub ghost_text_color: Color,
 }
 
 impl TextTheme {
@@ -58,10 +172,34 @@ impl TextTheme {
             monospace_text_color: Color::from_rgb8(0xFF, 0x8C, 0x00),
             text_size: 16,
             monospace_text_size: 16,
-            cursor_color: Color::from_rgb8(0x55, 0x55, 0x55),
-            selection_color: Color::from_rgb8(0x15, 0x15, 0x15),
+            cursor_color: Color::from_rgb8(0xE0, 0xE0, 0xE0),
+            selection_color: Color::from_rgb8(0x26, 0x4F, 0x78),
+            ghost_text_color: Color::from_rgb8(0x80, 0x80, 0x80),
         }
     }
+
+    /// Sets the font to the first available of the `families`. The generic
+    /// sans-serif family is used when none of them is available.
+    pub fn with_font_families(mut self, families: &[&str]) -> TextTheme {
+        self.font_stack = font_stack(families, GenericFamily::SansSerif);
+        self
+    }
+
+    /// Sets the monospace font to the first available of the `families`. The
+    /// generic monospace family is used when none of them is available.
+    pub fn with_monospace_font_families(mut self, families: &[&str]) -> TextTheme {
+        self.monospace_font_stack = font_stack(families, GenericFamily::Monospace);
+        self
+    }
+}
+
+fn font_stack(families: &[&str], fallback: GenericFamily) -> FontStack<'static> {
+    let families = families
+        .iter()
+        .map(|family| FontFamily::Named(Cow::Owned(family.to_string())))
+        .chain([FontFamily::Generic(fallback)])
+        .collect::<Vec<_>>();
+    FontStack::List(Cow::Owned(families))
 }
 
 #[derive(Debug, Clone)]
@@ -69,7 +207,13 @@ pub struct MarkdowTheme {
     pub bullet_list_indentation: f64,
     pub numbered_list_indentation: f64,
     pub list_after_indentation: f64,
+    /// Extra indentation of a list per level of nesting. The nested lists are
+    /// already aligned with the text column of their parent item.
+    pub list_nesting_indentation: f64,
     pub list_top_margin: f64,
+    pub definition_indent: f64,
+    pub task_unchecked_sign: String,
+    pub task_checked_sign: String,
 
     pub standard_quotation: StandardQuotation,
     pub box_quotation: BoxQuotation,
@@ -83,10 +227,26 @@ pub struct MarkdowTheme {
 
     pub horizontal_code_block_margin: f64,
     pub code_block_margin: f64,
+    // The language of the code block is shown in its top right corner.
+    pub code_block_language_label_size: f32,
+    pub code_block_language_label_color: Color,
 
     pub header_line_height: f32,
 
     pub link_color: Color,
+    // Color of the link under the pointer.
+    pub link_hover_color: Color,
+
+    pub search_match_color: Color,
+    pub current_search_match_color: Color,
+
+    pub scrollbar_width: f64,
+    pub scrollbar_color: Color,
+
+    pub table_margin: f64,
+    pub table_cell_padding: f64,
+    pub table_border_width: f64,
+    pub table_border_color: Color,
 }
 
 impl MarkdowTheme {
@@ -96,7 +256,11 @@ impl MarkdowTheme {
             bullet_list_indentation: 10.0,
             numbered_list_indentation: 10.0,
             list_after_indentation: 5.0,
+            list_nesting_indentation: 0.0,
             list_top_margin: 10.0,
+            definition_indent: 20.0,
+            task_unchecked_sign: "󰄱".to_string(),
+            task_checked_sign: "󰄲".to_string(),
 
             standard_quotation: StandardQuotation {
                 margine: Margin {
@@ -150,10 +314,24 @@ impl MarkdowTheme {
 
             horizontal_code_block_margin: 10.0,
             code_block_margin: 10.0,
+            code_block_language_label_size: 10.0,
+            code_block_language_label_color: Color::from_rgb8(0x80, 0x80, 0x80),
 
             header_line_height: 2.0,
 
-            link_color: Color::from_rgb8(0x00, 0x4D, 0x00),
+            link_color: Color::from_rgb8(0x4D, 0x9D, 0xF0),
+            link_hover_color: Color::from_rgb8(0x8C, 0xC4, 0xFF),
+
+            search_match_color: Color::from_rgb8(0x5C, 0x4B, 0x12),
+            current_search_match_color: Color::from_rgb8(0xA0, 0x6E, 0x00),
+
+            scrollbar_width: 6.0,
+            scrollbar_color: Color::from_rgb8(0x5A, 0x5A, 0x5A),
+
+            table_margin: 10.0,
+            table_cell_padding: 5.0,
+            table_border_width: 1.0,
+            table_border_color: Color::from_rgb8(0x4D, 0x4D, 0x4D),
         }
     }
 }
@@ -223,3 +401,165 @@ pub struct BoxQuotation {
 pub fn get_theme<'a>() -> RwLockReadGuard<'a, Theme> {
     (*THEME).read().unwrap()
 }
+
+/// Replaces the current theme. The generation of the new theme always differs
+/// from the replaced one, so the widgets caching values derived from the
+/// theme can detect the change by comparing the generations.
+pub fn set_theme(mut theme: Theme) {
+    let mut current = (*THEME).write().unwrap();
+    theme.generation = current.generation;
+    theme.generation.nudge();
+    *current = theme;
+}
+
+/// Replaces the current theme by the `preset` with the user's overrides (see
+/// [`PartialTheme::apply`]) applied on top of it. The scale of the current
+/// theme is kept.
+pub fn set_theme_preset(preset: ThemePreset) {
+    let scale = get_theme().scale;
+    let base = match preset {
+        ThemePreset::Dark => Theme::dark_default(),
+        ThemePreset::Light => Theme::light_default(),
+        ThemePreset::HighContrast => Theme::high_contrast(),
+    };
+    let mut theme = base.merge(&(*THEME_OVERRIDES).read().unwrap());
+    theme.preset = Some(preset);
+    theme.scale = scale;
+    set_theme(theme);
+}
+
+/// Switches between the dark and light presets. Custom and high contrast
+/// themes are replaced by the light preset.
+pub fn toggle_dark_light() {
+    let preset = get_theme().preset;
+    set_theme_preset(match preset {
+        Some(ThemePreset::Light) => ThemePreset::Dark,
+        _ => ThemePreset::Light,
+    });
+}
+
+const MIN_SCALE: f32 = 0.5;
+const MAX_SCALE: f32 = 3.0;
+const ZOOM_STEP: f32 = 0.1;
+
+pub fn zoom_in() {
+    update_scale(|scale| scale + ZOOM_STEP);
+}
+
+pub fn zoom_out() {
+    update_scale(|scale| scale - ZOOM_STEP);
+}
+
+pub fn reset_zoom() {
+    update_scale(|_| 1.0);
+}
+
+/// Zooms by the key pressed together with the control key (`+`, `-` and
+/// `0`). Returns `false` when the key is not a zoom key.
+pub fn zoom_by_key(key: &str) -> bool {
+    match key {
+        "+" | "=" => zoom_in(),
+        "-" => zoom_out(),
+        "0" => reset_zoom(),
+        _ => return false,
+    }
+    true
+}
+
+fn update_scale<F>(f: F)
+where
+    F: FnOnce(f32) -> f32,
+{
+    let mut theme = (*THEME).write().unwrap();
+    let scale = f(theme.scale).clamp(MIN_SCALE, MAX_SCALE);
+    if scale != theme.scale {
+        theme.scale = scale;
+        theme.generation.nudge();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use vello::peniko::Color;
+
+    use super::{get_theme, set_theme, PartialTextTheme, PartialTheme, Theme};
+
+    // Serializes the tests changing the global theme.
+    static GLOBAL_THEME: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn set_theme_nudges_generation() {
+        let _guard = GLOBAL_THEME.lock().unwrap();
+        let original = get_theme().clone();
+        let mut theme = original.clone();
+        theme.text.text_color = Color::from_rgb8(0x01, 0x02, 0x03);
+        set_theme(theme);
+
+        {
+            let theme = get_theme();
+            assert_eq!(theme.text.text_color, Color::from_rgb8(0x01, 0x02, 0x03));
+            assert!(theme.generation.is_newer_than(original.generation));
+        }
+        set_theme(original);
+    }
+
+    #[test]
+    fn merge_into_local_theme() {
+        let base = Theme::dark_default();
+        let overlay = PartialTheme {
+            text: Some(PartialTextTheme {
+                text_color: Some(Color::from_rgb8(0x01, 0x02, 0x03)),
+                ..PartialTextTheme::default()
+            }),
+            ..PartialTheme::default()
+        };
+        let theme = base.merge(&overlay);
+        assert_eq!(theme.text.text_color, Color::from_rgb8(0x01, 0x02, 0x03));
+        assert_eq!(theme.background_color, base.background_color);
+        // Only `set_theme` changes the generation.
+        assert_eq!(theme.generation, base.generation);
+    }
+
+    #[test]
+    fn contrast_ratio_bounds() {
+        let ratio = Theme::contrast_ratio(Color::WHITE, Color::BLACK);
+        assert!((ratio - 21.0).abs() < 1e-6);
+        let ratio = Theme::contrast_ratio(Color::BLACK, Color::WHITE);
+        assert!((ratio - 21.0).abs() < 1e-6);
+        let gray = Color::from_rgb8(0x80, 0x80, 0x80);
+        assert!((Theme::contrast_ratio(gray, gray) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn high_contrast_colors_pass_aa() {
+        let theme = Theme::high_contrast();
+        let background = theme.background_color;
+        let text = &theme.text;
+        let markdown = &theme.markdown;
+        let box_quotation = &markdown.box_quotation;
+        let pairs = [
+            (text.text_color, background),
+            (text.monospace_text_color, background),
+            (text.ghost_text_color, background),
+            (markdown.link_color, background),
+            (markdown.link_hover_color, background),
+            (box_quotation.note_color, background),
+            (box_quotation.important_color, background),
+            (box_quotation.tip_color, background),
+            (box_quotation.warning_color, background),
+            (box_quotation.caution_color, background),
+            (text.text_color, text.selection_color),
+            (text.text_color, markdown.search_match_color),
+            (text.text_color, markdown.current_search_match_color),
+        ];
+        for (foreground, background) in pairs {
+            let ratio = Theme::contrast_ratio(foreground, background);
+            assert!(
+                ratio >= 4.5,
+                "{foreground:?} on {background:?} has contrast ratio {ratio}"
+            );
+        }
+    }
+}
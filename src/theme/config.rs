@@ -0,0 +1,345 @@
+//! Partial theme overrides loaded from a TOML file. All fields are optional,
+//! the missing ones keep the values of the theme the overrides are merged
+//! into (see [`Theme::merge`]).
+//!
+//! ```toml
+//! scale = 1.0
+//!
+//! [text]
+//! font_family = ["JetBrains Mono", "Fira Code"]
+//! text_color = "#f0f0ea"
+//!
+//! [markdown]
+//! paragraph_top_margin = 10.0
+//! link_color = "#4d9df0"
+//! ```
+
+use std::{
+    borrow::Cow,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{eyre, Result};
+use parley::FontStack;
+use serde::{Deserialize, Deserializer};
+use vello::peniko::Color;
+
+use super::{get_theme, set_theme, Theme, THEME_OVERRIDES};
+
+/// Deserializes the color in the `#rrggbb` or `#rrggbbaa` format.
+fn hex_color<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Color>, D::Error> {
+    let str = String::deserialize(deserializer)?;
+    parse_color(&str)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Deserializes the font stack in the CSS `font-family` format.
+fn font_stack_source<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<FontStack<'static>>, D::Error> {
+    let str = String::deserialize(deserializer)?;
+    Ok(Some(FontStack::Source(Cow::Owned(str))))
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PartialTheme {
+    pub scale: Option<f32>,
+    #[serde(deserialize_with = "hex_color")]
+    pub background_color: Option<Color>,
+    pub multi_click_register_time: Option<f64>,
+    pub text: Option<PartialTextTheme>,
+    pub markdown: Option<PartialMarkdowTheme>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PartialTextTheme {
+    // The families are tried in order, see `TextTheme::with_font_families`.
+    // They take precedence over the `font_stack`.
+    pub font_family: Option<Vec<String>>,
+    pub monospace_font_family: Option<Vec<String>>,
+    #[serde(deserialize_with = "font_stack_source")]
+    pub font_stack: Option<FontStack<'static>>,
+    #[serde(deserialize_with = "font_stack_source")]
+    pub monospace_font_stack: Option<FontStack<'static>>,
+    #[serde(deserialize_with = "hex_color")]
+    pub text_color: Option<Color>,
+    #[serde(deserialize_with = "hex_color")]
+    pub monospace_text_color: Option<Color>,
+    pub text_size: Option<u32>,
+    pub monospace_text_size: Option<u32>,
+    #[serde(deserialize_with = "hex_color")]
+    pub cursor_color: Option<Color>,
+    #[serde(deserialize_with = "hex_color")]
+    pub selection_color: Option<Color>,
+    #[serde(deserialize_with = "hex_color")]
+    pub ghost_text_color: Option<Color>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PartialMarkdowTheme {
+    pub bullet_list_indentation: Option<f64>,
+    pub numbered_list_indentation: Option<f64>,
+    pub list_after_indentation: Option<f64>,
+    pub list_nesting_indentation: Option<f64>,
+    pub list_top_margin: Option<f64>,
+    pub definition_indent: Option<f64>,
+    pub task_unchecked_sign: Option<String>,
+    pub task_checked_sign: Option<String>,
+    pub paragraph_top_margin: Option<f64>,
+    pub horizontal_line_height: Option<f64>,
+    pub horizontal_line_vertical_margin: Option<f64>,
+    pub horizontal_line_horizontal_margin: Option<f64>,
+    #[serde(deserialize_with = "hex_color")]
+    pub horizontal_line_color: Option<Color>,
+    pub horizontal_code_block_margin: Option<f64>,
+    pub code_block_margin: Option<f64>,
+    pub code_block_language_label_size: Option<f32>,
+    #[serde(deserialize_with = "hex_color")]
+    pub code_block_language_label_color: Option<Color>,
+    pub header_line_height: Option<f32>,
+    #[serde(deserialize_with = "hex_color")]
+    pub link_color: Option<Color>,
+    #[serde(deserialize_with = "hex_color")]
+    pub link_hover_color: Option<Color>,
+    #[serde(deserialize_with = "hex_color")]
+    pub search_match_color: Option<Color>,
+    #[serde(deserialize_with = "hex_color")]
+    pub current_search_match_color: Option<Color>,
+    pub scrollbar_width: Option<f64>,
+    #[serde(deserialize_with = "hex_color")]
+    pub scrollbar_color: Option<Color>,
+    pub table_margin: Option<f64>,
+    pub table_cell_padding: Option<f64>,
+    pub table_border_width: Option<f64>,
+    #[serde(deserialize_with = "hex_color")]
+    pub table_border_color: Option<Color>,
+}
+
+// Overwrites the fields of the `target` by the fields set in the `overlay`.
+macro_rules! apply {
+    ($target:expr, $overlay:expr, $($field:ident),* $(,)?) => {
+        $(
+            if let Some(value) = &$overlay.$field {
+                $target.$field = value.clone();
+            }
+        )*
+    };
+}
+
+impl Theme {
+    /// Returns the path of the user's theme configuration:
+    /// `$XDG_CONFIG_HOME/wrenched/config.toml` (or `~/.config/...`) on Unix
+    /// and `%APPDATA%\wrenched\config.toml` on Windows.
+    pub fn user_config_path() -> Option<PathBuf> {
+        let config_dir = if cfg!(windows) {
+            PathBuf::from(env::var_os("APPDATA")?)
+        } else {
+            match env::var_os("XDG_CONFIG_HOME") {
+                Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+                _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+            }
+        };
+        Some(config_dir.join("wrenched").join("config.toml"))
+    }
+
+    pub fn from_toml<P: AsRef<Path>>(path: P) -> Result<Theme> {
+        Ok(Theme::new().merge(&PartialTheme::from_toml(path)?))
+    }
+
+    pub fn from_toml_str(content: &str) -> Result<Theme> {
+        Ok(Theme::new().merge(&PartialTheme::from_toml_str(content)?))
+    }
+
+    /// Returns a copy of the theme with the fields set in the `overlay`
+    /// replaced. The merged theme is a custom one (it has no preset).
+    pub fn merge(&self, overlay: &PartialTheme) -> Theme {
+        let mut theme = self.clone();
+        theme.preset = None;
+        apply!(
+            theme,
+            overlay,
+            scale,
+            background_color,
+            multi_click_register_time
+        );
+        if let Some(text) = &overlay.text {
+            apply!(
+                theme.text,
+                text,
+                font_stack,
+                monospace_font_stack,
+                text_color,
+                monospace_text_color,
+                text_size,
+                monospace_text_size,
+                cursor_color,
+                selection_color,
+                ghost_text_color,
+            );
+            let as_strs = |families: &[String]| {
+                families.iter().map(String::as_str).collect::<Vec<_>>()
+            };
+            if let Some(families) = &text.font_family {
+                theme.text = theme.text.with_font_families(&as_strs(families));
+            }
+            if let Some(families) = &text.monospace_font_family {
+                theme.text =
+                    theme.text.with_monospace_font_families(&as_strs(families));
+            }
+        }
+        if let Some(markdown) = &overlay.markdown {
+            apply!(
+                theme.markdown,
+                markdown,
+                bullet_list_indentation,
+                numbered_list_indentation,
+                list_after_indentation,
+                list_nesting_indentation,
+                list_top_margin,
+                definition_indent,
+                task_unchecked_sign,
+                task_checked_sign,
+                paragraph_top_margin,
+                horizontal_line_height,
+                horizontal_line_vertical_margin,
+                horizontal_line_horizontal_margin,
+                horizontal_line_color,
+                horizontal_code_block_margin,
+                code_block_margin,
+                code_block_language_label_size,
+                code_block_language_label_color,
+                header_line_height,
+                link_color,
+                link_hover_color,
+                search_match_color,
+                current_search_match_color,
+                scrollbar_width,
+                scrollbar_color,
+                table_margin,
+                table_cell_padding,
+                table_border_width,
+                table_border_color,
+            );
+        }
+        theme
+    }
+}
+
+impl PartialTheme {
+    pub fn from_toml<P: AsRef<Path>>(path: P) -> Result<PartialTheme> {
+        let content = fs::read_to_string(&path)?;
+        PartialTheme::from_toml_str(&content)
+    }
+
+    pub fn from_toml_str(content: &str) -> Result<PartialTheme> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// Merges the overrides into the current theme (see [`Theme::merge`] and
+    /// [`set_theme`]). The overrides are kept for the presets selected later
+    /// (see [`super::set_theme_preset`]).
+    pub fn apply(&self) {
+        *(*THEME_OVERRIDES).write().unwrap() = self.clone();
+        let theme = get_theme().merge(self);
+        set_theme(theme);
+    }
+}
+
+fn parse_color(str: &str) -> Result<Color> {
+    let hex = str
+        .strip_prefix('#')
+        .ok_or_else(|| eyre!("Color {str:?} doesn't start with '#'"))?;
+    if !hex.is_ascii() || (hex.len() != 6 && hex.len() != 8) {
+        return Err(eyre!("Color {str:?} is not in #rrggbb or #rrggbbaa format"));
+    }
+    let component = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16);
+    let alpha = if hex.len() == 8 { component(6)? } else { 0xFF };
+    Ok(Color::from_rgba8(
+        component(0)?,
+        component(2)?,
+        component(4)?,
+        alpha,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use vello::peniko::Color;
+
+    use super::{parse_color, PartialTheme, Theme};
+
+    #[test]
+    fn parse_hex_colors() {
+        assert_eq!(
+            parse_color("#f0f0ea").unwrap(),
+            Color::from_rgb8(0xf0, 0xf0, 0xea)
+        );
+        assert_eq!(
+            parse_color("#10203040").unwrap(),
+            Color::from_rgba8(0x10, 0x20, 0x30, 0x40)
+        );
+        assert!(parse_color("f0f0ea").is_err());
+        assert!(parse_color("#f0f0e").is_err());
+        assert!(parse_color("#gggggg").is_err());
+    }
+
+    #[test]
+    fn missing_fields_keep_defaults() {
+        let theme = Theme::from_toml_str(
+            "[markdown]\nlink_color = \"#4d9df0\"\nparagraph_top_margin = 4.0\n",
+        )
+        .unwrap();
+        let default = Theme::new();
+        assert_eq!(
+            theme.markdown.link_color,
+            Color::from_rgb8(0x4d, 0x9d, 0xf0)
+        );
+        assert_eq!(theme.markdown.paragraph_top_margin, 4.0);
+        assert_eq!(theme.text.text_color, default.text.text_color);
+        assert_eq!(
+            theme.markdown.code_block_margin,
+            default.markdown.code_block_margin
+        );
+    }
+
+    #[test]
+    fn merge_keeps_the_base_theme() {
+        let overlay = PartialTheme::from_toml_str(
+            "scale = 1.5\n[markdown]\nlink_color = \"#4d9df0\"\n",
+        )
+        .unwrap();
+        let base = Theme::light_default();
+        let theme = base.merge(&overlay);
+        assert_eq!(theme.scale, 1.5);
+        assert_eq!(
+            theme.markdown.link_color,
+            Color::from_rgb8(0x4d, 0x9d, 0xf0)
+        );
+        assert_eq!(theme.text.text_color, base.text.text_color);
+        assert_eq!(theme.background_color, base.background_color);
+        assert_eq!(theme.preset, None);
+    }
+
+    #[test]
+    fn font_family_list() {
+        let theme = Theme::from_toml_str(
+            "[text]\nfont_family = [\"JetBrains Mono\", \"Fira Code\"]\n",
+        )
+        .unwrap();
+        let expected = Theme::new()
+            .text
+            .with_font_families(&["JetBrains Mono", "Fira Code"]);
+        assert_eq!(theme.text.font_stack, expected.font_stack);
+        assert_eq!(
+            theme.text.monospace_font_stack,
+            expected.monospace_font_stack
+        );
+    }
+}
@@ -1,27 +1,66 @@
 use std::path::Path;
 
-pub fn load_font_blobs_dir<P>(path: P) -> std::io::Result<Vec<Vec<u8>>>
+use tracing::warn;
+use walkdir::WalkDir;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LoadFontOptions {
+    /// Load also the fonts in the subdirectories.
+    pub recursive: bool,
+}
+
+pub fn load_font_blobs_dir<P>(
+    path: P,
+    opts: LoadFontOptions,
+) -> std::io::Result<Vec<Vec<u8>>>
 where
     P: AsRef<Path>,
 {
-    let paths = std::fs::read_dir(path)?;
+    let max_depth = if opts.recursive { usize::MAX } else { 1 };
+    let entries = WalkDir::new(path)
+        .min_depth(1)
+        .max_depth(max_depth)
+        .follow_links(true)
+        .sort_by_file_name();
     let mut blobs = Vec::new();
-    for entry in paths {
+    for entry in entries {
         let entry = entry?;
-        if !entry.metadata()?.is_file() {
+        if !entry.file_type().is_file() {
             continue;
         }
         let path = entry.path();
         if path
             .extension()
             .and_then(|ext| ext.to_str())
-            .map(|ext| !["ttf", "otf", "ttc", "otc"].contains(&ext))
+            .map(|ext| {
+                !["ttf", "otf", "ttc", "otc"]
+                    .iter()
+                    .any(|font_ext| ext.eq_ignore_ascii_case(font_ext))
+            })
             .unwrap_or(true)
         {
             continue;
         }
-        let font_data = std::fs::read(&path)?;
+        let font_data = std::fs::read(path)?;
         blobs.push(font_data);
     }
     Ok(blobs)
 }
+
+/// Loads the fonts of all `dirs`. The directories which can't be read are
+/// reported and skipped.
+pub fn load_font_blobs_dirs<P>(dirs: &[P], opts: LoadFontOptions) -> Vec<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    let mut blobs = Vec::new();
+    for dir in dirs {
+        match load_font_blobs_dir(dir, opts) {
+            Ok(dir_blobs) => blobs.extend(dir_blobs),
+            Err(err) => {
+                warn!("Fonts from {:?} can't be loaded: {err}", dir.as_ref())
+            }
+        }
+    }
+    blobs
+}